@@ -0,0 +1,11 @@
+// Generates the `kintai.v1` messages and service for `kintai grpc` from the
+// published schema, with a vendored protoc so no system install is needed.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // SAFETY: build scripts are single-threaded.
+    unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/kintai/v1/kintai.proto"], &["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! User-defined command aliases from the `[alias]` config section, e.g.
+//!
+//! ```toml
+//! [alias]
+//! lunch = "break-start --kind lunch"
+//! wfh = ["start", "--location", "remote"]
+//! ```
+//!
+//! An alias is expanded in place of the subcommand, and any further
+//! arguments are appended to it. Built-in commands and their aliases
+//! (`in`, `out`, `brb`, `back`) cannot be redefined.
+
+use clap::Command;
+use std::ffi::OsString;
+
+use crate::config::{Config, Value};
+
+/// Global options that take a value as the next argument.
+pub const VALUE_OPTIONS: [&str; 7] = [
+    "-l",
+    "--log",
+    "-u",
+    "--user",
+    "--overnight",
+    "--now",
+    "--home",
+];
+
+fn expansion(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Str(s) => Some(s.split_whitespace().map(str::to_string).collect()),
+        Value::List(items) => items
+            .iter()
+            .map(|i| i.as_str().map(str::to_string))
+            .collect(),
+        _ => None,
+    }
+}
+
+/// `args` with the subcommand replaced by its alias expansion, if it is one.
+pub fn expand(args: Vec<OsString>, config: &Config, cli: &Command) -> Vec<OsString> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i).and_then(|a| a.to_str()) {
+        if !arg.starts_with('-') {
+            break;
+        }
+        i += if VALUE_OPTIONS.contains(&arg) { 2 } else { 1 };
+    }
+    let Some(name) = args.get(i).and_then(|a| a.to_str()) else {
+        return args;
+    };
+    let Some(value) = config
+        .entries("alias")
+        .into_iter()
+        .find_map(|(k, v)| (k == name).then_some(v))
+    else {
+        return args;
+    };
+    if cli.find_subcommand(name).is_some() {
+        eprintln!("warning: alias `{name}` is ignored; it shadows a built-in command");
+        return args;
+    }
+    let Some(expansion) = expansion(value).filter(|e| !e.is_empty()) else {
+        eprintln!("warning: alias `{name}` must be a string or a list of strings");
+        return args;
+    };
+    let mut expanded = args[..i].to_vec();
+    expanded.extend(expansion.into_iter().map(OsString::from));
+    expanded.extend_from_slice(&args[i + 1..]);
+    expanded
+}
@@ -0,0 +1,374 @@
+//! Archiving closed periods: `kintai archive --before 2023-01` moves older
+//! events out of the log into a gzip (or zstd) file in `<log>.archive/`, next to a
+//! JSON summary of the archived months, and leaves a `type=archive`
+//! tombstone. Commands then only see the archived events when run with
+//! `--include-archived`.
+
+use clap::ValueEnum;
+use flate2::{Compression, write::GzEncoder};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use crate::{
+    chain,
+    close::closed_months,
+    config::Config,
+    event::{LogEvent, LogFormat, decompress, new_id, parse_events, parse_line},
+    privacy,
+    report::{format_hm, plural},
+    session::build_sessions,
+    state,
+    stats::parse_month,
+};
+
+/// How archive files are compressed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    /// `.log.gz`
+    Gzip,
+    /// `.log.zst`, written and read with the `zstd` command
+    Zstd,
+}
+
+impl ArchiveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Gzip => ".log.gz",
+            ArchiveFormat::Zstd => ".log.zst",
+        }
+    }
+}
+
+const EXTENSIONS: [&str; 2] = [".log.gz", ".log.zst"];
+
+static INCLUDE: OnceLock<bool> = OnceLock::new();
+
+/// Makes reading a log also read its archives (`--include-archived`).
+pub fn set_include(include: bool) {
+    let _ = INCLUDE.set(include);
+}
+
+pub fn included() -> bool {
+    INCLUDE.get().copied().unwrap_or(false)
+}
+
+/// `<log>.archive/`, where the archives of `log` are kept.
+pub fn dir(log: &Path) -> PathBuf {
+    let mut name = log.as_os_str().to_os_string();
+    name.push(".archive");
+    PathBuf::from(name)
+}
+
+/// The archive files of `log`, oldest first.
+fn files(log: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir(log)) {
+        Ok(entries) => entries
+            .filter_map(|e| Some(e.ok()?.path()))
+            .filter(|p| {
+                let name = p.to_string_lossy();
+                EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+            })
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    files.sort();
+    Ok(files)
+}
+
+/// The archived events of `log`, oldest first.
+pub fn read(log: &Path) -> anyhow::Result<Vec<LogEvent>> {
+    let mut events = Vec::new();
+    for path in files(log)? {
+        events.extend(read_one(&path)?);
+    }
+    Ok(events)
+}
+
+/// Minutes per month and user of `events`, as kept next to an archive.
+fn summary(events: Vec<LogEvent>, count: usize) -> Value {
+    let mut months: BTreeMap<(String, Option<String>), i64> = BTreeMap::new();
+    for s in build_sessions(events) {
+        *months
+            .entry((s.date[..7].to_string(), s.user.clone()))
+            .or_default() += s.minutes();
+    }
+    let months: Vec<Value> = months
+        .into_iter()
+        .map(|((month, user), minutes)| json!({ "month": month, "user": user, "minutes": minutes }))
+        .collect();
+    json!({ "events": count, "months": months })
+}
+
+/// Moves the events dated before `before` (`YYYY-MM`), and corrections that
+/// refer to them, from `log` into a new archive. Every month archived must
+/// be closed, unless `force`. Without `yes`, only reports what would move.
+pub fn archive(
+    log: &Path,
+    before: &str,
+    format: Option<ArchiveFormat>,
+    force: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    parse_month(before)?;
+    let format = match format {
+        Some(f) => f,
+        None => match Config::load()?.str("archive.compression") {
+            None => ArchiveFormat::Gzip,
+            Some(v) => ArchiveFormat::from_str(v, true).map_err(|_| {
+                anyhow::anyhow!("archive.compression must be \"gzip\" or \"zstd\", not {v:?}")
+            })?,
+        },
+    };
+    let text = fs::read_to_string(log)?;
+    let events: Vec<LogEvent> = text.lines().filter_map(parse_line).collect();
+
+    let mut archived_ids = HashSet::new();
+    let mut archived: Vec<&str> = Vec::new();
+    let mut kept: Vec<&str> = Vec::new();
+    let mut months = BTreeSet::new();
+    for line in text.lines() {
+        let old = parse_line(line).filter(|e| {
+            let old = e.ts.get(..7).is_some_and(|m| m < before);
+            let orphan = e.field("ref").is_some_and(|r| archived_ids.contains(r));
+            old || orphan
+        });
+        match old {
+            Some(e) => {
+                archived_ids.insert(e.id().to_string());
+                months.insert(e.ts[..7].to_string());
+                archived.push(line);
+            }
+            None => kept.push(line),
+        }
+    }
+    if archived.is_empty() {
+        println!("No events before {before}.");
+        return Ok(());
+    }
+    let closed = closed_months(&events);
+    let open: Vec<&str> = months
+        .iter()
+        .map(String::as_str)
+        .filter(|m| !closed.contains_key(*m))
+        .collect();
+    if !open.is_empty() && !force {
+        anyhow::bail!(
+            "{} not closed yet; close {} first (`kintai close <YYYY-MM>`) or use --force",
+            open.join(", "),
+            if open.len() == 1 { "it" } else { "them" }
+        );
+    }
+    let (first, last) = (months.first().unwrap(), months.last().unwrap());
+    let name = format!("{first}_{last}");
+    if !yes {
+        println!(
+            "Would archive {} event{} from {first} to {last} into {}; rerun with --yes to move them.",
+            archived.len(),
+            plural(archived.len()),
+            dir(log)
+                .join(format!("{name}{}", format.extension()))
+                .display()
+        );
+        return Ok(());
+    }
+
+    let dir = dir(log);
+    fs::create_dir_all(&dir)?;
+    let file = format!("{name}{}", format.extension());
+    let path = dir.join(&file);
+    if EXTENSIONS
+        .iter()
+        .any(|ext| dir.join(format!("{name}{ext}")).exists())
+    {
+        anyhow::bail!(
+            "an archive of {first} to {last} already exists in {}",
+            dir.display()
+        );
+    }
+    let mut data = archived.join("\n");
+    data.push('\n');
+    write_compressed(&path, format, data.as_bytes())?;
+    let archived_events = archived.iter().filter_map(|l| parse_line(l)).collect();
+    let summary = summary(archived_events, archived.len());
+    fs::write(
+        dir.join(format!("{name}.summary.json")),
+        serde_json::to_string_pretty(&summary)? + "\n",
+    )?;
+
+    let id = new_id();
+    let count = archived.len().to_string();
+    let mut fields = vec![
+        ("id", id.as_str()),
+        ("before", before),
+        ("count", &count),
+        ("file", &file),
+    ];
+    let prev = chain::enabled().then(|| chain::prev_of(&kept.join("\n")));
+    fields.extend(prev.as_deref().map(|p| ("prev", p)));
+    let format = match LogFormat::sniff(&text) {
+        Some(f) => f,
+        None => LogFormat::configured()?,
+    };
+    let tombstone = format.format(&state::now().to_rfc3339(), "archive", &fields, None);
+    let mut new = kept.join("\n");
+    if !new.is_empty() {
+        new.push('\n');
+    }
+    new.push_str(&tombstone);
+    new.push('\n');
+
+    // Write the new log next to the old one first, so a crash cannot lose both.
+    let tmp = log.with_extension("archive-tmp");
+    {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(new.as_bytes())?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, log)?;
+    if prev.is_some() {
+        chain::save_head(log, &tombstone)?;
+    }
+    println!(
+        "Archived {} event{} from {first} to {last} into {}.",
+        archived.len(),
+        plural(archived.len()),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Lists the archives of `log` with the hours of each month, from the
+/// summaries kept next to them.
+pub fn list(log: &Path) -> anyhow::Result<()> {
+    let files = files(log)?;
+    if files.is_empty() {
+        println!("No archives of {}.", log.display());
+        return Ok(());
+    }
+    println!("| archive | month | user | hours |");
+    println!("|---------|-------|------|-------|");
+    for path in files {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let name = EXTENSIONS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+            .unwrap_or(&name);
+        let summary_path = path.with_file_name(format!("{name}.summary.json"));
+        let summary: Value = match fs::read_to_string(&summary_path) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(_) => {
+                let events = read_one(&path)?;
+                let count = events.len();
+                summary(events, count)
+            }
+        };
+        for m in summary["months"].as_array().into_iter().flatten() {
+            println!(
+                "| {name} | {} | {} | {} |",
+                m["month"].as_str().unwrap_or_default(),
+                m["user"].as_str().unwrap_or_default(),
+                format_hm(m["minutes"].as_i64().unwrap_or(0) as f64 / 60.0)
+            );
+        }
+    }
+    println!();
+    Ok(())
+}
+
+/// Removes the events `purges` picks from every archive of `log` (when
+/// `write`, otherwise only counts them), for `privacy purge`. Rewritten
+/// archives get a new summary; emptied ones are deleted. Old contents are
+/// overwritten with zeros first. Returns the number of events removed.
+pub fn purge(
+    log: &Path,
+    purges: &mut impl FnMut(&LogEvent) -> bool,
+    write: bool,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for path in files(log)? {
+        let mut text = String::new();
+        decompress(fs::File::open(&path)?)?.read_to_string(&mut text)?;
+        let mut kept: Vec<&str> = Vec::new();
+        let mut removed = 0;
+        for line in text.lines() {
+            if parse_line(line).is_some_and(|e| purges(&e)) {
+                removed += 1;
+            } else {
+                kept.push(line);
+            }
+        }
+        count += removed;
+        if !write || removed == 0 {
+            continue;
+        }
+        let file = path.file_name().unwrap_or_default().to_string_lossy();
+        let (name, format) = if let Some(name) = file.strip_suffix(".log.zst") {
+            (name.to_string(), ArchiveFormat::Zstd)
+        } else {
+            let name = file.strip_suffix(".log.gz").unwrap_or(&file);
+            (name.to_string(), ArchiveFormat::Gzip)
+        };
+        let summary_path = path.with_file_name(format!("{name}.summary.json"));
+        privacy::shred(&path)?;
+        if kept.is_empty() {
+            fs::remove_file(&path)?;
+            if summary_path.exists() {
+                fs::remove_file(&summary_path)?;
+            }
+            continue;
+        }
+        let mut data = kept.join("\n");
+        data.push('\n');
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".purge-tmp");
+        let tmp = PathBuf::from(tmp);
+        write_compressed(&tmp, format, data.as_bytes())?;
+        fs::rename(&tmp, &path)?;
+        let events: Vec<LogEvent> = kept.iter().filter_map(|l| parse_line(l)).collect();
+        let summary = summary(events, kept.len());
+        if summary_path.exists() {
+            privacy::shred(&summary_path)?;
+        }
+        fs::write(
+            &summary_path,
+            serde_json::to_string_pretty(&summary)? + "\n",
+        )?;
+    }
+    Ok(count)
+}
+
+fn read_one(path: &Path) -> anyhow::Result<Vec<LogEvent>> {
+    parse_events(decompress(fs::File::open(path)?)?)
+}
+
+fn write_compressed(path: &Path, format: ArchiveFormat, data: &[u8]) -> anyhow::Result<()> {
+    match format {
+        ArchiveFormat::Gzip => {
+            let mut gz = GzEncoder::new(fs::File::create(path)?, Compression::default());
+            gz.write_all(data)?;
+            gz.finish()?.sync_all()?;
+        }
+        ArchiveFormat::Zstd => {
+            let mut child = Command::new("zstd")
+                .args(["-q", "-o"])
+                .arg(path)
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| anyhow::anyhow!("cannot run zstd to write the archive: {e}"))?;
+            child.stdin.take().unwrap().write_all(data)?;
+            let status = child.wait()?;
+            if !status.success() {
+                anyhow::bail!("zstd failed to write {} ({status})", path.display());
+            }
+        }
+    }
+    Ok(())
+}
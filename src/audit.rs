@@ -0,0 +1,68 @@
+//! Audit trail: events that were not recorded live by the person clocking in,
+//! so reviewers can tell after-the-fact edits from ordinary clock events.
+
+use std::{fmt::Write, path::PathBuf};
+
+use crate::event::{LogEvent, filter_user, read_events};
+
+/// Why an event is worth a reviewer's attention, or `None` for a plain live
+/// clock event.
+fn reason(e: &LogEvent) -> Option<String> {
+    let mut why = Vec::new();
+    match e.ty.as_str() {
+        "amend" => why.push(format!("amends {}", e.field("ref").unwrap_or("?"))),
+        "void" => why.push(format!("voids {}", e.field("ref").unwrap_or("?"))),
+        "purge" => why.push(format!(
+            "purged {} events before {}",
+            e.field("count").unwrap_or("?"),
+            e.field("before").unwrap_or("?")
+        )),
+        "close" => why.push(format!("closes {}", e.field("month").unwrap_or("?"))),
+        _ => {}
+    }
+    if e.field("correction") == Some("1") {
+        why.push("recorded into a closed month".to_string());
+    }
+    if let Some(auto) = e.field("auto") {
+        why.push(format!("automatic ({auto})"));
+    }
+    if e.field("manual") == Some("1") {
+        why.push("added manually".to_string());
+    }
+    if let Some(source) = e.field("source") {
+        why.push(format!("via {source}"));
+    }
+    (!why.is_empty()).then(|| why.join(", "))
+}
+
+pub fn audit(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: Option<&str>,
+) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let mut out = String::new();
+    writeln!(out, "| ts | user | type | id | reason | content |").unwrap();
+    writeln!(out, "|----|------|------|----|--------|---------|").unwrap();
+    let mut count = 0;
+    for e in &events {
+        if month.is_some_and(|m| !e.ts.starts_with(m)) {
+            continue;
+        }
+        let Some(why) = reason(e) else { continue };
+        count += 1;
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {why} | {} |",
+            e.ts,
+            e.user().unwrap_or_default(),
+            e.ty,
+            e.id(),
+            e.content.as_deref().unwrap_or_default()
+        )
+        .unwrap();
+    }
+    println!("{out}");
+    println!("{count} of {} events were not recorded live.", events.len());
+    Ok(())
+}
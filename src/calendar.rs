@@ -0,0 +1,242 @@
+//! Which days are workdays: Monday to Friday unless `calendar.workdays`
+//! says otherwise, minus holidays: the dates listed in `calendar.holidays`,
+//! Japan's national holidays with `calendar.national = "jp"`, and company
+//! closures such as `calendar.closures = ["2026-12-29..2027-01-03"]`. A standard
+//! working pattern (`[calendar.pattern]`) instead gives each weekday its
+//! hours, and optionally where it is worked:
+//!
+//! ```toml
+//! [calendar.pattern]
+//! mon = 7
+//! tue = 7
+//! wed = "7 remote"
+//! thu = 7
+//! fri = 5.5
+//! ```
+//!
+//! Days left out (or `0`) are not workdays.
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::{
+    config::{Config, Value},
+    holidays,
+};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// A weekday of the standard pattern.
+#[derive(Clone, Default)]
+struct PatternDay {
+    hours: f64,
+    location: Option<String>,
+}
+
+impl PatternDay {
+    /// Parses `7`, or `"7h remote"` (hours, `h` optional, then a location).
+    fn parse(value: &Value) -> anyhow::Result<PatternDay> {
+        let (hours, location) = match value {
+            Value::Num(n) => (*n, None),
+            Value::Str(s) => {
+                let (hours, location) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+                let hours = hours.trim_end_matches('h').parse().map_err(|_| {
+                    anyhow::anyhow!("expected hours and an optional location, got {s:?}")
+                })?;
+                let location = Some(location.trim().to_string()).filter(|l| !l.is_empty());
+                (hours, location)
+            }
+            other => anyhow::bail!("expected hours such as 7 or \"7 remote\", got {other}"),
+        };
+        Ok(PatternDay { hours, location })
+    }
+}
+
+/// Parses a closure, `YYYY-MM-DD..YYYY-MM-DD` or a single `YYYY-MM-DD`.
+fn parse_closure(s: &str) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let (a, b) = s.split_once("..").unwrap_or((s, s));
+    let parse = |d: &str| NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok();
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) if a <= b => Ok((a, b)),
+        _ => anyhow::bail!("expected YYYY-MM-DD..YYYY-MM-DD or YYYY-MM-DD, got {s:?}"),
+    }
+}
+
+pub struct Calendar {
+    workdays: [bool; 7],
+    holidays: Vec<NaiveDate>,
+    /// Whether Japan's national holidays are days off.
+    national: bool,
+    /// Closure periods, first and last day inclusive.
+    closures: Vec<(NaiveDate, NaiveDate)>,
+    /// `[calendar.pattern]`, when set.
+    pattern: Option<[PatternDay; 7]>,
+}
+
+impl Calendar {
+    pub fn load(config: &Config) -> anyhow::Result<Calendar> {
+        let mut workdays = [true, true, true, true, true, false, false];
+        if let Some(days) = config.strs("calendar.workdays") {
+            for (i, name) in WEEKDAYS.iter().enumerate() {
+                workdays[i] = days.iter().any(|d| d.eq_ignore_ascii_case(name));
+            }
+        }
+        let mut pattern = None;
+        if !config.entries("calendar.pattern").is_empty() {
+            let mut days: [PatternDay; 7] = Default::default();
+            for (i, name) in WEEKDAYS.iter().enumerate() {
+                let key = format!("calendar.pattern.{name}");
+                if let Some(v) = config.get(&key) {
+                    days[i] = PatternDay::parse(v).map_err(|e| anyhow::anyhow!("{key}: {e}"))?;
+                }
+                workdays[i] = days[i].hours > 0.0;
+            }
+            pattern = Some(days);
+        }
+        let holidays = config
+            .strs("calendar.holidays")
+            .unwrap_or_default()
+            .iter()
+            .map(|d| {
+                NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|_| {
+                    anyhow::anyhow!("calendar.holidays: expected YYYY-MM-DD, got {d:?}")
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let national = match config.str("calendar.national") {
+            None => false,
+            Some(c) if c.eq_ignore_ascii_case("jp") => true,
+            Some(other) => anyhow::bail!("calendar.national must be \"jp\", not {other:?}"),
+        };
+        let closures = config
+            .strs("calendar.closures")
+            .unwrap_or_default()
+            .iter()
+            .map(|c| parse_closure(c).map_err(|e| anyhow::anyhow!("calendar.closures: {e}")))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Calendar {
+            workdays,
+            holidays,
+            national,
+            closures,
+            pattern,
+        })
+    }
+
+    /// Whether `date` is a listed holiday, a national holiday (with
+    /// `calendar.national`) or in a closure.
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.contains(&date)
+            || self.closures.iter().any(|(a, b)| (*a..=*b).contains(&date))
+            || (self.national && holidays::is_japanese(date))
+    }
+
+    pub fn is_workday(&self, date: NaiveDate) -> bool {
+        self.workdays[date.weekday().num_days_from_monday() as usize] && !self.is_holiday(date)
+    }
+
+    /// Whether a standard working pattern is configured.
+    pub fn has_pattern(&self) -> bool {
+        self.pattern.is_some()
+    }
+
+    fn pattern_day(&self, date: NaiveDate) -> Option<&PatternDay> {
+        let days = self.pattern.as_ref()?;
+        Some(&days[date.weekday().num_days_from_monday() as usize])
+    }
+
+    /// Hours the pattern expects on `date` (0 on days off and holidays), or
+    /// `None` without a pattern.
+    pub fn expected_hours(&self, date: NaiveDate) -> Option<f64> {
+        let day = self.pattern_day(date)?;
+        Some(if self.is_workday(date) {
+            day.hours
+        } else {
+            0.0
+        })
+    }
+
+    /// Where the pattern says `date` is worked, if anywhere in particular.
+    pub fn location(&self, date: NaiveDate) -> Option<&str> {
+        self.pattern_day(date)?.location.as_deref()
+    }
+
+    /// Workdays after `date` up to the end of its month.
+    pub fn remaining_in_month(&self, date: NaiveDate) -> u32 {
+        self.remaining_days(date).count() as u32
+    }
+
+    /// Hours the pattern expects after `date` up to the end of its month.
+    pub fn remaining_hours(&self, date: NaiveDate) -> Option<f64> {
+        self.pattern.as_ref()?;
+        Some(
+            self.remaining_days(date)
+                .filter_map(|d| self.expected_hours(d))
+                .sum(),
+        )
+    }
+
+    fn remaining_days(&self, date: NaiveDate) -> impl Iterator<Item = NaiveDate> + '_ {
+        date.iter_days()
+            .skip(1)
+            .take_while(move |d| d.month() == date.month())
+            .filter(|d| self.is_workday(*d))
+    }
+}
+
+/// How reports number weeks (`weeks.numbering`): ISO 8601 weeks, or US-style
+/// weeks where week 1 contains January 1st and weeks begin on `weeks.start`
+/// (Sunday unless set).
+pub enum Weeks {
+    Iso,
+    Us(Weekday),
+}
+
+/// The day weeks begin on (`weeks.start`), Sunday unless set.
+pub fn week_start(config: &Config) -> anyhow::Result<Weekday> {
+    match config.str("weeks.start") {
+        None => Ok(Weekday::Sun),
+        Some(day) => WEEKDAYS
+            .iter()
+            .position(|d| d.eq_ignore_ascii_case(day))
+            .and_then(|i| Weekday::try_from(i as u8).ok())
+            .ok_or_else(|| anyhow::anyhow!("weeks.start must be a day like \"mon\", not {day:?}")),
+    }
+}
+
+impl Weeks {
+    /// `None` unless `weeks.numbering` is set.
+    pub fn load(config: &Config) -> anyhow::Result<Option<Weeks>> {
+        let start = week_start(config)?;
+        match config.str("weeks.numbering") {
+            None => Ok(None),
+            Some("iso") => Ok(Some(Weeks::Iso)),
+            Some("us") => Ok(Some(Weeks::Us(start))),
+            Some(other) => {
+                anyhow::bail!("weeks.numbering must be \"iso\" or \"us\", not {other:?}")
+            }
+        }
+    }
+
+    /// Week label such as `2026-W41`.
+    pub fn label(&self, date: NaiveDate) -> String {
+        match self {
+            Weeks::Iso => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Weeks::Us(start) => {
+                let jan1 = NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap();
+                let offset =
+                    (jan1.weekday().num_days_from_monday() + 7 - start.num_days_from_monday()) % 7;
+                format!("{}-W{:02}", date.year(), (date.ordinal0() + offset) / 7 + 1)
+            }
+        }
+    }
+
+    /// Week label of a session date (`YYYY/MM/DD`).
+    pub fn label_of(&self, date: &str) -> String {
+        NaiveDate::parse_from_str(date, "%Y/%m/%d")
+            .map(|d| self.label(d))
+            .unwrap_or_default()
+    }
+}
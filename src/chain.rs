@@ -0,0 +1,215 @@
+//! Optional hash chain over the log (`log.chain = true` in the config). Each
+//! appended event carries `prev=`, the SHA-256 of the line before it, and the
+//! hash of the newest line is kept outside the log, so `kintai verify` can
+//! detect edited, inserted, removed or truncated events.
+
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    event::{LogEvent, parse_line},
+    paths, state,
+};
+
+pub fn enabled() -> bool {
+    Config::load()
+        .ok()
+        .and_then(|c| c.bool("log.chain"))
+        .unwrap_or(false)
+}
+
+pub fn digest(line: &str) -> String {
+    Sha256::digest(line.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn last_line(text: &str) -> &str {
+    text.lines()
+        .rev()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("")
+}
+
+/// The `prev=` value for a line appended after `text`.
+pub fn prev_of(text: &str) -> String {
+    digest(last_line(text))
+}
+
+/// The `prev=` value for the next line appended to `log`.
+pub fn prev(log: &Path) -> anyhow::Result<String> {
+    match fs::read_to_string(log) {
+        Ok(text) => Ok(prev_of(&text)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(digest("")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Where the hash of the newest line is kept: with the data rather than the
+/// cache, since losing it would hide a truncated log.
+fn head_path(log: &Path) -> PathBuf {
+    paths::data_dir().join(format!("head-{}", state::log_key(log, None)))
+}
+
+/// Where older versions kept the head.
+fn old_head_path(log: &Path) -> PathBuf {
+    paths::cache_dir().join(format!("head-{}", state::log_key(log, None)))
+}
+
+/// Remembers `line` as the newest line of `log`.
+pub fn save_head(log: &Path, line: &str) -> anyhow::Result<()> {
+    write_head(log, &digest(line))
+}
+
+fn write_head(log: &Path, head: &str) -> anyhow::Result<()> {
+    let path = head_path(log);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, head)?;
+    match fs::remove_file(old_head_path(log)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The recorded head of `log`, moving one left in the cache by an older
+/// version to where it is kept now.
+fn load_head(log: &Path) -> anyhow::Result<Option<String>> {
+    match fs::read_to_string(head_path(log)) {
+        Ok(head) => return Ok(Some(head)),
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        Err(_) => {}
+    }
+    match fs::read_to_string(old_head_path(log)) {
+        Ok(head) => {
+            write_head(log, &head)?;
+            Ok(Some(head))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A `purge` or `archive` tombstone chained to the line before it, with the
+/// number of broken links its removals can still account for: one when it
+/// removed a run of old events, up to the events removed when only one
+/// user's were.
+struct Tombstone {
+    line: usize,
+    event: LogEvent,
+    breaks: usize,
+}
+
+impl Tombstone {
+    fn new(line: usize, event: LogEvent) -> Option<Tombstone> {
+        let count: usize = event.field("count")?.parse().ok()?;
+        event.field("before")?;
+        let breaks = if event.user().is_some() {
+            count
+        } else {
+            count.min(1)
+        };
+        Some(Tombstone {
+            line,
+            event,
+            breaks,
+        })
+    }
+
+    /// Whether the removals account for line `n`, `event`, linking to a line
+    /// that is gone; if so, that is one break fewer left to account for.
+    fn explains(&mut self, n: usize, event: &LogEvent) -> bool {
+        let before = self.event.field("before").unwrap_or_default();
+        let kept = event.ts.get(..10).is_some_and(|d| d >= before)
+            || self.event.user().is_some_and(|u| event.user() != Some(u));
+        let explained = self.line > n && self.breaks > 0 && kept;
+        if explained {
+            self.breaks -= 1;
+        }
+        explained
+    }
+}
+
+pub fn verify(log: &Path) -> anyhow::Result<()> {
+    let text = fs::read_to_string(log)?;
+    let lines: Vec<(usize, &str)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(n, l)| (n + 1, l))
+        .collect();
+    // Tombstones of purges and archives that are chained like any event;
+    // the events they removed leave breaks before them.
+    let mut tombstones = Vec::new();
+    let mut previous = "";
+    for &(n, line) in &lines {
+        if let Some(e) = parse_line(line)
+            && matches!(e.ty.as_str(), "purge" | "archive")
+            && e.field("prev") == Some(digest(previous).as_str())
+        {
+            tombstones.extend(Tombstone::new(n, e));
+        }
+        previous = line;
+    }
+
+    let mut problems = Vec::new();
+    let mut chained = 0;
+    let mut previous = "";
+    for &(n, line) in &lines {
+        let event = parse_line(line);
+        match event.as_ref().and_then(|e| e.field("prev")) {
+            Some(prev)
+                if prev != digest(previous)
+                    && !event
+                        .as_ref()
+                        .is_some_and(|e| tombstones.iter_mut().any(|t| t.explains(n, e))) =>
+            {
+                problems.push(format!("line {n}: does not follow the line before it"));
+                chained += 1;
+            }
+            Some(_) => chained += 1,
+            None if chained > 0 => problems.push(format!("line {n}: event without prev=")),
+            None => {}
+        }
+        previous = line;
+    }
+
+    match load_head(log)? {
+        Some(head) if chained > 0 && head.trim() != digest(previous) => problems.push(
+            "last line is not the last event recorded; the log was truncated or its end edited"
+                .to_string(),
+        ),
+        Some(_) => {}
+        None if chained > 0 => problems.push(format!(
+            "no recorded head at {}; truncation cannot be checked (it is recorded on this machine as events are added)",
+            head_path(log).display()
+        )),
+        None => {}
+    }
+
+    if chained == 0 {
+        anyhow::bail!(
+            "{} has no chained events (set log.chain = true in the config)",
+            log.display()
+        );
+    }
+    if !problems.is_empty() {
+        for p in &problems {
+            println!("{p}");
+        }
+        anyhow::bail!(
+            "{} failed verification ({} problems)",
+            log.display(),
+            problems.len()
+        );
+    }
+    println!("{}: {chained} chained events, intact.", log.display());
+    Ok(())
+}
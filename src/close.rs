@@ -0,0 +1,126 @@
+//! Month closing. `kintai close YYYY-MM` appends a `close` event; events that
+//! later land in a closed month are refused unless forced, in which case they
+//! are tagged `correction=1`.
+
+use chrono::NaiveDate;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    calendar::Weeks,
+    config::Config,
+    display::Formats,
+    event::{self, LogEvent, filter_user, record_event_at, user_field},
+    report::{sessions_table, summary_table},
+    session::build_sessions,
+    state,
+};
+
+pub struct Closure {
+    pub ts: String,
+    pub by: Option<String>,
+}
+
+/// Closed months (`YYYY-MM`) and who closed them; a later close of the same
+/// month replaces an earlier one.
+pub fn closed_months(events: &[LogEvent]) -> BTreeMap<String, Closure> {
+    let mut closed = BTreeMap::new();
+    for e in events.iter().filter(|e| e.ty == "close") {
+        if let Some(month) = e.field("month") {
+            closed.insert(
+                month.to_string(),
+                Closure {
+                    ts: e.ts.clone(),
+                    by: e.field("by").map(str::to_string),
+                },
+            );
+        }
+    }
+    closed
+}
+
+fn read_log(log: &Path, user: Option<&str>) -> anyhow::Result<Vec<LogEvent>> {
+    Ok(filter_user(event::read_log(log)?, user))
+}
+
+/// Checks whether an event at `ts` may be recorded. Returns the extra fields
+/// to attach: `correction=1` when a closed month is written to with `force`.
+pub fn guard(
+    log: &Path,
+    user: Option<&str>,
+    ts: &str,
+    force: bool,
+) -> anyhow::Result<Vec<(&'static str, &'static str)>> {
+    let month = &ts[..7];
+    let closed = closed_months(&read_log(log, user)?);
+    let Some(c) = closed.get(month) else {
+        return Ok(Vec::new());
+    };
+    if !force {
+        anyhow::bail!(
+            "{month} was closed at {}{}; use --force to record a correction",
+            c.ts,
+            c.by.as_deref()
+                .map(|b| format!(" by {b}"))
+                .unwrap_or_default()
+        );
+    }
+    Ok(vec![("correction", "1")])
+}
+
+/// The name recorded as `by=`: the kintai user, else the login name.
+fn closer(user: Option<&str>) -> Option<String> {
+    user.map(str::to_string)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .filter(|u| !u.is_empty())
+}
+
+pub fn close(log: &Path, user: Option<&str>, month: &str, force: bool) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a month as YYYY-MM, got {month:?}"))?;
+    let events = read_log(log, user)?;
+    if let Some(c) = closed_months(&events).get(month)
+        && !force
+    {
+        anyhow::bail!(
+            "{month} is already closed (at {}); use --force to close it again",
+            c.ts
+        );
+    }
+    let st = state::TrackingState::from_events(events.clone());
+    if st
+        .session_start
+        .is_some_and(|s| s.format("%Y-%m").to_string() == month)
+    {
+        eprintln!("warning: a session started in {month} is still running and is not included");
+    }
+
+    let prefix = month.replace('-', "/");
+    let sessions: Vec<_> = build_sessions(events)
+        .into_iter()
+        .filter(|s| s.date.starts_with(&prefix))
+        .collect();
+    let config = Config::load()?;
+    let weeks = Weeks::load(&config)?;
+    let formats = Formats::load(&config)?;
+
+    let ts = state::now().to_rfc3339();
+    let by = closer(user);
+    let mut fields = user_field(user);
+    fields.push(("month", month));
+    if let Some(b) = &by {
+        fields.push(("by", b));
+    }
+    record_event_at(Some(log), &ts, "close", &fields, None)?;
+
+    println!(
+        "{}",
+        sessions_table(&sessions, &[], weeks.as_ref(), &formats)
+    );
+    println!("{}", summary_table(&sessions, None, &formats, None));
+    println!(
+        "Closed {month}{} at {ts}.",
+        by.map(|b| format!(" by {b}")).unwrap_or_default()
+    );
+    Ok(())
+}
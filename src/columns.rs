@@ -0,0 +1,364 @@
+//! Computed columns defined under `[columns]`, e.g.
+//! `overtime = "max(hours - 8, 0)"`. Each column is evaluated once per worked
+//! day; a month's value is the sum of its days.
+//!
+//! Expressions use numbers, `+ - * /`, parentheses, the functions `max`,
+//! `min`, `round`, `floor`, `ceil` and `abs`, other columns by name, and
+//! these values of the day: `hours`, `minutes`, `sessions` (count),
+//! `breaks` (break minutes), `workday` (1 on workdays of `[calendar]`, else
+//! 0) and `rate` (the `--rate` given, else 0).
+
+use chrono::NaiveDate;
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use crate::{
+    calendar::Calendar,
+    config::Config,
+    display::{self, Formats, HourStyle},
+    event::{filter_user, read_events},
+    report::format_hours,
+    session::{Session, build_sessions, users},
+};
+
+enum Expr {
+    Num(f64),
+    Var(String),
+    Neg(Box<Expr>),
+    Op(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+const FUNCTIONS: [&str; 6] = ["max", "min", "round", "floor", "ceil", "abs"];
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        match self.peek() {
+            Some(got) if got == c => {
+                self.chars.next();
+                Ok(())
+            }
+            Some(got) => anyhow::bail!("expected {c:?}, found {got:?}"),
+            None => anyhow::bail!("expected {c:?} at the end"),
+        }
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expr(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            left = Expr::Op(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    /// `factor (('*' | '/') factor)*`
+    fn term(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            left = Expr::Op(op, Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> anyhow::Result<Expr> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut num = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    num.push(c);
+                }
+                Ok(Expr::Num(
+                    num.parse()
+                        .map_err(|_| anyhow::anyhow!("invalid number {num:?}"))?,
+                ))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                if self.peek() != Some('(') {
+                    return Ok(Expr::Var(name));
+                }
+                if !FUNCTIONS.contains(&name.as_str()) {
+                    anyhow::bail!("unknown function {name:?}");
+                }
+                self.chars.next();
+                let mut args = vec![self.expr()?];
+                while self.peek() == Some(',') {
+                    self.chars.next();
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => anyhow::bail!("unexpected {c:?}"),
+            None => anyhow::bail!("unexpected end"),
+        }
+    }
+}
+
+fn parse(text: &str) -> anyhow::Result<Expr> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let e = parser.expr()?;
+    match parser.peek() {
+        None => Ok(e),
+        Some(c) => anyhow::bail!("unexpected {c:?}"),
+    }
+}
+
+/// The columns of `[columns]`, in name order.
+pub struct Columns {
+    columns: Vec<(String, Expr)>,
+}
+
+impl Columns {
+    pub fn load(config: &Config) -> anyhow::Result<Columns> {
+        let mut columns = Vec::new();
+        for (name, value) in config.entries("columns") {
+            let text = value
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("columns.{name} must be a string"))?;
+            let expr = parse(text).map_err(|e| anyhow::anyhow!("columns.{name}: {e}"))?;
+            columns.push((name.to_string(), expr));
+        }
+        Ok(Columns { columns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|(name, _)| name.as_str())
+    }
+
+    fn eval(&self, e: &Expr, vars: &BTreeMap<&str, f64>, depth: usize) -> anyhow::Result<f64> {
+        Ok(match e {
+            Expr::Num(n) => *n,
+            Expr::Neg(e) => -self.eval(e, vars, depth)?,
+            Expr::Op(op, a, b) => {
+                let (a, b) = (self.eval(a, vars, depth)?, self.eval(b, vars, depth)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    // Division by zero gives 0 rather than an infinite cell.
+                    _ if b == 0.0 => 0.0,
+                    _ => a / b,
+                }
+            }
+            Expr::Call(f, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.eval(a, vars, depth))
+                    .collect::<anyhow::Result<Vec<f64>>>()?;
+                match f.as_str() {
+                    "max" => args.into_iter().fold(f64::MIN, f64::max),
+                    "min" => args.into_iter().fold(f64::MAX, f64::min),
+                    "round" => args[0].round(),
+                    "floor" => args[0].floor(),
+                    "ceil" => args[0].ceil(),
+                    _ => args[0].abs(),
+                }
+            }
+            Expr::Var(name) => match vars.get(name.as_str()) {
+                Some(v) => *v,
+                None => {
+                    let Some((_, e)) = self.columns.iter().find(|(n, _)| n == name) else {
+                        anyhow::bail!("unknown name {name:?}");
+                    };
+                    if depth > self.columns.len() {
+                        anyhow::bail!("column {name:?} refers to itself");
+                    }
+                    self.eval(e, vars, depth + 1)?
+                }
+            },
+        })
+    }
+
+    /// The value of every column for one day.
+    fn day_values(
+        &self,
+        sessions: &[&Session],
+        rate: f64,
+        workday: bool,
+    ) -> anyhow::Result<Vec<f64>> {
+        let minutes: i64 = sessions.iter().map(|s| s.minutes()).sum();
+        let breaks: i64 = sessions.iter().flat_map(|s| s.break_minutes.values()).sum();
+        let vars = BTreeMap::from([
+            ("hours", minutes as f64 / 60.0),
+            ("minutes", minutes as f64),
+            ("sessions", sessions.len() as f64),
+            ("breaks", breaks as f64),
+            ("workday", if workday { 1.0 } else { 0.0 }),
+            ("rate", rate),
+        ]);
+        self.columns
+            .iter()
+            .map(|(name, e)| {
+                self.eval(e, &vars, 0)
+                    .map_err(|err| anyhow::anyhow!("columns.{name}: {err}"))
+            })
+            .collect()
+    }
+
+    /// `(date, hours, values)` for every worked day, in date order.
+    pub fn days<'a>(
+        &self,
+        sessions: impl IntoIterator<Item = &'a Session>,
+        rate: Option<f64>,
+        calendar: &Calendar,
+    ) -> anyhow::Result<Vec<(String, f64, Vec<f64>)>> {
+        let mut by_day: BTreeMap<&str, Vec<&Session>> = BTreeMap::new();
+        for s in sessions {
+            by_day.entry(&s.date).or_default().push(s);
+        }
+        by_day
+            .into_iter()
+            .map(|(date, day)| {
+                let workday = NaiveDate::parse_from_str(date, "%Y/%m/%d")
+                    .is_ok_and(|d| calendar.is_workday(d));
+                let hours = day.iter().map(|s| s.minutes()).sum::<i64>() as f64 / 60.0;
+                let values = self.day_values(&day, rate.unwrap_or(0.0), workday)?;
+                Ok((date.to_string(), hours, values))
+            })
+            .collect()
+    }
+}
+
+/// Monthly sums of the day rows from [`Columns::days`].
+pub fn months(days: &[(String, f64, Vec<f64>)]) -> Vec<(String, f64, Vec<f64>)> {
+    let mut months: Vec<(String, f64, Vec<f64>)> = Vec::new();
+    for (date, hours, values) in days {
+        match months.last_mut() {
+            Some((m, h, sums)) if *m == date[..7] => {
+                *h += hours;
+                for (sum, v) in sums.iter_mut().zip(values) {
+                    *sum += v;
+                }
+            }
+            _ => months.push((date[..7].to_string(), *hours, values.clone())),
+        }
+    }
+    months
+}
+
+/// A value rounded to two decimals, without trailing zeros.
+pub fn format_value(v: f64) -> String {
+    let s = format!("{v:.2}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// One row per worked day, then one per month.
+pub fn columns_table(
+    columns: &Columns,
+    days: &[(String, f64, Vec<f64>)],
+    formats: &Formats,
+) -> String {
+    let names: Vec<&str> = columns.names().collect();
+    let mut out = String::new();
+    writeln!(out, "| date | hours | {} |", names.join(" | ")).unwrap();
+    let rules: Vec<String> = names.iter().map(|n| "-".repeat(n.len() + 2)).collect();
+    writeln!(out, "|------|-------|{}|", rules.join("|")).unwrap();
+    for (date, hours, values) in days.iter().chain(&months(days)) {
+        let cells: Vec<String> = values.iter().map(|v| format_value(*v)).collect();
+        let date = if date.len() == 7 {
+            formats.month(date)
+        } else {
+            formats.date(date)
+        };
+        writeln!(
+            out,
+            "| {date} | {} | {} |",
+            format_hours(*hours),
+            cells.join(" | ")
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Hours in CSV: plain numbers, unless `format.hours` asks for fixed
+/// decimals or `7:30`.
+fn csv_hours(h: f64) -> String {
+    let hours = display::hours();
+    match hours.style {
+        HourStyle::Hm => format_value(h),
+        HourStyle::Decimal => hours.decimal(h),
+        HourStyle::Clock => hours.format(h),
+    }
+}
+
+/// The same rows as [`columns_table`], as CSV.
+pub fn columns_csv(columns: &Columns, days: &[(String, f64, Vec<f64>)]) -> String {
+    let names: Vec<&str> = columns.names().collect();
+    let mut out = String::new();
+    writeln!(out, "date,hours,{}", names.join(",")).unwrap();
+    for (date, hours, values) in days.iter().chain(&months(days)) {
+        let cells: Vec<String> = values.iter().map(|v| format_value(*v)).collect();
+        writeln!(out, "{date},{},{}", csv_hours(*hours), cells.join(",")).unwrap();
+    }
+    out
+}
+
+/// `kintai columns`: the computed columns per day and month, as a Markdown
+/// table or with `csv` as CSV.
+pub fn print(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    rate: Option<f64>,
+    csv: bool,
+) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let columns = Columns::load(&config)?;
+    if columns.is_empty() {
+        anyhow::bail!("no computed columns; define them under [columns] in the config");
+    }
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let people = users(&sessions);
+    if people.len() > 1 {
+        anyhow::bail!(
+            "the log contains several users ({}); pick one with --user",
+            people.join(", ")
+        );
+    }
+    let days = columns.days(&sessions, rate, &Calendar::load(&config)?)?;
+    if csv {
+        print!("{}", columns_csv(&columns, &days));
+    } else {
+        println!(
+            "{}",
+            columns_table(&columns, &days, &Formats::load(&config)?)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,312 @@
+//! Shell completion scripts (`kintai completions <shell>`), generated from
+//! the command definitions. `--project`, `--tag` and `--kind` complete the
+//! names already used, which the scripts ask `kintai names` for, so a typo
+//! does not split a project in two in the reports.
+
+use clap::{Arg, Command, ValueEnum};
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{config::Config, event::read_log};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Names {
+    /// Projects: `project=` in the log, `[budget]` and `outlook.tag`
+    Projects,
+    /// Kinds of breaks: `kind=` in the log
+    Kinds,
+    /// Both, for `--tag`, which names a project or a kind of break
+    Tags,
+}
+
+const BIN: &str = "kintai";
+
+/// Options completed with names, by argument id.
+const NAMED: [(&str, &str); 3] = [("project", "projects"), ("tag", "tags"), ("kind", "kinds")];
+
+/// Names of `what` used in `log` and the config, sorted.
+fn collect(log: Option<&Path>, what: Names) -> anyhow::Result<BTreeSet<String>> {
+    let mut keys = Vec::new();
+    if what != Names::Kinds {
+        keys.push("project");
+    }
+    if what != Names::Projects {
+        keys.push("kind");
+    }
+    let mut names = BTreeSet::new();
+    if let Some(log) = log {
+        for e in read_log(log)? {
+            names.extend(keys.iter().filter_map(|k| e.field(k)).map(str::to_string));
+        }
+    }
+    if keys.contains(&"project") {
+        let config = Config::load()?;
+        names.extend(
+            config
+                .entries("budget")
+                .into_iter()
+                .map(|(k, _)| k.to_string()),
+        );
+        names.extend(config.str("outlook.tag").map(str::to_string));
+    }
+    Ok(names)
+}
+
+/// Prints the names of `what`, one per line.
+pub fn print_names(log: Option<&Path>, what: Names) -> anyhow::Result<()> {
+    for name in collect(log, what)? {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+fn visible(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands().filter(|c| !c.is_hide_set())
+}
+
+/// A subcommand's name and visible aliases.
+fn names_of(cmd: &Command) -> Vec<&str> {
+    let mut names = vec![cmd.get_name()];
+    names.extend(cmd.get_visible_aliases());
+    names
+}
+
+/// `-s` and `--long` spellings of an option; none for positionals.
+fn flags(arg: &Arg) -> Vec<String> {
+    let mut flags: Vec<String> = arg
+        .get_short()
+        .map(|s| format!("-{s}"))
+        .into_iter()
+        .collect();
+    flags.extend(arg.get_long().map(|l| format!("--{l}")));
+    flags
+}
+
+fn options(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments()
+        .filter(|a| !a.is_positional() && !a.is_hide_set())
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+/// The `names` argument for options completed with names.
+fn named(arg: &Arg) -> Option<&'static str> {
+    NAMED
+        .iter()
+        .find(|(id, _)| arg.get_id() == *id)
+        .map(|(_, names)| *names)
+}
+
+fn bash(root: &Command) -> String {
+    let bin = BIN;
+    let globals: Vec<&Arg> = options(root).collect();
+    let global_values: Vec<String> = globals
+        .iter()
+        .filter(|a| takes_value(a))
+        .flat_map(|a| flags(a))
+        .collect();
+    let global_flags: Vec<String> = globals.iter().flat_map(|a| flags(a)).collect();
+
+    let mut named_cases = Vec::new();
+    let mut word_cases = Vec::new();
+    let mut top = global_flags.clone();
+    for sub in visible(root) {
+        let names = names_of(sub);
+        top.extend(names.iter().map(|n| n.to_string()));
+        let mut words: Vec<String> = visible(sub).map(|c| c.get_name().to_string()).collect();
+        words.extend(options(sub).flat_map(flags));
+        // Options of nested commands (`import ics --tag`) count for the
+        // top-level command, which is all the script tracks.
+        for arg in options(sub).chain(visible(sub).flat_map(options)) {
+            if let Some(what) = named(arg) {
+                let pattern: Vec<String> = names
+                    .iter()
+                    .flat_map(|n| flags(arg).into_iter().map(move |f| format!("{n}:{f}")))
+                    .collect();
+                let case = format!(
+                    "        {}) _{bin}_names {what}; return ;;",
+                    pattern.join("|")
+                );
+                if !named_cases.contains(&case) {
+                    named_cases.push(case);
+                }
+            }
+        }
+        words.extend(global_flags.iter().cloned());
+        word_cases.push(format!(
+            "        {}) words=\"{}\" ;;",
+            names.join("|"),
+            words.join(" ")
+        ));
+    }
+    let value_options: BTreeSet<String> = visible(root)
+        .flat_map(options)
+        .chain(globals.iter().copied())
+        .filter(|a| takes_value(a))
+        .flat_map(flags)
+        .collect();
+
+    format!(
+        r#"# bash completion for {bin}; generated by `{bin} completions bash`
+_{bin}_names() {{
+    local log=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case ${{COMP_WORDS[i]}} in
+            -l|--log) log=(--log "${{COMP_WORDS[i+1]}}") ;;
+        esac
+    done
+    local IFS=$'\n'
+    COMPREPLY=($(compgen -W "$({bin} "${{log[@]}}" names "$1" 2>/dev/null)" -- "$cur"))
+}}
+
+_{bin}() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local i cmd= words
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case ${{COMP_WORDS[i]}} in
+            {global_values}) ((i++)) ;;
+            -*) ;;
+            *) cmd=${{COMP_WORDS[i]}}; break ;;
+        esac
+    done
+    case $cmd:$prev in
+{named_cases}
+    esac
+    case $prev in
+        {value_options}) return ;;
+    esac
+    case $cmd in
+        '') words="{top}" ;;
+{word_cases}
+    esac
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}}
+complete -o default -F _{bin} {bin}
+"#,
+        global_values = global_values.join("|"),
+        named_cases = named_cases.join("\n"),
+        value_options = value_options.into_iter().collect::<Vec<_>>().join("|"),
+        top = top.join(" "),
+        word_cases = word_cases.join("\n"),
+    )
+}
+
+/// `text` as a single-quoted fish string.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The first line of an argument's or command's help.
+fn summary(help: Option<&clap::builder::StyledStr>) -> String {
+    help.map(|h| h.to_string().lines().next().unwrap_or("").to_string())
+        .unwrap_or_default()
+}
+
+fn fish_option(bin: &str, condition: &str, arg: &Arg) -> String {
+    let mut line = format!("complete -c {bin}");
+    if !condition.is_empty() {
+        line.push_str(&format!(" -n {}", fish_quote(condition)));
+    }
+    if let Some(s) = arg.get_short() {
+        line.push_str(&format!(" -s {s}"));
+    }
+    if let Some(l) = arg.get_long() {
+        line.push_str(&format!(" -l {l}"));
+    }
+    if let Some(what) = named(arg) {
+        line.push_str(&format!(" -x -a '(__{bin}_names {what})'"));
+    } else if takes_value(arg) {
+        let values: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        if values.is_empty() {
+            line.push_str(" -r -F");
+        } else {
+            line.push_str(&format!(" -x -a {}", fish_quote(&values.join(" "))));
+        }
+    }
+    let help = summary(arg.get_help());
+    if !help.is_empty() {
+        line.push_str(&format!(" -d {}", fish_quote(&help)));
+    }
+    line
+}
+
+fn fish(root: &Command) -> String {
+    let bin = BIN;
+    let mut out = format!(
+        r#"# fish completion for {bin}; generated by `{bin} completions fish`
+function __{bin}_names
+    set -l log
+    set -l words (commandline -opc)
+    for i in (seq (count $words))
+        if contains -- $words[$i] -l --log
+            set log --log $words[(math $i + 1)]
+        end
+    end
+    {bin} $log names $argv 2>/dev/null
+end
+
+complete -c {bin} -f
+"#
+    );
+    for arg in options(root) {
+        out.push_str(&fish_option(bin, "", arg));
+        out.push('\n');
+    }
+    for sub in visible(root) {
+        let names = names_of(sub);
+        for name in &names {
+            out.push_str(&format!(
+                "complete -c {bin} -n __fish_use_subcommand -a {name} -d {}\n",
+                fish_quote(&summary(sub.get_about()))
+            ));
+        }
+        let seen = format!("__fish_seen_subcommand_from {}", names.join(" "));
+        for nested in visible(sub) {
+            out.push_str(&format!(
+                "complete -c {bin} -n {} -a {} -d {}\n",
+                fish_quote(&seen),
+                nested.get_name(),
+                fish_quote(&summary(nested.get_about()))
+            ));
+        }
+        for arg in options(sub) {
+            out.push_str(&fish_option(bin, &seen, arg));
+            out.push('\n');
+        }
+        for nested in visible(sub) {
+            let seen = format!("__fish_seen_subcommand_from {}", nested.get_name());
+            for arg in options(nested) {
+                out.push_str(&fish_option(bin, &seen, arg));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Prints the completion script for `shell`.
+pub fn print(shell: Shell, cmd: &Command) {
+    let script = match shell {
+        Shell::Bash => bash(cmd),
+        // zsh runs the bash script through its bash compatibility layer.
+        Shell::Zsh => format!(
+            "#compdef {BIN}\nautoload -U +X bashcompinit && bashcompinit\n{}",
+            bash(cmd)
+        ),
+        Shell::Fish => fish(cmd),
+    };
+    print!("{script}");
+}
@@ -0,0 +1,419 @@
+//! `kintai compliance`: a month checked against the Labor Standards Act, as
+//! a Markdown report to share with a manager:
+//!
+//! - breaks: over 6 hours of work a day need 45 minutes of break, over 8
+//!   hours 60 (counting the kinds in `breaks.statutory`);
+//! - days off: streaks of more than `compliance.max_consecutive_days`
+//!   (default 6) workdays in a row;
+//! - overtime against the 36協定 ceilings: `compliance.monthly_overtime`
+//!   (default 45h) a month and `compliance.yearly_overtime` (default 360h)
+//!   a year, from month `compliance.year_start` (default 4, April); at most 6
+//!   months over the monthly limit a year; and, counting holiday work, under
+//!   100 hours in the month and at most 80 hours on average over the last 2
+//!   to 6 months;
+//! - rest between shifts: less than `compliance.min_interval_hours` (default
+//!   11) between one session's end and the next one's start.
+
+use chrono::{DateTime, Datelike, FixedOffset, Months, NaiveDate};
+use std::{collections::BTreeSet, fmt::Write, path::PathBuf};
+
+use crate::{
+    config::Config,
+    event::{filter_user, read_events},
+    overtime::daily_hours,
+    payroll::{self, breakdown},
+    report::format_hm,
+    session::{Session, build_sessions, users},
+    state,
+    stats::parse_month,
+};
+
+/// Hours of work after which a break is due, and its minimum minutes.
+const BREAK_RULES: [(f64, i64); 2] = [(8.0, 60), (6.0, 45)];
+/// Overtime plus holiday work must stay under this in any month.
+const MONTHLY_CEILING: f64 = 100.0;
+/// Average overtime plus holiday work allowed over 2 to 6 months.
+const AVERAGE_CEILING: f64 = 80.0;
+/// Months a year that may exceed the monthly limit.
+const MONTHS_OVER_LIMIT: usize = 6;
+
+struct Limits {
+    consecutive_days: usize,
+    monthly_overtime: f64,
+    yearly_overtime: f64,
+    year_start: u32,
+    interval_hours: f64,
+}
+
+impl Limits {
+    fn load(config: &Config) -> anyhow::Result<Limits> {
+        let num = |key: &str, default: f64| {
+            config
+                .get(&format!("compliance.{key}"))
+                .and_then(|v| v.as_num())
+                .unwrap_or(default)
+        };
+        let year_start = num("year_start", 4.0);
+        if !(1.0..=12.0).contains(&year_start) || year_start.fract() != 0.0 {
+            anyhow::bail!("compliance.year_start must be a month number from 1 to 12");
+        }
+        Ok(Limits {
+            consecutive_days: num("max_consecutive_days", 6.0) as usize,
+            monthly_overtime: num("monthly_overtime", 45.0),
+            yearly_overtime: num("yearly_overtime", 360.0),
+            year_start: year_start as u32,
+            interval_hours: num("min_interval_hours", 11.0),
+        })
+    }
+}
+
+fn first_of(month: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").expect("month checked")
+}
+
+/// Days in `month` whose break was shorter than the work required, as
+/// `(date, worked hours, break minutes, required minutes)`.
+fn break_violations(
+    sessions: &[&Session],
+    statutory: Option<&[&str]>,
+) -> Vec<(String, f64, i64, i64)> {
+    let counts = |kind: &str| statutory.is_none_or(|list| list.contains(&kind));
+    let mut days: Vec<(String, f64, i64)> = Vec::new();
+    for s in sessions {
+        let breaks: i64 = s
+            .break_minutes
+            .iter()
+            .filter(|(k, _)| counts(k))
+            .map(|(_, m)| m)
+            .sum();
+        match days.last_mut() {
+            Some((date, worked, b)) if *date == s.date => {
+                *worked += s.minutes() as f64 / 60.0;
+                *b += breaks;
+            }
+            _ => days.push((s.date.clone(), s.minutes() as f64 / 60.0, breaks)),
+        }
+    }
+    days.into_iter()
+        .filter_map(|(date, worked, breaks)| {
+            let (_, required) = BREAK_RULES.iter().find(|(h, _)| worked > *h)?;
+            (breaks < *required).then_some((date, worked, breaks, *required))
+        })
+        .collect()
+}
+
+/// Streaks of more than `limit` days worked in a row that reach into the
+/// month starting `first`, as `(first day, last day, days)`.
+fn long_streaks(
+    worked: &BTreeSet<NaiveDate>,
+    first: NaiveDate,
+    limit: usize,
+) -> Vec<(NaiveDate, NaiveDate, usize)> {
+    let mut streaks = Vec::new();
+    let mut current: Option<(NaiveDate, NaiveDate)> = None;
+    for &day in worked {
+        current = match current {
+            Some((start, end)) if end.succ_opt() == Some(day) => Some((start, day)),
+            _ => {
+                streaks.extend(current);
+                Some((day, day))
+            }
+        };
+    }
+    streaks.extend(current);
+    let next = first + Months::new(1);
+    streaks
+        .into_iter()
+        .map(|(a, b)| (a, b, (b - a).num_days() as usize + 1))
+        .filter(|(a, b, n)| *n > limit && *b >= first && *a < next)
+        .collect()
+}
+
+/// Rests shorter than `min_hours` before shifts starting on a day of the
+/// month `prefix` (`YYYY/MM`), as `(end of one shift, start of the next,
+/// hours between)`. Sessions that follow each other without a gap, as one
+/// shift split at midnight does, are one shift; sessions on the same day
+/// are breaks within the day's shift rather than rests between shifts.
+fn short_rests(
+    sessions: &[Session],
+    prefix: &str,
+    min_hours: f64,
+) -> Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>, f64)> {
+    let mut ordered: Vec<&Session> = sessions.iter().collect();
+    ordered.sort_by_key(|s| s.start);
+    // Shifts as (date, start, end).
+    let mut shifts: Vec<(&str, DateTime<FixedOffset>, DateTime<FixedOffset>)> = Vec::new();
+    for s in ordered {
+        match shifts.last_mut() {
+            Some((_, _, end)) if *end == s.start => *end = s.end(),
+            _ => shifts.push((&s.date, s.start, s.end())),
+        }
+    }
+    shifts
+        .windows(2)
+        .filter_map(|pair| {
+            let ((prev_date, _, finished), (date, started, _)) = (pair[0], pair[1]);
+            let rest = (started - finished).num_minutes() as f64 / 60.0;
+            (date.starts_with(prefix) && date != prev_date && rest < min_hours)
+                .then_some((finished, started, rest))
+        })
+        .collect()
+}
+
+/// Overtime (see [`payroll::breakdown`]) and holiday work of `month`, in
+/// hours.
+fn overtime_and_holiday(
+    events: &[crate::event::LogEvent],
+    month: NaiveDate,
+    limits: &payroll::Limits,
+) -> (f64, f64) {
+    let b = breakdown(events, &month.format("%Y-%m").to_string(), limits);
+    (b.overtime, b.holiday)
+}
+
+/// Prints the compliance report for `month` (`YYYY-MM`, default this month).
+pub fn report(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: Option<&str>,
+) -> anyhow::Result<()> {
+    let month = month
+        .map(str::to_string)
+        .unwrap_or_else(|| state::now().format("%Y-%m").to_string());
+    parse_month(&month)?;
+    let events = filter_user(read_events(input)?, user);
+    let sessions = build_sessions(events.clone());
+    let people = users(&sessions);
+    if people.len() > 1 {
+        anyhow::bail!(
+            "the log contains several users ({}); pick one with --user",
+            people.join(", ")
+        );
+    }
+    let config = Config::load()?;
+    let limits = Limits::load(&config)?;
+    let overtime_limits = payroll::Limits::load(&config)?;
+    let first = first_of(&month);
+    let prefix = month.replace('-', "/");
+    let in_month: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| s.date.starts_with(&prefix))
+        .collect();
+
+    let mut out = String::new();
+    let mut issues = 0;
+    writeln!(out, "# Compliance for {month}\n").unwrap();
+
+    // Breaks.
+    let statutory = config.strs("breaks.statutory");
+    let breaks = break_violations(&in_month, statutory.as_deref());
+    writeln!(out, "## Breaks\n").unwrap();
+    if breaks.is_empty() {
+        writeln!(out, "No days with too short a break.\n").unwrap();
+    } else {
+        writeln!(out, "| date | worked | break | required |").unwrap();
+        writeln!(out, "|------|--------|-------|----------|").unwrap();
+        for (date, worked, got, required) in &breaks {
+            writeln!(
+                out,
+                "| {date} | {} | {got}m | {required}m |",
+                format_hm(*worked)
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+        issues += breaks.len();
+    }
+
+    // Days off.
+    let worked: BTreeSet<NaiveDate> = daily_hours(&sessions)
+        .keys()
+        .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .collect();
+    let streaks = long_streaks(&worked, first, limits.consecutive_days);
+    writeln!(out, "## Consecutive workdays\n").unwrap();
+    if streaks.is_empty() {
+        writeln!(
+            out,
+            "No more than {} days worked in a row.\n",
+            limits.consecutive_days
+        )
+        .unwrap();
+    } else {
+        writeln!(out, "| from | to | days |").unwrap();
+        writeln!(out, "|------|----|------|").unwrap();
+        for (a, b, n) in &streaks {
+            writeln!(out, "| {a} | {b} | {n} |").unwrap();
+        }
+        writeln!(out).unwrap();
+        issues += streaks.len();
+    }
+
+    // 36協定.
+    let (overtime, holiday) = overtime_and_holiday(&events, first, &overtime_limits);
+    let year_first = {
+        let back = (first.month() + 12 - limits.year_start) % 12;
+        first - Months::new(back)
+    };
+    let mut year_overtime = 0.0;
+    let mut months_over = 0;
+    let mut m = year_first;
+    while m <= first {
+        let (o, _) = overtime_and_holiday(&events, m, &overtime_limits);
+        year_overtime += o;
+        if o > limits.monthly_overtime {
+            months_over += 1;
+        }
+        m = m + Months::new(1);
+    }
+    let mut total = overtime + holiday;
+    let mut worst_average: Option<(usize, f64)> = None;
+    for n in 2..=6 {
+        let (o, h) =
+            overtime_and_holiday(&events, first - Months::new(n as u32 - 1), &overtime_limits);
+        total += o + h;
+        let average = total / n as f64;
+        if average > AVERAGE_CEILING && worst_average.is_none_or(|(_, a)| average > a) {
+            worst_average = Some((n, average));
+        }
+    }
+    let mark = |bad: bool| if bad { "⚠" } else { "ok" };
+    let checks = [
+        (
+            format!("overtime this month (limit {}h)", limits.monthly_overtime),
+            format_hm(overtime),
+            overtime > limits.monthly_overtime,
+        ),
+        (
+            format!("overtime and holiday work (under {MONTHLY_CEILING}h)"),
+            format_hm(overtime + holiday),
+            overtime + holiday >= MONTHLY_CEILING,
+        ),
+        (
+            format!("highest 2-6 month average (max {AVERAGE_CEILING}h)"),
+            worst_average.map_or("-".to_string(), |(n, a)| {
+                format!("{} over {n} months", format_hm(a))
+            }),
+            worst_average.is_some(),
+        ),
+        (
+            format!(
+                "overtime since {} (limit {}h)",
+                year_first.format("%Y-%m"),
+                limits.yearly_overtime
+            ),
+            format_hm(year_overtime),
+            year_overtime > limits.yearly_overtime,
+        ),
+        (
+            format!(
+                "months over {}h this year (max {MONTHS_OVER_LIMIT})",
+                limits.monthly_overtime
+            ),
+            months_over.to_string(),
+            months_over > MONTHS_OVER_LIMIT,
+        ),
+    ];
+    writeln!(out, "## Overtime (36協定)\n").unwrap();
+    writeln!(out, "| check | value | |").unwrap();
+    writeln!(out, "|-------|-------|-|").unwrap();
+    for (check, value, bad) in &checks {
+        writeln!(out, "| {check} | {value} | {} |", mark(*bad)).unwrap();
+    }
+    writeln!(out).unwrap();
+    issues += checks.iter().filter(|(_, _, bad)| *bad).count();
+
+    // Rest between shifts.
+    let short_rests = short_rests(&sessions, &prefix, limits.interval_hours);
+    writeln!(out, "## Rest between shifts\n").unwrap();
+    if short_rests.is_empty() {
+        writeln!(
+            out,
+            "At least {}h between all shifts.\n",
+            limits.interval_hours
+        )
+        .unwrap();
+    } else {
+        writeln!(out, "| finished | started | rest |").unwrap();
+        writeln!(out, "|----------|---------|------|").unwrap();
+        for (finished, started, rest) in &short_rests {
+            writeln!(
+                out,
+                "| {} | {} | {} |",
+                finished.format("%Y-%m-%d %H:%M"),
+                started.format("%Y-%m-%d %H:%M"),
+                format_hm(*rest)
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+        issues += short_rests.len();
+    }
+
+    match issues {
+        0 => writeln!(out, "No issues found."),
+        n => writeln!(out, "{n} issue{} found.", crate::report::plural(n)),
+    }
+    .unwrap();
+    print!("{out}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session on `date` starting at `start` (RFC 3339) with `range`.
+    fn session(date: &str, start: &str, range: &str) -> Session {
+        Session {
+            start: DateTime::parse_from_rfc3339(start).unwrap(),
+            user: None,
+            date: date.to_string(),
+            time_range: range.to_string(),
+            content: None,
+            refs: Vec::new(),
+            location: None,
+            project: None,
+            break_minutes: Default::default(),
+        }
+    }
+
+    #[test]
+    fn treats_a_shift_split_at_midnight_as_one() {
+        // 22:00-06:00 split at midnight, then the next night's shift.
+        let sessions = [
+            session("2026/04/06", "2026-04-06T22:00:00+09:00", "22:00~24:00"),
+            session("2026/04/07", "2026-04-07T00:00:00+09:00", "00:00~06:00"),
+            session("2026/04/07", "2026-04-07T22:00:00+09:00", "22:00~24:00"),
+            session("2026/04/08", "2026-04-08T00:00:00+09:00", "00:00~06:00"),
+        ];
+        assert!(short_rests(&sessions, "2026/04", 11.0).is_empty());
+    }
+
+    #[test]
+    fn reports_short_rests_after_a_split_shift() {
+        let sessions = [
+            session("2026/04/06", "2026-04-06T22:00:00+09:00", "22:00~24:00"),
+            session("2026/04/07", "2026-04-07T00:00:00+09:00", "00:00~06:00"),
+            session("2026/04/07", "2026-04-07T13:00:00+09:00", "13:00~18:00"),
+        ];
+        let rests = short_rests(&sessions, "2026/04", 11.0);
+        assert_eq!(rests.len(), 1);
+        let (finished, started, rest) = rests[0];
+        assert_eq!(finished.to_rfc3339(), "2026-04-07T06:00:00+09:00");
+        assert_eq!(started.to_rfc3339(), "2026-04-07T13:00:00+09:00");
+        assert_eq!(rest, 7.0);
+    }
+
+    #[test]
+    fn ignores_gaps_between_sessions_of_one_day() {
+        let sessions = [
+            session("2026/04/06", "2026-04-06T09:00:00+09:00", "09:00~12:00"),
+            session("2026/04/06", "2026-04-06T13:00:00+09:00", "13:00~23:00"),
+            session("2026/04/07", "2026-04-07T08:00:00+09:00", "08:00~17:00"),
+        ];
+        let rests = short_rests(&sessions, "2026/04", 11.0);
+        assert_eq!(rests.len(), 1);
+        assert_eq!(rests[0].2, 9.0);
+    }
+}
@@ -0,0 +1,189 @@
+//! User configuration, read from `$KINTAI_CONFIG`, else `config.toml` in
+//! [`paths::config_dir`] (usually `~/.config/kintai`).
+//!
+//! The file is parsed as TOML and flattened into dotted keys, so that
+//! `[clients."acme corp"]` with `rate = 5000` is `clients.acme corp.rate`.
+//! Integers and floats are both numbers, and dates and times are kept as
+//! their text.
+
+use std::{collections::BTreeMap, env, fmt, fs, path::PathBuf};
+
+use crate::paths;
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+            Value::Num(n) => write!(f, "{n}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Config {
+    /// Values keyed by their dotted path, e.g. `http.token`.
+    values: BTreeMap<String, Value>,
+}
+
+pub fn path() -> PathBuf {
+    if let Some(p) = env::var_os("KINTAI_CONFIG").filter(|p| !p.is_empty()) {
+        return PathBuf::from(p);
+    }
+    paths::config_dir().join("config.toml")
+}
+
+impl Config {
+    /// Loads the config file; a missing file is an empty config.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = path();
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                Config::parse(&text).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(anyhow::anyhow!("{}: {e}", path.display())),
+        }
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let table: toml::Table = toml::from_str(text)?;
+        let mut values = BTreeMap::new();
+        flatten(&mut values, "", table)?;
+        Ok(Config { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    /// The `key = value` pairs directly inside the `[<prefix>]` section.
+    pub fn entries(&self, prefix: &str) -> Vec<(&str, &Value)> {
+        self.values
+            .iter()
+            .filter_map(|(k, v)| {
+                let key = k.strip_prefix(prefix)?.strip_prefix('.')?;
+                (!key.contains('.')).then_some((key, v))
+            })
+            .collect()
+    }
+
+    /// Names of the `[<prefix>.<name>]` sections, in order.
+    pub fn sections(&self, prefix: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .values
+            .keys()
+            .filter_map(|k| k.strip_prefix(prefix)?.strip_prefix('.')?.rsplit_once('.'))
+            .map(|(name, _)| name)
+            .collect();
+        names.dedup();
+        names
+    }
+
+    /// A list of strings; a single string is a one-item list.
+    pub fn strs(&self, key: &str) -> Option<Vec<&str>> {
+        match self.get(key)? {
+            Value::List(items) => Some(items.iter().filter_map(Value::as_str).collect()),
+            Value::Str(s) => Some(vec![s.as_str()]),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(Value::as_bool)
+    }
+}
+
+/// Prints the config file location and every value it sets.
+pub fn show() -> anyhow::Result<()> {
+    println!("# {}", path().display());
+    println!("# data: {}", paths::data_dir().display());
+    println!("# cache: {}", paths::cache_dir().display());
+    for (k, v) in &Config::load()?.values {
+        if k.ends_with("token") || k.ends_with("password") {
+            println!("{k} = \"********\"");
+        } else {
+            println!("{k} = {v}");
+        }
+    }
+    Ok(())
+}
+
+/// Adds the values of `table` to `values`, keyed by their path below `prefix`.
+fn flatten(
+    values: &mut BTreeMap<String, Value>,
+    prefix: &str,
+    table: toml::Table,
+) -> anyhow::Result<()> {
+    for (key, value) in table {
+        let full = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            toml::Value::Table(t) => flatten(values, &full, t)?,
+            v => {
+                let v = convert(v).ok_or_else(|| {
+                    anyhow::anyhow!("`{full}`: arrays of tables are not supported")
+                })?;
+                values.insert(full, v);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A TOML value other than a table; `None` for an array holding tables.
+fn convert(value: toml::Value) -> Option<Value> {
+    Some(match value {
+        toml::Value::String(s) => Value::Str(s),
+        toml::Value::Integer(n) => Value::Num(n as f64),
+        toml::Value::Float(n) => Value::Num(n),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::Str(d.to_string()),
+        toml::Value::Array(items) => {
+            Value::List(items.into_iter().map(convert).collect::<Option<_>>()?)
+        }
+        toml::Value::Table(_) => return None,
+    })
+}
@@ -0,0 +1,39 @@
+//! Console setup on Windows, whose consoles default to the ANSI code page
+//! (cp932 on Japanese systems) and print colour escapes literally.
+
+/// Switches the console to UTF-8, so hooks and plugins that print Japanese
+/// are not garbled, and turns on ANSI escapes for the coloured warnings.
+/// Does nothing when not attached to a console.
+#[cfg(windows)]
+pub fn init() {
+    const CP_UTF8: u32 = 65001;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCP(cp: u32) -> i32;
+        fn SetConsoleOutputCP(cp: u32) -> i32;
+        fn GetStdHandle(std: u32) -> *mut std::ffi::c_void;
+        fn GetConsoleMode(handle: *mut std::ffi::c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut std::ffi::c_void, mode: u32) -> i32;
+    }
+
+    // SAFETY: plain Win32 calls on this process's own standard handles;
+    // failures (no console, redirected output) are ignored.
+    unsafe {
+        SetConsoleCP(CP_UTF8);
+        SetConsoleOutputCP(CP_UTF8);
+        for std in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            let handle = GetStdHandle(std);
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) != 0 {
+                SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn init() {}
@@ -0,0 +1,222 @@
+//! Append-only corrections. Instead of rewriting the log, `amend` and `void`
+//! events refer to an earlier event by its ID (`ref=`) and are applied when
+//! the log is read.
+
+use chrono::{DateTime, NaiveDate};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    close,
+    event::{LogEvent, filter_user, read_log, record_event_at, user_field},
+    fix::{raw_sessions, when},
+    state,
+    time::parse_when,
+};
+
+fn is_correction(ty: &str) -> bool {
+    matches!(ty, "amend" | "void")
+}
+
+/// IDs of the events cancelled by a `void`. A void that is itself voided
+/// (as `kintai redo` does to the voids of an undo) cancels nothing.
+fn voided_ids(events: &[LogEvent]) -> HashSet<String> {
+    let mut voids_of: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut voids = Vec::new();
+    for e in events.iter().filter(|e| e.ty == "void") {
+        if let Some(target) = e.field("ref") {
+            voids_of.entry(target).or_default().push(e.id());
+            voids.push((e.id(), target));
+        }
+    }
+    fn cancelled(id: &str, voids_of: &HashMap<&str, Vec<&str>>, depth: usize) -> bool {
+        depth < 64
+            && voids_of
+                .get(id)
+                .is_some_and(|voids| voids.iter().any(|v| !cancelled(v, voids_of, depth + 1)))
+    }
+    voids
+        .into_iter()
+        .filter(|(void, _)| !cancelled(void, &voids_of, 0))
+        .map(|(_, target)| target.to_string())
+        .collect()
+}
+
+/// Drops voided events and the correction events themselves, and rewrites
+/// amended events with their new timestamp and/or content. When an event is
+/// amended several times, the latest amendment wins.
+pub fn apply_corrections(events: Vec<LogEvent>) -> Vec<LogEvent> {
+    if !events.iter().any(|e| is_correction(&e.ty)) {
+        return events;
+    }
+    let voided = voided_ids(&events);
+    let mut amends: Vec<&LogEvent> = events
+        .iter()
+        .filter(|e| e.ty == "amend" && !voided.contains(e.id()))
+        .collect();
+    amends.sort_by_key(|e| e.ts.clone());
+    let mut amended: HashMap<String, (Option<String>, Option<String>)> = HashMap::new();
+    for a in amends {
+        if let Some(target) = a.field("ref") {
+            let entry = amended.entry(target.to_string()).or_default();
+            if let Some(ts) = a.field("new_ts") {
+                entry.0 = Some(ts.to_string());
+            }
+            if a.content.is_some() {
+                entry.1 = a.content.clone();
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(events.len());
+    for mut e in events {
+        if is_correction(&e.ty) || voided.contains(e.id()) {
+            continue;
+        }
+        if let Some((ts, content)) = amended.get(e.id()) {
+            // Keep legacy events addressable by their original timestamp.
+            if !e.fields.contains_key("id") {
+                e.fields.insert("id".to_string(), e.ts.clone());
+            }
+            if let Some(ts) = ts {
+                e.ts = ts.clone();
+            }
+            if content.is_some() {
+                e.content = content.clone();
+            }
+        }
+        out.push(e);
+    }
+    out
+}
+
+fn find_target(log: &Path, target: &str) -> anyhow::Result<LogEvent> {
+    read_log(log)?
+        .into_iter()
+        .find(|e| e.id() == target)
+        .ok_or_else(|| anyhow::anyhow!("no event with id {target:?} in {}", log.display()))
+}
+
+/// Collects the `correction=1` marker if any of `stamps` falls in a closed month.
+fn guard_all(
+    log: &Path,
+    user: Option<&str>,
+    stamps: &[&str],
+    force: bool,
+) -> anyhow::Result<Vec<(&'static str, &'static str)>> {
+    let mut extra = Vec::new();
+    for ts in stamps {
+        for f in close::guard(log, user, ts, force)? {
+            if !extra.contains(&f) {
+                extra.push(f);
+            }
+        }
+    }
+    Ok(extra)
+}
+
+pub fn amend(
+    log: &Path,
+    user: Option<&str>,
+    target: &str,
+    new_ts: Option<&str>,
+    content: Option<String>,
+    force: bool,
+) -> anyhow::Result<()> {
+    if new_ts.is_none() && content.is_none() {
+        anyhow::bail!("nothing to amend; pass --ts and/or --content");
+    }
+    let original = find_target(log, target)?;
+    if is_correction(&original.ty) {
+        anyhow::bail!(
+            "{target} is a {} event; amend the original event instead",
+            original.ty
+        );
+    }
+    let base = DateTime::parse_from_rfc3339(&original.ts)?;
+    let new_ts = new_ts
+        .map(|t| parse_when(t, base).map(|dt| dt.to_rfc3339()))
+        .transpose()?;
+    let now = state::now().to_rfc3339();
+
+    let mut stamps = vec![original.ts.as_str(), now.as_str()];
+    stamps.extend(new_ts.as_deref());
+    let mut fields = user_field(original.user().or(user));
+    fields.push(("ref", target));
+    if let Some(ts) = &new_ts {
+        fields.push(("new_ts", ts));
+    }
+    fields.extend(guard_all(log, user, &stamps, force)?);
+    record_event_at(Some(log), &now, "amend", &fields, content)?;
+    println!("Amended {} event {target}.", original.ty);
+    Ok(())
+}
+
+pub fn void(log: &Path, user: Option<&str>, target: &str, force: bool) -> anyhow::Result<()> {
+    let original = find_target(log, target)?;
+    if original.ty == "void" {
+        anyhow::bail!("{target} is already a void event");
+    }
+    let now = state::now().to_rfc3339();
+    let mut fields = user_field(original.user().or(user));
+    fields.push(("ref", target));
+    fields.extend(guard_all(log, user, &[&original.ts, &now], force)?);
+    record_event_at(Some(log), &now, "void", &fields, None)?;
+    println!("Voided {} event {target}.", original.ty);
+    Ok(())
+}
+
+/// Sets the content of a finished session on `date` (`YYYY-MM-DD`, default:
+/// today), or with `append` adds to it. With several sessions that day,
+/// `at` (`HH:MM`) picks the one running then; otherwise the last is taken.
+pub fn annotate(
+    log: &Path,
+    user: Option<&str>,
+    date: Option<&str>,
+    at: Option<&str>,
+    content: String,
+    append: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    let date = match date {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("expected a day as YYYY-MM-DD, got {d:?}"))?,
+        None => state::now().date_naive(),
+    };
+    let sessions: Vec<_> = raw_sessions(filter_user(read_log(log)?, user))
+        .into_iter()
+        .filter(|s| when(&s.start).date_naive() == date)
+        .collect();
+    let session = match at {
+        Some(at) => {
+            let base = when(
+                &sessions
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("no sessions on {date}"))?
+                    .start,
+            );
+            let t = parse_when(at, base)?;
+            sessions
+                .iter()
+                .find(|s| when(&s.start) <= t && s.finish.as_ref().is_none_or(|f| t <= when(f)))
+                .ok_or_else(|| anyhow::anyhow!("no session on {date} was running at {at}"))?
+        }
+        None => sessions
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("no sessions on {date}"))?,
+    };
+    let Some(finish) = &session.finish else {
+        anyhow::bail!("that session is still running; pass the content to `kintai finish`");
+    };
+    let content = content.trim();
+    if content.is_empty() {
+        anyhow::bail!("nothing to annotate with");
+    }
+    let content = match finish.content.as_deref() {
+        Some(old) if append && !old.is_empty() => format!("{old}; {content}"),
+        _ => content.to_string(),
+    };
+    amend(log, user, finish.id(), None, Some(content), force)
+}
@@ -0,0 +1,459 @@
+//! Long-running daemon that keeps the current tracking state in memory and
+//! answers queries over a Unix socket.
+//!
+//! The wire protocol is one request line per connection (`status`, `start`,
+//! `finish [content]`, `break_start`, `break_end`) answered by one line:
+//! `ok <state fields>` or `error <message>`.
+
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::ToSocketAddrs,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    close,
+    config::Config,
+    event::{filter_user, read_log, record, record_event_at, user_field},
+    history,
+    hours::{self, Period},
+    http::{self, Request, Response},
+    metrics, paths, schedule,
+    session::{Session, build_sessions},
+    state::{self, LogStamp, Phase, TrackingState},
+    wifi,
+};
+
+/// What to record when the daemon is stopped (SIGTERM/SIGINT) mid-session.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ShutdownAction {
+    /// Leave the log untouched
+    #[default]
+    None,
+    /// Start a break if currently working
+    Break,
+    /// Finish the running session
+    Finish,
+}
+
+impl ShutdownAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShutdownAction::None => "none",
+            ShutdownAction::Break => "break",
+            ShutdownAction::Finish => "finish",
+        }
+    }
+}
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_terminate(_: libc::c_int) {
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() {
+    let handler = on_terminate as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+pub fn socket_path(log: &Path, user: Option<&str>) -> PathBuf {
+    paths::runtime_dir().join(format!("daemon-{}.sock", state::log_key(log, user)))
+}
+
+/// Sends one request line to the daemon serving `log` (for `user`) and
+/// returns its reply.
+pub fn request(log: &Path, user: Option<&str>, req: &str) -> anyhow::Result<String> {
+    let mut stream = UnixStream::connect(socket_path(log, user))?;
+    stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+    writeln!(stream, "{req}")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let reply = reply.trim_end();
+    match reply.strip_prefix("ok") {
+        Some(rest) => Ok(rest.trim_start().to_string()),
+        None => anyhow::bail!("daemon: {}", reply.strip_prefix("error ").unwrap_or(reply)),
+    }
+}
+
+/// Asks a running daemon for the current state; `None` if no daemon is up.
+pub fn query(log: &Path, user: Option<&str>) -> Option<TrackingState> {
+    let fields = request(log, user, "status").ok()?;
+    TrackingState::from_fields(&fields)
+}
+
+pub struct Daemon {
+    log: PathBuf,
+    user: Option<String>,
+    stamp: Option<LogStamp>,
+    state: TrackingState,
+    sessions: Vec<Session>,
+    /// Reason and start of the current break, if `auto_break` started it.
+    auto_break: Option<(String, DateTime<FixedOffset>)>,
+}
+
+impl Daemon {
+    fn new(log: &Path, user: Option<&str>) -> anyhow::Result<Self> {
+        let mut d = Daemon {
+            log: log.to_path_buf(),
+            user: user.map(str::to_string),
+            stamp: None,
+            state: TrackingState::idle(),
+            sessions: Vec::new(),
+            auto_break: None,
+        };
+        d.reload()?;
+        Ok(d)
+    }
+
+    fn reload(&mut self) -> anyhow::Result<()> {
+        self.stamp = state::log_stamp(&self.log)?;
+        let events = filter_user(read_log(&self.log)?, self.user.as_deref());
+        self.state = TrackingState::from_events(events.clone());
+        self.sessions = build_sessions(events);
+        Ok(())
+    }
+
+    /// Re-reads the log if it changed since the last look (e.g. because the
+    /// CLI appended an event directly).
+    pub fn refresh(&mut self) -> anyhow::Result<&TrackingState> {
+        if state::log_stamp(&self.log)? != self.stamp {
+            self.reload()?;
+        }
+        Ok(&self.state)
+    }
+
+    /// Current state together with the finished sessions.
+    pub fn snapshot(&mut self) -> anyhow::Result<(&TrackingState, &[Session])> {
+        self.refresh()?;
+        Ok((&self.state, &self.sessions))
+    }
+
+    /// Records an event for the daemon's user, plus any `extra` fields.
+    fn record(
+        &self,
+        ts: Option<&str>,
+        ty: &str,
+        extra: &[(&str, &str)],
+        content: Option<String>,
+    ) -> anyhow::Result<()> {
+        let user = self.user.as_deref();
+        history::begin(format!("daemon: {ty}"));
+        let Some(ts) = ts else {
+            return record(Some(&self.log), user, ty, extra, content, false);
+        };
+        let mut fields = user_field(user);
+        fields.extend_from_slice(extra);
+        fields.extend(close::guard(&self.log, user, ts, false)?);
+        record_event_at(Some(&self.log), ts, ty, &fields, content)
+    }
+
+    fn handle(&mut self, req: &str) -> anyhow::Result<String> {
+        let (cmd, arg) = match req.split_once(' ') {
+            Some((c, a)) => (c, Some(a.to_string())),
+            None => (req, None),
+        };
+        match cmd {
+            "status" => {}
+            "hours" => {
+                let period = Period::from_str(arg.as_deref().unwrap_or("today"), true)
+                    .map_err(|_| anyhow::anyhow!("unknown period"))?;
+                let (st, sessions) = self.snapshot()?;
+                return Ok(hours::secs(st, sessions, period, state::now())?.to_string());
+            }
+            "start" | "break_start" | "break_end" => self.record(None, cmd, &[], None)?,
+            "finish" => self.record(None, cmd, &[], arg)?,
+            _ => anyhow::bail!("unknown request: {cmd}"),
+        }
+        Ok(self.refresh()?.to_fields())
+    }
+
+    /// Records an event received over HTTP, tagged `source=http`.
+    fn ingest(&mut self, ty: &str, content: Option<String>) -> anyhow::Result<&TrackingState> {
+        match ty {
+            "start" | "break_start" | "break_end" | "finish" => {
+                let content = if ty == "finish" { content } else { None };
+                self.record(None, ty, &[("source", "http")], content)?;
+            }
+            _ => anyhow::bail!("unknown event type: {ty}"),
+        }
+        self.refresh()
+    }
+
+    /// Starts a session while clocked out, tagged `auto=<reason>`. Returns
+    /// whether it did.
+    pub fn auto_start(&mut self, reason: &str) -> anyhow::Result<bool> {
+        if self.refresh()?.phase != Phase::Idle {
+            return Ok(false);
+        }
+        self.record(None, "start", &[("auto", reason)], None)?;
+        Ok(true)
+    }
+
+    /// Starts a break (`start`) while working, or ends the current break if
+    /// it was started here for the same `reason`. The events carry
+    /// `auto=<reason>`. Returns whether anything was recorded.
+    pub fn auto_break(&mut self, start: bool, reason: &str) -> anyhow::Result<bool> {
+        let st = self.refresh()?.clone();
+        let auto = [("auto", reason)];
+        match (start, st.phase) {
+            (true, Phase::Working) => {
+                self.record(None, "break_start", &auto, None)?;
+                let since = self.refresh()?.break_since;
+                self.auto_break = since.map(|since| (reason.to_string(), since));
+            }
+            (false, Phase::OnBreak)
+                if self
+                    .auto_break
+                    .as_ref()
+                    .is_some_and(|(r, since)| r == reason && Some(*since) == st.break_since) =>
+            {
+                self.record(None, "break_end", &auto, None)?;
+                self.auto_break = None;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Records the configured shutdown event. Events written here carry
+    /// `auto=shutdown` so they can be told apart from manual ones.
+    fn shut_down(&mut self, action: ShutdownAction) -> anyhow::Result<()> {
+        let st = self.refresh()?.clone();
+        let auto = [("auto", "shutdown")];
+        match (action, st.phase) {
+            (ShutdownAction::Break, Phase::Working) => {
+                self.record(None, "break_start", &auto, None)?
+            }
+            (ShutdownAction::Finish, Phase::Working) => self.record(None, "finish", &auto, None)?,
+            // Already on break: the session really ended when the break began.
+            (ShutdownAction::Finish, Phase::OnBreak) => {
+                let ts = st.break_since.unwrap_or_else(state::now).to_rfc3339();
+                self.record(Some(&ts), "finish", &auto, None)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// How long a client may take to send its request or read the reply.
+/// Connections are served one at a time, so a stalled client holds up the
+/// others for at most this long.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn serve(daemon: &Mutex<Daemon>, stream: UnixStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut req = String::new();
+    BufReader::new(&stream).read_line(&mut req)?;
+    let reply = match daemon.lock().unwrap().handle(req.trim()) {
+        Ok(fields) => format!("ok {fields}"),
+        Err(e) => format!("error {e}"),
+    };
+    writeln!(&stream, "{reply}")?;
+    Ok(())
+}
+
+fn serve_http(daemon: &Mutex<Daemon>, token: Option<&str>, req: &Request) -> Response {
+    match (req.method.as_str(), req.path.as_str()) {
+        ("GET", "/metrics") => match daemon.lock().unwrap().snapshot() {
+            Ok((st, sessions)) => Response::new(
+                200,
+                "text/plain; version=0.0.4",
+                metrics::render(st, sessions, state::now()),
+            ),
+            Err(e) => Response::text(500, format!("{e}\n")),
+        },
+        ("POST", "/events") => {
+            let Some(token) = token else {
+                return Response::text(
+                    403,
+                    "event ingest is disabled; set http.token in the config\n",
+                );
+            };
+            if !authorized(req, token) {
+                return Response::text(401, "invalid or missing bearer token\n")
+                    .with_header("WWW-Authenticate", "Bearer");
+            }
+            let (ty, content) = match ingest_body(req) {
+                Ok(parsed) => parsed,
+                Err(e) => return Response::text(400, format!("{e}\n")),
+            };
+            match daemon.lock().unwrap().ingest(&ty, content) {
+                Ok(st) => Response::text(201, format!("{}\n", st.describe(state::now()))),
+                Err(e) => Response::text(400, format!("{e}\n")),
+            }
+        }
+        _ => Response::text(404, "not found\n"),
+    }
+}
+
+fn authorized(req: &Request, token: &str) -> bool {
+    let Some(given) = req
+        .header("Authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Compare without short-circuiting so timing does not leak the prefix.
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Accepts `{"type": "start", "content": "..."}` or the same keys as a
+/// form-encoded body, which is what iOS Shortcuts and most NFC apps send.
+fn ingest_body(req: &Request) -> anyhow::Result<(String, Option<String>)> {
+    let body = std::str::from_utf8(&req.body)?;
+    let is_json = req
+        .header("Content-Type")
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let (ty, content) = if is_json {
+        let v: serde_json::Value = serde_json::from_str(body)?;
+        (
+            v["type"].as_str().map(str::to_string),
+            v["content"].as_str().map(str::to_string),
+        )
+    } else {
+        let mut ty = None;
+        let mut content = None;
+        for pair in body.split('&') {
+            match pair.split_once('=') {
+                Some(("type", v)) => ty = Some(http::url_decode(v)),
+                Some(("content", v)) => content = Some(http::url_decode(v)),
+                _ => {}
+            }
+        }
+        (ty, content)
+    };
+    let ty = ty.ok_or_else(|| anyhow::anyhow!("missing `type`"))?;
+    Ok((ty, content.filter(|c| !c.is_empty())))
+}
+
+/// Notifies once per stretch of work that runs longer than `limit` hours
+/// without a break.
+fn watch_breaks(daemon: Arc<Mutex<Daemon>>, limit: f64) {
+    thread::spawn(move || {
+        let mut warned = None;
+        loop {
+            thread::sleep(Duration::from_secs(60));
+            let st = match daemon.lock().unwrap().refresh() {
+                Ok(st) => st.clone(),
+                Err(_) => continue,
+            };
+            let stretch = st.resumed.or(st.session_start);
+            if let Some(warning) = state::break_warning(&st, state::now(), limit)
+                && warned != stretch
+            {
+                warned = stretch;
+                eprintln!("kintai daemon: {warning}");
+                if let Err(e) = state::desktop_notify(&warning, true) {
+                    eprintln!("kintai daemon: {e}");
+                }
+            }
+        }
+    });
+}
+
+/// Whether `addr` only accepts connections from this machine.
+fn loopback(addr: &str) -> bool {
+    addr.to_socket_addrs()
+        .is_ok_and(|mut addrs| addrs.all(|a| a.ip().is_loopback()))
+}
+
+pub fn run(
+    log: &Path,
+    user: Option<&str>,
+    on_shutdown: ShutdownAction,
+    http_addr: Option<&str>,
+) -> anyhow::Result<()> {
+    let sock = socket_path(log, user);
+    if UnixStream::connect(&sock).is_ok() {
+        anyhow::bail!("a daemon is already serving {}", log.display());
+    }
+    if let Some(dir) = sock.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let _ = fs::remove_file(&sock);
+    let listener = UnixListener::bind(&sock)?;
+    // Poll instead of blocking in accept() so a termination signal is noticed.
+    listener.set_nonblocking(true)?;
+    install_signal_handlers();
+    let daemon = Arc::new(Mutex::new(Daemon::new(log, user)?));
+    eprintln!("kintai daemon listening on {}", sock.display());
+
+    if let Some(addr) = http_addr {
+        let daemon = Arc::clone(&daemon);
+        let addr = addr.to_string();
+        let token = Config::load()?.str("http.token").map(str::to_string);
+        if token.is_some() && !loopback(&addr) {
+            eprintln!(
+                "kintai daemon: warning: {addr} is reachable from other machines, and http.token \
+                 is sent over plain HTTP; listen on localhost or put a TLS proxy in front"
+            );
+        }
+        thread::spawn(move || {
+            let served = http::serve(&addr, move |req| serve_http(&daemon, token.as_deref(), req));
+            if let Err(e) = served {
+                eprintln!("kintai daemon: http: {e}");
+            }
+        });
+    }
+
+    let config = Config::load()?;
+    schedule::spawn(schedule::jobs(&config)?, log, user);
+    watch_breaks(Arc::clone(&daemon), state::max_continuous_hours(&config));
+
+    #[cfg(feature = "tray")]
+    if let Err(e) = crate::tray::spawn(log, Arc::clone(&daemon)) {
+        eprintln!("kintai daemon: tray icon disabled: {e}");
+    }
+    wifi::spawn(
+        wifi::rules(&config)?,
+        config.str("wifi.command").map(str::to_string),
+        Arc::clone(&daemon),
+    );
+    #[cfg(feature = "screenlock")]
+    if let Err(e) = crate::screenlock::spawn(Arc::clone(&daemon)) {
+        eprintln!("kintai daemon: screen lock tracking disabled: {e}");
+    }
+
+    while !TERMINATED.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((s, _)) => {
+                if let Err(e) = s
+                    .set_nonblocking(false)
+                    .map_err(Into::into)
+                    .and_then(|_| serve(&daemon, s))
+                {
+                    eprintln!("kintai daemon: {e}");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(100))
+            }
+            Err(e) => eprintln!("kintai daemon: {e}"),
+        }
+    }
+
+    let _ = fs::remove_file(&sock);
+    daemon.lock().unwrap().shut_down(on_shutdown)
+}
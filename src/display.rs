@@ -0,0 +1,222 @@
+//! Date and time display formats (`[format]`), as strftime-style strings.
+//! Sessions keep `YYYY/MM/DD` and `HH:MM` internally; these only change how
+//! reports show them. `format.hours` and `format.precision` choose how
+//! durations are shown.
+
+use chrono::{
+    Datelike, NaiveDate, NaiveTime,
+    format::{Item, StrftimeItems},
+};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+
+#[derive(Default)]
+pub struct Formats {
+    /// `format.date`, for session dates.
+    pub date: Option<String>,
+    /// `format.time`, for the times in time ranges.
+    pub time: Option<String>,
+    /// `format.month`, for month labels.
+    pub month: Option<String>,
+    /// `format.era`: Japanese era years (令和6年) in spreadsheet titles and dates.
+    pub era: bool,
+}
+
+impl Formats {
+    /// Reads `[format]`, rejecting strings chrono cannot format with, which
+    /// would otherwise panic in the middle of a report.
+    pub fn load(config: &Config) -> anyhow::Result<Formats> {
+        let get = |key: &str| -> anyhow::Result<Option<String>> {
+            let Some(fmt) = config.str(key) else {
+                return Ok(None);
+            };
+            if StrftimeItems::new(fmt).any(|item| item == Item::Error) {
+                anyhow::bail!("{key} is not a valid strftime format: {fmt:?}");
+            }
+            Ok(Some(fmt.to_string()))
+        };
+        Ok(Formats {
+            date: get("format.date")?,
+            time: get("format.time")?,
+            month: get("format.month")?,
+            era: config.bool("format.era").unwrap_or(false),
+        })
+    }
+
+    /// Formats a session date (`YYYY/MM/DD`).
+    pub fn date(&self, date: &str) -> String {
+        match (&self.date, NaiveDate::parse_from_str(date, "%Y/%m/%d")) {
+            (Some(fmt), Ok(d)) => d.format(fmt).to_string(),
+            _ => date.to_string(),
+        }
+    }
+
+    /// Formats a month label (`YYYY/MM`, or `YYYY-MM` as in event fields).
+    pub fn month(&self, month: &str) -> String {
+        match (
+            &self.month,
+            NaiveDate::parse_from_str(&format!("{}/01", month.replace('-', "/")), "%Y/%m/%d"),
+        ) {
+            (Some(fmt), Ok(d)) => d.format(fmt).to_string(),
+            _ => month.to_string(),
+        }
+    }
+
+    /// Formats each time of a `HH:MM~HH:MM,...` range. `24:00` is kept as is.
+    pub fn time_range(&self, range: &str) -> String {
+        let Some(fmt) = &self.time else {
+            return range.to_string();
+        };
+        let time = |t: &str| match NaiveTime::parse_from_str(t, "%H:%M") {
+            Ok(t) => t.format(fmt).to_string(),
+            Err(_) => t.to_string(),
+        };
+        range
+            .split(',')
+            .map(|part| match part.split_once('~') {
+                Some((a, b)) => format!("{}~{}", time(a), time(b)),
+                None => part.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Starts of the Japanese eras, newest first.
+const ERAS: [(&str, i32, u32, u32); 4] = [
+    ("令和", 2019, 5, 1),
+    ("平成", 1989, 1, 8),
+    ("昭和", 1926, 12, 25),
+    ("大正", 1912, 7, 30),
+];
+
+/// The Japanese era year of `date`, e.g. `令和6年` (the first year is `元年`).
+/// Dates before 大正 keep the Western year.
+pub fn era_year(date: NaiveDate) -> String {
+    for (name, y, m, d) in ERAS {
+        if date >= NaiveDate::from_ymd_opt(y, m, d).unwrap() {
+            return match date.year() - y + 1 {
+                1 => format!("{name}元年"),
+                n => format!("{name}{n}年"),
+            };
+        }
+    }
+    format!("{}年", date.year())
+}
+
+/// Columns `s` takes in a monospaced cell: East Asian wide and fullwidth
+/// characters (kanji, kana, fullwidth forms, most emoji) count as two,
+/// combining marks and zero-width characters as none.
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0..=0x1F | 0x7F..=0x9F => 0,
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0x3099..=0x309A => 0,
+        0xFE00..=0xFE0F | 0xFEFF => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// How durations are shown (`format.hours`).
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum HourStyle {
+    /// `7h30m`
+    #[default]
+    Hm,
+    /// `7.50h`
+    Decimal,
+    /// `7:30`
+    Clock,
+}
+
+/// The duration style of summaries, CSV and spreadsheets.
+#[derive(Clone, Copy)]
+pub struct Hours {
+    pub style: HourStyle,
+    /// `format.precision`: decimal places of decimal hours.
+    pub precision: usize,
+}
+
+impl Default for Hours {
+    fn default() -> Hours {
+        Hours {
+            style: HourStyle::Hm,
+            precision: 2,
+        }
+    }
+}
+
+impl Hours {
+    pub fn from_config(config: &Config) -> anyhow::Result<Hours> {
+        let style = match config.str("format.hours") {
+            None | Some("hm") => HourStyle::Hm,
+            Some("decimal") => HourStyle::Decimal,
+            Some("clock") => HourStyle::Clock,
+            Some(v) => {
+                anyhow::bail!("format.hours must be \"hm\", \"decimal\" or \"clock\", not {v:?}")
+            }
+        };
+        let precision = match config.get("format.precision") {
+            None => 2,
+            Some(v) => match v.as_num() {
+                Some(n) if (0.0..=6.0).contains(&n) && n.fract() == 0.0 => n as usize,
+                _ => anyhow::bail!("format.precision must be a whole number from 0 to 6"),
+            },
+        };
+        Ok(Hours { style, precision })
+    }
+
+    /// `h` hours in this style: `7h30m`, `7.50h` or `7:30`.
+    pub fn format(&self, h: f64) -> String {
+        let minutes = (h * 60.0).round() as i64;
+        let sign = if minutes < 0 { "-" } else { "" };
+        let (hours, mins) = (minutes.abs() / 60, minutes.abs() % 60);
+        match self.style {
+            HourStyle::Hm => format!("{sign}{hours}h{mins:02}m"),
+            HourStyle::Decimal => format!("{}h", self.decimal(h)),
+            HourStyle::Clock => format!("{sign}{hours}:{mins:02}"),
+        }
+    }
+
+    /// `h` with `precision` decimal places, as in `7.50`.
+    pub fn decimal(&self, h: f64) -> String {
+        format!("{h:.*}", self.precision)
+    }
+
+    /// Excel number format for decimal hours, as in `0.00`.
+    pub fn excel_format(&self) -> String {
+        match self.precision {
+            0 => "0".to_string(),
+            n => format!("0.{}", "0".repeat(n)),
+        }
+    }
+}
+
+static HOURS: OnceLock<Hours> = OnceLock::new();
+
+/// Sets the duration style; call before any report is printed.
+pub fn set_hours(hours: Hours) {
+    let _ = HOURS.set(hours);
+}
+
+pub fn hours() -> &'static Hours {
+    HOURS.get_or_init(Hours::default)
+}
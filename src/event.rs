@@ -0,0 +1,461 @@
+use chrono::DateTime;
+use clap::ValueEnum;
+use flate2::read::MultiGzDecoder;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    archive, chain, close,
+    config::Config,
+    history,
+    hooks::{self, HookEvent},
+    input, skew, state, text, time,
+};
+
+#[derive(Clone)]
+pub struct LogEvent {
+    pub ts: String,
+    pub ty: String,
+    pub content: Option<String>,
+    /// Any other `key=value` pairs on the line (e.g. `user=alice`).
+    pub fields: BTreeMap<String, String>,
+}
+
+impl LogEvent {
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.field("user")
+    }
+
+    /// The event's `id=`, or its timestamp for events recorded before IDs
+    /// existed. Corrections refer to events by this.
+    pub fn id(&self) -> &str {
+        self.field("id").unwrap_or(&self.ts)
+    }
+}
+
+/// A short random identifier for a new event. With a fixed clock, IDs are
+/// derived from the time and count up within the process instead, so the
+/// same commands give the same log.
+pub fn new_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let clock = time::clock();
+    if clock.is_fixed() {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let digest = Sha256::digest(format!("{} {n}", clock.now().to_rfc3339()));
+        return digest[..6].iter().map(|b| format!("{b:02x}")).collect();
+    }
+    let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+    h.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    h.write_u32(std::process::id());
+    format!("{:012x}", h.finish() & 0xffff_ffff_ffff)
+}
+
+/// The `user=` field to attach to recorded events, if a user is set.
+pub fn user_field(user: Option<&str>) -> Vec<(&'static str, &str)> {
+    user.map(|u| ("user", u)).into_iter().collect()
+}
+
+/// Keeps only the events of `user`; with no user, keeps everything.
+pub fn filter_user(events: Vec<LogEvent>, user: Option<&str>) -> Vec<LogEvent> {
+    match user {
+        Some(u) => events.into_iter().filter(|e| e.user() == Some(u)).collect(),
+        None => events,
+    }
+}
+
+/// How events are written to a log. Readers accept both on every line, so a
+/// log can hold a mix, e.g. after `log.format` was changed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// `key=value` pairs, the original format
+    Logfmt,
+    /// One JSON object per line (v2), which round-trips any content
+    Jsonl,
+}
+
+impl LogFormat {
+    /// `log.format` from the config, for new logs.
+    pub fn configured() -> anyhow::Result<LogFormat> {
+        match Config::load()?.str("log.format") {
+            None => Ok(LogFormat::Logfmt),
+            Some(v) => LogFormat::from_str(v, true).map_err(|_| {
+                anyhow::anyhow!("log.format must be \"logfmt\" or \"jsonl\", not {v:?}")
+            }),
+        }
+    }
+
+    /// The format of the first event line in `text`, if any.
+    pub fn sniff(text: &str) -> Option<LogFormat> {
+        let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+        Some(if line.starts_with('{') {
+            LogFormat::Jsonl
+        } else {
+            LogFormat::Logfmt
+        })
+    }
+
+    /// The format to append to `log` in: the one it is written in, or the
+    /// configured one for a new log.
+    pub fn of(log: &Path) -> anyhow::Result<LogFormat> {
+        let head = match File::open(log) {
+            Ok(f) => {
+                let mut head = String::new();
+                BufReader::new(f).take(4096).read_to_string(&mut head).ok();
+                head
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        match LogFormat::sniff(&head) {
+            Some(f) => Ok(f),
+            None => LogFormat::configured(),
+        }
+    }
+
+    /// Formats an event as one line. Extra fields go between `type` and
+    /// `content`, which always comes last.
+    pub fn format(
+        self,
+        ts: &str,
+        event_type: &str,
+        fields: &[(&str, &str)],
+        content: Option<&str>,
+    ) -> String {
+        match self {
+            LogFormat::Logfmt => format_event(ts, event_type, fields, content),
+            LogFormat::Jsonl => {
+                let json = |s: &str| serde_json::to_string(s).unwrap();
+                let mut line = format!("{{\"ts\":{},\"type\":{}", json(ts), json(event_type));
+                for (k, v) in fields {
+                    line.push_str(&format!(",{}:{}", json(k), json(v)));
+                }
+                if let Some(c) = content {
+                    line.push_str(&format!(",\"content\":{}", json(c)));
+                }
+                line.push('}');
+                line
+            }
+        }
+    }
+}
+
+/// Formats an event as a single logfmt line. Extra fields go between `type`
+/// and `content`, which always comes last. Line breaks, which logfmt cannot
+/// hold, become spaces; use `log.format = "jsonl"` to keep them.
+pub fn format_event(
+    ts: &str,
+    event_type: &str,
+    fields: &[(&str, &str)],
+    content: Option<&str>,
+) -> String {
+    let one_line = |s: &str| s.replace(['\r', '\n'], " ");
+    let mut line = format!("ts={ts} type={event_type}");
+    for (k, v) in fields {
+        if v.is_empty() || v.contains([' ', '"', '=', '\r', '\n']) {
+            line.push_str(&format!(" {k}=\"{}\"", one_line(v).replace('"', "\\\"")));
+        } else {
+            line.push_str(&format!(" {k}={v}"));
+        }
+    }
+    if let Some(c) = content {
+        let esc = one_line(c).replace('"', "\\\"");
+        line.push_str(&format!(" content=\"{esc}\""));
+    }
+    line
+}
+
+/// Records an event for `user` stamped with the current time, after checking
+/// it against the log (closed months, a clock set back). Checks are skipped
+/// when there is no log to check against.
+pub fn record(
+    log: Option<&Path>,
+    user: Option<&str>,
+    event_type: &str,
+    extra: &[(&str, &str)],
+    content: Option<String>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let ts = state::now().to_rfc3339();
+    let mut fields = user_field(user);
+    fields.extend_from_slice(extra);
+    if let Some(path) = log {
+        fields.extend(close::guard(path, user, &ts, force)?);
+        skew::check_clock(path, user, &ts, force)?;
+    }
+    record_event_at(log, &ts, event_type, &fields, content)
+}
+
+/// Assigns the event a fresh `id=` (and `prev=` when the log is hash-chained)
+/// and appends it to `log` when one is configured, otherwise prints it to
+/// stdout. Then starts the event's `[hooks]`.
+pub fn record_event_at(
+    log: Option<&Path>,
+    ts: &str,
+    event_type: &str,
+    fields: &[(&str, &str)],
+    content: Option<String>,
+) -> anyhow::Result<()> {
+    let id = new_id();
+    let prev = match log {
+        Some(path) if chain::enabled() => Some(chain::prev(path)?),
+        _ => None,
+    };
+    let mut all = vec![("id", id.as_str())];
+    all.extend(prev.as_deref().map(|p| ("prev", p)));
+    all.extend_from_slice(fields);
+    let format = match log {
+        Some(path) => LogFormat::of(path)?,
+        None => LogFormat::Logfmt,
+    };
+    let all: Vec<(&str, Cow<str>)> = all.into_iter().map(|(k, v)| (k, text::clean(v))).collect();
+    let all: Vec<(&str, &str)> = all.iter().map(|(k, v)| (*k, v.as_ref())).collect();
+    let content = content.map(|c| text::clean(&c).into_owned());
+    let line = format.format(ts, event_type, &all, content.as_deref());
+    match log {
+        Some(path) => {
+            append_line(path, &line)?;
+            if prev.is_some() {
+                chain::save_head(path, &line)?;
+            }
+            if let Err(e) = history::note(path, &id) {
+                eprintln!("kintai: cannot update the undo history: {e}");
+            }
+        }
+        None => println!("{line}"),
+    }
+    let user = fields.iter().find(|(k, _)| *k == "user").map(|(_, v)| *v);
+    hooks::run(
+        log,
+        &HookEvent {
+            ts,
+            ty: event_type,
+            id: &id,
+            content: content.as_deref(),
+            user,
+        },
+    )
+}
+
+pub fn append_line(path: &Path, line: &str) -> anyhow::Result<()> {
+    if path
+        .extension()
+        .is_some_and(|ext| ext == "gz" || ext == "zst")
+    {
+        anyhow::bail!(
+            "{} is compressed; record events to an uncompressed log",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(f, "{line}")?;
+    Ok(())
+}
+
+pub fn read_events(input: Option<PathBuf>) -> anyhow::Result<Vec<LogEvent>> {
+    let Some(path) = input else {
+        return parse_events(decompress(io::stdin())?);
+    };
+    let files = input::files(&path)?;
+    let mut events = match files.as_slice() {
+        [file] => archived(file)?,
+        _ => Vec::new(),
+    };
+    for file in &files {
+        let f = File::open(file).map_err(|e| anyhow::anyhow!("{}: {e}", file.display()))?;
+        events.extend(parse_events(decompress(f)?)?);
+    }
+    let stdin = input::with_stdin();
+    if stdin {
+        // Events piped in may repeat ones already in the log.
+        events.extend(parse_events(decompress(io::stdin())?)?);
+        let mut seen = HashSet::new();
+        events.retain(|e| seen.insert((e.id().to_string(), e.ty.clone(), e.content.clone())));
+    }
+    if files.len() > 1 || stdin {
+        // Events from several sources are merged in time order.
+        events.sort_by_cached_key(|e| DateTime::parse_from_rfc3339(&e.ts).ok());
+    }
+    Ok(events)
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Reads gzip and zstd data decompressed, told apart from plain text by
+/// their magic bytes. zstd is decompressed by the `zstd` command.
+pub fn decompress(reader: impl Read + 'static) -> anyhow::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+    if head.starts_with(&GZIP_MAGIC) {
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+    }
+    if !head.starts_with(&ZSTD_MAGIC) {
+        return Ok(Box::new(reader));
+    }
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut child = Command::new("zstd")
+        .args(["-d", "-c", "-q"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("cannot run zstd to read zstd-compressed events: {e}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(&data));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        anyhow::bail!("zstd failed to decompress the events ({})", output.status);
+    }
+    Ok(Box::new(io::Cursor::new(output.stdout)))
+}
+
+/// The archived events of `log`, with `--include-archived`.
+fn archived(log: &Path) -> anyhow::Result<Vec<LogEvent>> {
+    if archive::included() {
+        archive::read(log)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Reads a log file; a log that does not exist yet has no events.
+pub fn read_log(path: &Path) -> anyhow::Result<Vec<LogEvent>> {
+    let mut events = archived(path)?;
+    match File::open(path) {
+        Ok(f) => events.extend(parse_events(decompress(f)?)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(events)
+}
+
+pub fn parse_events(reader: impl BufRead) -> anyhow::Result<Vec<LogEvent>> {
+    let mut events: Vec<LogEvent> = Vec::new();
+    for line in reader.lines() {
+        if let Some(e) = parse_line(&line?) {
+            events.push(e);
+        }
+    }
+    Ok(events)
+}
+
+/// Parses one line, JSON or logfmt. Lines without both `ts` and `type` are
+/// ignored.
+pub fn parse_line(line: &str) -> Option<LogEvent> {
+    let mut e = if line.trim_start().starts_with('{') {
+        parse_json_line(line)?
+    } else {
+        parse_logfmt_line(line)?
+    };
+    // Text recorded before it was cleaned on write compares equal too.
+    if let Some(c) = &mut e.content
+        && let Cow::Owned(clean) = text::clean(c)
+    {
+        *c = clean;
+    }
+    for v in e.fields.values_mut() {
+        if let Cow::Owned(clean) = text::clean(v) {
+            *v = clean;
+        }
+    }
+    Some(e)
+}
+
+/// A v2 line: a JSON object of strings. Other values are kept as their JSON
+/// text, and nulls are dropped.
+fn parse_json_line(line: &str) -> Option<LogEvent> {
+    let serde_json::Value::Object(map) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+    let mut fields: BTreeMap<String, String> = map
+        .into_iter()
+        .filter_map(|(k, v)| match v {
+            serde_json::Value::String(s) => Some((k, s)),
+            serde_json::Value::Null => None,
+            v => Some((k, v.to_string())),
+        })
+        .collect();
+    let ts = fields.remove("ts")?;
+    let ty = fields.remove("type")?;
+    let content = fields.remove("content");
+    Some(LogEvent {
+        ts,
+        ty,
+        content,
+        fields,
+    })
+}
+
+/// A logfmt line. Quoted values have their `\"` escapes undone; nothing else
+/// was ever escaped on write.
+fn parse_logfmt_line(line: &str) -> Option<LogEvent> {
+    let mut fields = BTreeMap::new();
+    let mut rest = line.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            // `content` used to be matched greedily up to the last quote on
+            // the line; keep that so unescaped quotes inside it survive.
+            let end = if key == "content" {
+                quoted.rfind('"')
+            } else {
+                closing_quote(quoted)
+            };
+            match end {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            let end = after.find(' ').unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if !key.is_empty() && !key.contains(' ') {
+            fields.insert(key.to_string(), value.replace("\\\"", "\""));
+        }
+        rest = next.trim_start();
+    }
+    let ts = fields.remove("ts")?;
+    let ty = fields.remove("type")?;
+    let content = fields.remove("content");
+    Some(LogEvent {
+        ts,
+        ty,
+        content,
+        fields,
+    })
+}
+
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
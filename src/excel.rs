@@ -0,0 +1,812 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use clap::ValueEnum;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use umya_spreadsheet::{
+    Cell, Spreadsheet, Worksheet, new_file,
+    structs::{
+        Border, Chart, ChartType, Image, OrientationValues, Style, drawing::spreadsheet::MarkerType,
+    },
+    writer::xlsx::{write, write_with_password},
+};
+
+use crate::{
+    calendar::Calendar,
+    columns::{Columns, months},
+    config::Config,
+    display::{self, Formats, HourStyle, display_width, era_year},
+    event::{filter_user, read_events},
+    overtime::daily_limit,
+    session::{Session, build_sessions, users},
+};
+
+/// How the workbook is locked down for recipients.
+#[derive(Default)]
+pub struct ExcelOptions {
+    /// Protect the sheet against edits, with this password if non-empty.
+    pub protect: Option<String>,
+    /// Also encrypt the file with the protection password.
+    pub encrypt: bool,
+    /// One row for every day of the month.
+    pub full_month: bool,
+    /// Sign-off block below the totals.
+    pub approval: Option<Approval>,
+    /// Start, end and worked time as Excel time values instead of text.
+    pub native_times: bool,
+    /// Put month totals in a "Summary" sheet before the per-day "Detail" sheet.
+    pub summary_sheet: bool,
+    /// Hourly rate for the salary on the summary sheet.
+    pub rate: Option<f64>,
+    /// Add a bar chart of hours per day below the table.
+    pub chart: bool,
+}
+
+/// Sign-off block appended below the totals.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Approval {
+    /// 担当者印 and 上長印 boxes for seals
+    Stamps,
+    /// Name and date lines for the employee and the manager
+    Lines,
+}
+
+const WEEKDAYS_JP: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+/// Whether a row's day is a weekday, a Saturday, or a Sunday or holiday
+/// (see [`Calendar::is_holiday`]), which 勤務表 sheets color blue and red.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DayKind {
+    Weekday,
+    Saturday,
+    Holiday,
+}
+
+pub struct SheetRow {
+    pub date: String,
+    /// Day of the week, e.g. `月`.
+    pub weekday: &'static str,
+    pub kind: DayKind,
+    pub time: String,
+    pub content: String,
+    /// First start and last end in minutes since midnight (the end may run
+    /// past 24:00), for rows with sessions.
+    pub span: Option<(i64, i64)>,
+    /// Worked minutes.
+    pub minutes: i64,
+}
+
+/// The month exported to a spreadsheet: the first month in the log.
+pub struct MonthSheet {
+    pub year: String,
+    pub month: String,
+    pub title: String,
+    /// One row per session (or per day, for full-month sheets).
+    pub rows: Vec<SheetRow>,
+    pub total_label: String,
+    pub total_minutes: i64,
+    /// Days with at least one session.
+    pub days_worked: usize,
+    /// Minutes beyond `overtime.daily_hours`, summed over the days.
+    pub overtime_minutes: i64,
+    /// The month's value of each `[columns]` entry.
+    pub columns: Vec<(String, f64)>,
+}
+
+impl MonthSheet {
+    /// `YYYY_MM_勤務時間[_user].<ext>`, or `YYYY_MM_kinmu[_user].<ext>` on
+    /// Windows, where non-ASCII names get garbled by cp932 consoles and tools.
+    pub fn default_path(&self, user: Option<&str>, ext: &str) -> PathBuf {
+        let name = if cfg!(windows) {
+            "kinmu"
+        } else {
+            "勤務時間"
+        };
+        match user {
+            Some(u) => PathBuf::from(format!("{}_{}_{name}_{u}.{ext}", self.year, self.month)),
+            None => PathBuf::from(format!("{}_{}_{name}.{ext}", self.year, self.month)),
+        }
+    }
+}
+
+/// Reads the log and builds the sheet for its first month, or `None` when
+/// there is nothing to export. With `full_month`, every day of the month gets
+/// a row (also when `excel.full_month` is set). `rate` is the `rate` of
+/// computed columns.
+pub fn month_sheet(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    full_month: bool,
+    rate: Option<f64>,
+) -> anyhow::Result<Option<MonthSheet>> {
+    let events = filter_user(read_events(input)?, user);
+    let sessions = build_sessions(events);
+    let config = Config::load()?;
+    let formats = Formats::load(&config)?;
+    let full_month = full_month || config.bool("excel.full_month").unwrap_or(false);
+
+    let people = users(&sessions);
+    if people.len() > 1 {
+        anyhow::bail!(
+            "the log contains several users ({}); pick one with --user",
+            people.join(", ")
+        );
+    }
+
+    if sessions.is_empty() {
+        println!("Log data is empty. Skipping Excel output.");
+        return Ok(None);
+    }
+
+    let first_date = &sessions[0].date;
+    let first_ym = &first_date[..7];
+    let parts: Vec<&str> = first_ym.split('/').collect();
+    let year = parts[0];
+    let month = parts[1];
+    let year_label = match NaiveDate::parse_from_str(&format!("{first_ym}/01"), "%Y/%m/%d") {
+        Ok(d) if formats.era => era_year(d),
+        _ => format!("{year}年"),
+    };
+    let title_text = format!(
+        "{year_label}{}月の勤務時間記録",
+        month.trim_start_matches('0')
+    );
+
+    let filtered: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| s.date.starts_with(first_ym))
+        .collect();
+
+    let day_label = |date: &str| {
+        if formats.date.is_some() {
+            return formats.date(date);
+        }
+        let parts: Vec<&str> = date.split('/').collect();
+        let mm: &str = parts[1];
+        let dd: &str = parts[2];
+        let era = match NaiveDate::parse_from_str(date, "%Y/%m/%d") {
+            Ok(d) if formats.era => era_year(d),
+            _ => String::new(),
+        };
+        format!(
+            "{era}{}月{}日",
+            mm.trim_start_matches('0'),
+            dd.trim_start_matches('0')
+        )
+    };
+    let calendar = Calendar::load(&config)?;
+    let row = |date: &str, time: String, content: String, session: Option<&Session>| {
+        let day = NaiveDate::parse_from_str(date, "%Y/%m/%d").ok();
+        let kind = match day {
+            Some(d) if d.weekday() == Weekday::Sun || calendar.is_holiday(d) => DayKind::Holiday,
+            Some(d) if d.weekday() == Weekday::Sat => DayKind::Saturday,
+            _ => DayKind::Weekday,
+        };
+        SheetRow {
+            date: day_label(date),
+            weekday: day.map_or("", |d| {
+                WEEKDAYS_JP[d.weekday().num_days_from_monday() as usize]
+            }),
+            kind,
+            time,
+            content,
+            span: session.and_then(|s| {
+                let parts = s.parts();
+                let (start, mut end) = (parts.first()?.1, parts.last()?.2);
+                if end < start {
+                    end += 24 * 60;
+                }
+                Some((start, end))
+            }),
+            minutes: session.map_or(0, |s| s.minutes()),
+        }
+    };
+    let session_row = |s: &Session| {
+        row(
+            &s.date,
+            formats.time_range(&s.time_range),
+            s.content.clone().unwrap_or_default(),
+            Some(s),
+        )
+    };
+
+    let mut rows: Vec<SheetRow> = Vec::new();
+    if full_month {
+        // One row per calendar day; days off without sessions are marked 休.
+        let first = NaiveDate::parse_from_str(&format!("{first_ym}/01"), "%Y/%m/%d")?;
+        for day in first.iter_days().take_while(|d| d.month() == first.month()) {
+            let date = day.format("%Y/%m/%d").to_string();
+            let before = rows.len();
+            rows.extend(
+                filtered
+                    .iter()
+                    .filter(|s| s.date == date)
+                    .map(|s| session_row(s)),
+            );
+            if rows.len() == before {
+                let off = if calendar.is_workday(day) { "" } else { "休" };
+                rows.push(row(&date, off.to_string(), String::new(), None));
+            }
+        }
+    } else {
+        rows.extend(filtered.iter().map(|s| session_row(s)));
+    }
+    let total_minutes: i64 = filtered.iter().map(|s| s.minutes()).sum();
+    let mut per_day: BTreeMap<&str, i64> = BTreeMap::new();
+    for s in &filtered {
+        *per_day.entry(&s.date).or_default() += s.minutes();
+    }
+    let limit = (daily_limit(&config) * 60.0) as i64;
+    let overtime_minutes = per_day.values().map(|m| (m - limit).max(0)).sum();
+
+    let computed = Columns::load(&config)?;
+    let columns = match months(&computed.days(filtered.iter().copied(), rate, &calendar)?).pop() {
+        Some((_, _, values)) => computed.names().map(str::to_string).zip(values).collect(),
+        None => Vec::new(),
+    };
+
+    let total_label = {
+        let hours = display::hours();
+        match hours.style {
+            HourStyle::Hm => format!("{}時間{}分", total_minutes / 60, total_minutes % 60),
+            HourStyle::Decimal => format!("{}時間", hours.decimal(total_minutes as f64 / 60.0)),
+            HourStyle::Clock => hours.format(total_minutes as f64 / 60.0),
+        }
+    };
+
+    Ok(Some(MonthSheet {
+        year: year.to_string(),
+        month: month.to_string(),
+        title: title_text,
+        rows,
+        total_label,
+        total_minutes,
+        days_worked: per_day.len(),
+        overtime_minutes,
+        columns,
+    }))
+}
+
+/// Column widths (in characters) of the weekday and clock-time columns. The
+/// date, time-range and content columns are sized to their longest entry,
+/// the content column within `CONTENT_WIDTH`.
+const WEEKDAY_WIDTH: f64 = 5.0;
+const CLOCK_WIDTH: f64 = 8.0;
+const CONTENT_WIDTH: (f64, f64) = (12.0, 80.0);
+
+/// Width of a column with `header` and `cells`: the widest display width,
+/// where full-width characters count double, plus one for padding.
+fn fit_width<'a>(header: &str, cells: impl Iterator<Item = &'a str>) -> f64 {
+    let widest = cells
+        .map(display_width)
+        .fold(display_width(header), usize::max);
+    widest as f64 + 1.0
+}
+
+/// Excel number format for times and durations, which may exceed 24 hours.
+const DURATION_FORMAT: &str = "[h]:mm";
+
+/// Sets `cell` to a duration of `minutes`: an Excel duration, or with
+/// `format.hours = "decimal"` a number of hours.
+fn set_duration(cell: &mut Cell, minutes: i64) {
+    let hours = display::hours();
+    let (value, format) = match hours.style {
+        HourStyle::Decimal => (minutes as f64 / 60.0, hours.excel_format()),
+        _ => (minutes as f64 / (24.0 * 60.0), DURATION_FORMAT.to_string()),
+    };
+    cell.set_value_number(value);
+    cell.get_style_mut()
+        .get_number_format_mut()
+        .set_format_code(format);
+}
+
+fn col_to_letter(mut col: u32) -> String {
+    let mut s = String::new();
+    while col > 0 {
+        let rem = ((col - 1) % 26) as u8;
+        s.push((b'A' + rem) as char);
+        col = (col - 1) / 26;
+    }
+    s.chars().rev().collect()
+}
+
+fn coord(col: u32, row: u32) -> String {
+    format!("{}{}", col_to_letter(col), row)
+}
+
+/// Labelled lines of the company header block (`[excel.header]`), in order.
+/// The employee name defaults to `--user`.
+fn header_block(config: &Config, user: Option<&str>) -> Vec<(&'static str, String)> {
+    [
+        ("会社名", "company"),
+        ("所属", "department"),
+        ("氏名", "employee"),
+        ("社員番号", "employee_id"),
+    ]
+    .into_iter()
+    .filter_map(|(label, key)| {
+        let value = config.str(&format!("excel.header.{key}"));
+        let value = match key {
+            "employee" => value.or(user),
+            _ => value,
+        };
+        Some((label, value?.to_string()))
+    })
+    .collect()
+}
+
+/// Places the logo image (`excel.header.logo`) at the top right of the sheet.
+fn add_logo(sheet: &mut Worksheet, path: &str) -> anyhow::Result<()> {
+    let supported = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| {
+            matches!(
+                e.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif"
+            )
+        });
+    if !supported {
+        anyhow::bail!("excel.header.logo must be a PNG, JPEG or GIF image: {path}");
+    }
+    fs::metadata(path).map_err(|e| anyhow::anyhow!("excel.header.logo {path}: {e}"))?;
+    let mut marker = MarkerType::default();
+    marker.set_coordinate("D1");
+    let mut image = Image::default();
+    image.new_image(path, marker);
+    sheet.add_image(image);
+    Ok(())
+}
+
+/// Writes the sign-off block starting at `row` in a table `columns` wide and
+/// returns its last row.
+fn add_approval(sheet: &mut Worksheet, approval: Approval, row: u32, columns: u32) -> u32 {
+    fn border(sheet: &mut Worksheet, cell: String, sides: &[&str]) {
+        let borders = sheet.get_cell_mut(cell).get_style_mut().get_borders_mut();
+        for side in sides {
+            let b = match *side {
+                "left" => borders.get_left_mut(),
+                "right" => borders.get_right_mut(),
+                "top" => borders.get_top_mut(),
+                _ => borders.get_bottom_mut(),
+            };
+            b.set_border_style(Border::BORDER_THIN);
+        }
+    }
+    const BOX: [&str; 4] = ["left", "right", "top", "bottom"];
+
+    match approval {
+        Approval::Stamps => {
+            for (col, label) in [(columns - 1, "担当者印"), (columns, "上長印")] {
+                sheet
+                    .get_cell_mut(coord(col, row))
+                    .set_value(label.to_string());
+                border(sheet, coord(col, row), &BOX);
+                border(sheet, coord(col, row + 1), &BOX);
+            }
+            sheet.get_row_dimension_mut(&(row + 1)).set_height(45.0);
+            row + 1
+        }
+        Approval::Lines => {
+            for (i, label) in ["担当者", "上長"].into_iter().enumerate() {
+                let r = row + 2 * i as u32;
+                sheet.get_cell_mut(coord(1, r)).set_value(label.to_string());
+                sheet
+                    .get_cell_mut(coord(columns - 1, r))
+                    .set_value("日付".to_string());
+                border(sheet, coord(2, r), &["bottom"]);
+                border(sheet, coord(columns, r), &["bottom"]);
+            }
+            row + 2
+        }
+    }
+}
+
+/// Rows taken by the hours-per-day chart.
+const CHART_ROWS: u32 = 16;
+
+/// Adds a bar chart of hours per day below `last_row` and returns the chart's
+/// last row. The plotted hours go in two helper columns right of the
+/// `columns`-wide table, outside the print area.
+fn add_daily_chart(
+    sheet: &mut Worksheet,
+    sheet_name: &str,
+    rows: &[SheetRow],
+    header_row: u32,
+    columns: u32,
+    last_row: u32,
+) -> u32 {
+    let mut days: Vec<(&str, i64)> = Vec::new();
+    for row in rows {
+        match days.last_mut() {
+            Some((date, minutes)) if *date == row.date => *minutes += row.minutes,
+            _ => days.push((&row.date, row.minutes)),
+        }
+    }
+    let (label_col, hours_col) = (columns + 2, columns + 3);
+    sheet
+        .get_cell_mut(coord(label_col, header_row))
+        .set_value("日付".to_string());
+    sheet
+        .get_cell_mut(coord(hours_col, header_row))
+        .set_value("時間".to_string());
+    for (i, (date, minutes)) in days.iter().enumerate() {
+        let r = header_row + 1 + i as u32;
+        sheet
+            .get_cell_mut(coord(label_col, r))
+            .set_value(date.to_string());
+        sheet
+            .get_cell_mut(coord(hours_col, r))
+            .set_value_number((*minutes as f64 / 60.0 * 100.0).round() / 100.0);
+    }
+    let hours_letter = col_to_letter(hours_col);
+    let series = format!(
+        "{sheet_name}!${hours_letter}${}:${hours_letter}${}",
+        header_row + 1,
+        header_row + days.len() as u32
+    );
+
+    let top = last_row + 2;
+    let mut from = MarkerType::default();
+    from.set_coordinate(format!("A{top}"));
+    let mut to = MarkerType::default();
+    to.set_coordinate(coord(columns + 1, top + CHART_ROWS));
+    let mut chart = Chart::default();
+    chart.new_chart(ChartType::BarChart, from, to, vec![series.as_str()]);
+    chart
+        .set_title("日別勤務時間")
+        .set_series_title(vec!["時間"])
+        .set_series_point_title(days.iter().map(|(d, _)| d.to_string()).collect());
+    sheet.add_chart(chart);
+    top + CHART_ROWS
+}
+
+/// Month totals shown on the summary sheet.
+struct SheetTotals {
+    month: String,
+    days_worked: usize,
+    total_minutes: i64,
+    overtime_minutes: i64,
+    /// `overtime.daily_hours`
+    limit: f64,
+    rate: Option<f64>,
+}
+
+/// Fills the summary sheet: month, days worked, hours, overtime and, with a
+/// rate, the salary. Hours are Excel durations (or decimal hours)
+/// so they can be summed.
+fn write_summary(sheet: &mut Worksheet, totals: &SheetTotals) {
+    let hours = totals.total_minutes as f64 / 60.0;
+    sheet
+        .get_cell_mut("A1")
+        .set_value(format!("{}の勤務集計", totals.month));
+    let labels = [
+        "対象月",
+        "勤務日数",
+        "勤務時間",
+        &format!("残業時間（1日{}時間超）", totals.limit),
+    ];
+    for (i, label) in labels.iter().enumerate() {
+        sheet
+            .get_cell_mut(coord(1, 3 + i as u32))
+            .set_value(label.to_string());
+    }
+    sheet.get_cell_mut("B3").set_value(totals.month.clone());
+    sheet
+        .get_cell_mut("B4")
+        .set_value_number(totals.days_worked as f64);
+    for (cell, minutes) in [
+        ("B5", totals.total_minutes),
+        ("B6", totals.overtime_minutes),
+    ] {
+        set_duration(sheet.get_cell_mut(cell), minutes);
+    }
+    if let Some(rate) = totals.rate {
+        sheet.get_cell_mut("A7").set_value("時給".to_string());
+        sheet.get_cell_mut("B7").set_value_number(rate);
+        sheet.get_cell_mut("A8").set_value("給与".to_string());
+        sheet
+            .get_cell_mut("B8")
+            .set_value_number((hours * rate).round());
+    }
+    sheet.get_column_dimension_mut("A").set_width(24.0);
+    sheet.get_column_dimension_mut("B").set_width(14.0);
+}
+
+/// Sets the sheet up to print on one A4 portrait page: column `widths`,
+/// print area, margins, the header row repeated on every page, and a scale
+/// small enough for all `last_row` rows to fit.
+fn setup_print(
+    sheet: &mut Worksheet,
+    sheet_name: &str,
+    sheet_index: u32,
+    header_row: u32,
+    last_row: u32,
+    widths: &[f64],
+) {
+    // A4 in points, less 0.5" side and 0.75" top/bottom margins.
+    const PRINTABLE_WIDTH: f64 = 595.0 - 72.0;
+    const PRINTABLE_HEIGHT: f64 = 842.0 - 108.0;
+    const ROW_HEIGHT: f64 = 15.0;
+
+    for (i, w) in widths.iter().enumerate() {
+        sheet
+            .get_column_dimension_mut(&col_to_letter(i as u32 + 1))
+            .set_width(*w);
+    }
+
+    // A column `w` characters wide is about `7w + 5` pixels, at 0.75pt each.
+    let width: f64 = widths.iter().map(|w| (w * 7.0 + 5.0) * 0.75).sum();
+    let height = last_row as f64 * ROW_HEIGHT;
+    let scale = (PRINTABLE_WIDTH / width)
+        .min(PRINTABLE_HEIGHT / height)
+        .min(1.0);
+    sheet
+        .get_page_setup_mut()
+        .set_paper_size(9) // A4
+        .set_orientation(OrientationValues::Portrait)
+        .set_scale(((scale * 100.0) as u32).max(10));
+    sheet
+        .get_page_margins_mut()
+        .set_left(0.5)
+        .set_right(0.5)
+        .set_top(0.75)
+        .set_bottom(0.75)
+        .set_header(0.3)
+        .set_footer(0.3);
+    sheet.get_print_options_mut().set_horizontal_centered(true);
+
+    let last_col = col_to_letter(widths.len() as u32);
+    let area = format!("'{sheet_name}'!$A$1:${last_col}${last_row}");
+    let titles = format!("'{sheet_name}'!${header_row}:${header_row}");
+    let _ = sheet.add_defined_name("_xlnm.Print_Area".to_string(), area);
+    let _ = sheet.add_defined_name("_xlnm.Print_Titles".to_string(), titles);
+    for name in sheet.get_defined_names_mut() {
+        name.set_local_sheet_id(sheet_index);
+    }
+}
+
+/// Writes the first month in the log to an `.xlsx` file and returns its path,
+/// or `None` when there is nothing to export.
+pub fn export_excel(
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    user: Option<&str>,
+    options: &ExcelOptions,
+) -> anyhow::Result<Option<PathBuf>> {
+    let password = options.protect.as_deref().filter(|p| !p.is_empty());
+    if options.encrypt && password.is_none() {
+        anyhow::bail!("--encrypt needs a password: --protect <password>");
+    }
+    let Some(data) = month_sheet(input, user, options.full_month, options.rate)? else {
+        return Ok(None);
+    };
+    let out_path = output.unwrap_or_else(|| data.default_path(user, "xlsx"));
+    let config = Config::load()?;
+    let header_lines = header_block(&config, user);
+    let native_times = options.native_times || config.bool("excel.native_times").unwrap_or(false);
+    let summary_sheet =
+        options.summary_sheet || config.bool("excel.summary_sheet").unwrap_or(false);
+    let MonthSheet {
+        year,
+        month,
+        title: title_text,
+        rows,
+        total_label,
+        total_minutes,
+        days_worked,
+        overtime_minutes,
+        columns,
+    } = data;
+    let date_width = fit_width("日付", rows.iter().map(|r| r.date.as_str()));
+    let time_width = fit_width("勤務時間", rows.iter().map(|r| r.time.as_str()));
+    let content_width = fit_width("作業内容", rows.iter().map(|r| r.content.as_str()))
+        .clamp(CONTENT_WIDTH.0, CONTENT_WIDTH.1);
+    let (headers, widths): (Vec<&str>, Vec<f64>) = if native_times {
+        [
+            ("日付", date_width),
+            ("曜日", WEEKDAY_WIDTH),
+            ("開始", CLOCK_WIDTH),
+            ("終了", CLOCK_WIDTH),
+            ("勤務時間", CLOCK_WIDTH + 2.0),
+            ("作業内容", content_width),
+        ]
+        .into_iter()
+        .unzip()
+    } else {
+        [
+            ("日付", date_width),
+            ("曜日", WEEKDAY_WIDTH),
+            ("勤務時間", time_width),
+            ("作業内容", content_width),
+        ]
+        .into_iter()
+        .unzip()
+    };
+    let mut book: Spreadsheet = new_file();
+
+    let (sheet_name, sheet_index) = if summary_sheet {
+        let summary = book.get_sheet_by_name_mut("Sheet1").unwrap();
+        summary.set_name("Summary");
+        let totals = SheetTotals {
+            month: format!("{year}年{}月", month.trim_start_matches('0')),
+            days_worked,
+            total_minutes,
+            overtime_minutes,
+            limit: daily_limit(&config),
+            rate: options.rate,
+        };
+        write_summary(summary, &totals);
+        book.new_sheet("Detail")
+            .map_err(|e| anyhow::anyhow!("failed to add the Detail sheet: {e}"))?;
+        ("Detail", 1)
+    } else {
+        ("Sheet1", 0)
+    };
+    let sheet: &mut Worksheet = book.get_sheet_by_name_mut(sheet_name).unwrap();
+
+    let style = Style::default();
+
+    {
+        let cell = coord(1, 1);
+        let c = sheet.get_cell_mut(cell.clone());
+        c.set_value(title_text.clone());
+        c.set_style(style.clone());
+    }
+
+    for (i, (label, value)) in header_lines.iter().enumerate() {
+        let excel_row = 2 + i as u32;
+        sheet
+            .get_cell_mut(coord(1, excel_row))
+            .set_value(label.to_string());
+        sheet
+            .get_cell_mut(coord(2, excel_row))
+            .set_value(value.clone());
+    }
+    if let Some(logo) = config.str("excel.header.logo") {
+        add_logo(sheet, logo)?;
+    }
+    // The table starts below the title, the header block and a blank row.
+    let header_row = 3 + header_lines.len() as u32;
+
+    for (i, &h) in headers.iter().enumerate() {
+        let cell = coord((i as u32) + 1, header_row);
+        let c = sheet.get_cell_mut(cell.clone());
+        c.set_value(h.to_string());
+        c.set_style(style.clone());
+    }
+
+    for (i, row) in rows.iter().enumerate() {
+        let excel_row = header_row + 1 + i as u32;
+        let fill = match row.kind {
+            DayKind::Weekday => None,
+            DayKind::Saturday => Some("FFDDEBF7"),
+            DayKind::Holiday => Some("FFFCE4D6"),
+        };
+        for col in 1..=headers.len() as u32 {
+            let c = sheet.get_cell_mut(coord(col, excel_row));
+            if let Some(color) = fill {
+                c.get_style_mut().set_background_color(color);
+            }
+        }
+        sheet
+            .get_cell_mut(coord(1, excel_row))
+            .set_value(row.date.clone());
+        sheet
+            .get_cell_mut(coord(2, excel_row))
+            .set_value(row.weekday.to_string());
+        if native_times {
+            match row.span {
+                Some((start, end)) => {
+                    for (col, minutes) in [(3, start), (4, end)] {
+                        let c = sheet.get_cell_mut(coord(col, excel_row));
+                        c.set_value_number(minutes as f64 / (24.0 * 60.0));
+                        c.get_style_mut()
+                            .get_number_format_mut()
+                            .set_format_code(DURATION_FORMAT);
+                    }
+                    set_duration(sheet.get_cell_mut(coord(5, excel_row)), row.minutes);
+                }
+                // Days without sessions keep their 休 mark, if any.
+                None => {
+                    sheet
+                        .get_cell_mut(coord(3, excel_row))
+                        .set_value(row.time.clone());
+                }
+            }
+        } else {
+            sheet
+                .get_cell_mut(coord(3, excel_row))
+                .set_value(row.time.clone());
+        }
+        sheet
+            .get_cell_mut(coord(headers.len() as u32, excel_row))
+            .set_value(row.content.clone());
+    }
+
+    let data_end_row = header_row + rows.len() as u32;
+    let label_row = data_end_row + 2;
+    let value_row = data_end_row + 3;
+
+    {
+        let cell_label = coord(1, label_row);
+        sheet
+            .get_cell_mut(cell_label.clone())
+            .set_value("勤務時間の合計".to_string());
+    }
+
+    {
+        let c = sheet.get_cell_mut(coord(1, value_row));
+        if native_times {
+            set_duration(c, total_minutes);
+        } else {
+            c.set_value(total_label.clone());
+        }
+    }
+
+    // Computed columns sit next to the total, one per column.
+    for (i, (name, value)) in columns.iter().enumerate() {
+        let col = 2 + i as u32;
+        sheet
+            .get_cell_mut(coord(col, label_row))
+            .set_value(name.clone());
+        sheet
+            .get_cell_mut(coord(col, value_row))
+            .set_value_number((value * 100.0).round() / 100.0);
+    }
+
+    let approval = match options.approval {
+        Some(a) => Some(a),
+        None => match config.str("excel.approval") {
+            None => None,
+            Some(v) => Some(Approval::from_str(v, true).map_err(|_| {
+                anyhow::anyhow!("excel.approval must be \"stamps\" or \"lines\", not {v:?}")
+            })?),
+        },
+    };
+    let last_row = match approval {
+        Some(a) => add_approval(sheet, a, value_row + 2, headers.len() as u32),
+        None => value_row,
+    };
+    let chart = options.chart || config.bool("excel.chart").unwrap_or(false);
+    let last_row = if chart {
+        add_daily_chart(
+            sheet,
+            sheet_name,
+            &rows,
+            header_row,
+            headers.len() as u32,
+            last_row,
+        )
+    } else {
+        last_row
+    };
+
+    setup_print(
+        sheet,
+        sheet_name,
+        sheet_index,
+        header_row,
+        last_row,
+        &widths,
+    );
+
+    if let Some(p) = &options.protect {
+        let protection = sheet.get_sheet_protection_mut();
+        protection.set_sheet(true);
+        if !p.is_empty() {
+            protection.set_password(p);
+        }
+    }
+
+    let path_str = out_path.as_os_str().to_string_lossy();
+    match password {
+        Some(p) if options.encrypt => write_with_password(&book, path_str.as_ref(), p)?,
+        _ => write(&book, path_str.as_ref())?,
+    }
+
+    println!("Generated Excel file: {}", out_path.display());
+    Ok(Some(out_path))
+}
@@ -0,0 +1,96 @@
+//! Expenses recorded alongside working time (`kintai expense 1500 "taxi"`),
+//! stored as `type=expense` events with `amount=` and `date=`.
+
+use chrono::NaiveDate;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use crate::{
+    close,
+    correct::apply_corrections,
+    display::Formats,
+    event::{LogEvent, record},
+    state,
+};
+
+pub struct Expense {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub amount: f64,
+    pub description: Option<String>,
+}
+
+/// The expenses in `events`, after corrections, ordered by date.
+pub fn expenses(events: &[LogEvent]) -> Vec<Expense> {
+    let mut out: Vec<Expense> = apply_corrections(events.to_vec())
+        .into_iter()
+        .filter(|e| e.ty == "expense")
+        .filter_map(|e| {
+            Some(Expense {
+                date: e
+                    .field("date")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| e.ts.get(..10).unwrap_or_default().to_string()),
+                amount: e.field("amount")?.parse().ok()?,
+                description: e.content,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.date.cmp(&b.date));
+    out
+}
+
+pub fn record_expense(
+    log: Option<&Path>,
+    user: Option<&str>,
+    amount: &str,
+    description: Option<String>,
+    date: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let value: f64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("amount must be a number, got {amount:?}"))?;
+    if !value.is_finite() {
+        anyhow::bail!("amount must be a number, got {amount:?}");
+    }
+    let today = state::now().format("%Y-%m-%d").to_string();
+    let date = match date {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {d:?}"))?
+            .format("%Y-%m-%d")
+            .to_string(),
+        None => today.clone(),
+    };
+    let mut extra = vec![("amount", amount), ("date", date.as_str())];
+    // The expense belongs to its own day's month, which may already be
+    // closed; `record` checks the current month.
+    if let Some(path) = log
+        && date[..7] != today[..7]
+    {
+        extra.extend(close::guard(path, user, &date, force)?);
+    }
+    record(log, user, "expense", &extra, description, force)
+}
+
+/// Expenses itemized by date, with a subtotal per month.
+pub fn expenses_table(expenses: &[Expense], formats: &Formats) -> String {
+    let mut out = String::new();
+    writeln!(out, "| date | amount | description |").unwrap();
+    writeln!(out, "|------|--------|-------------|").unwrap();
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for e in expenses {
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            e.date,
+            e.amount,
+            e.description.as_deref().unwrap_or_default()
+        )
+        .unwrap();
+        *totals.entry(&e.date[..7]).or_default() += e.amount;
+    }
+    for (month, total) in totals {
+        writeln!(out, "| {} total | {total} | |", formats.month(month)).unwrap();
+    }
+    out
+}
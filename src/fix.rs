@@ -0,0 +1,360 @@
+//! `kintai fix`: pick a recent session from a filterable list and adjust its
+//! start, end, breaks and description with prompts. The changes are recorded
+//! as `amend`/`void` events, as `kintai amend` would.
+
+use chrono::{DateTime, FixedOffset};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+use crate::{
+    close,
+    correct::{self, apply_corrections},
+    event::{LogEvent, filter_user, read_log, record_event_at, user_field},
+    report::plural,
+    session::effective_type,
+    state,
+    time::parse_when,
+};
+
+/// The events of one session as they stand after corrections.
+pub struct RawSession {
+    pub start: LogEvent,
+    /// Each break's start event and, once it ended, its end event.
+    pub breaks: Vec<(LogEvent, Option<LogEvent>)>,
+    pub finish: Option<LogEvent>,
+}
+
+pub fn when(e: &LogEvent) -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339(&e.ts).unwrap()
+}
+
+impl RawSession {
+    /// Worked minutes: start to finish (or now, while running) less breaks.
+    pub fn minutes(&self) -> i64 {
+        let end = self.finish.as_ref().map_or_else(state::now, when);
+        let breaks: i64 = self
+            .breaks
+            .iter()
+            .map(|(b, e)| (e.as_ref().map_or(end, when) - when(b)).num_minutes())
+            .sum();
+        (end - when(&self.start)).num_minutes() - breaks
+    }
+
+    /// One line for the list, e.g. `2024/05/01 09:00~18:00 (alice) Docs`.
+    pub fn line(&self) -> String {
+        let start = when(&self.start);
+        let end = match &self.finish {
+            Some(f) => when(f).format("%H:%M").to_string(),
+            None => "running".to_string(),
+        };
+        let mut line = format!("{}~{end}", start.format("%Y/%m/%d %H:%M"));
+        if let Some(u) = self.start.user() {
+            line.push_str(&format!(" ({u})"));
+        }
+        if let Some(p) = self.start.field("project") {
+            line.push_str(&format!(" [{p}]"));
+        }
+        if let Some(c) = self.finish.as_ref().and_then(|f| f.content.as_deref()) {
+            line.push(' ');
+            line.push_str(c);
+        }
+        line
+    }
+}
+
+/// Groups the corrected events of each user into sessions, oldest first.
+/// Unlike reports, sessions merged by `sessions.merge_gap` stay apart, since
+/// each has its own events to correct.
+pub fn raw_sessions(events: Vec<LogEvent>) -> Vec<RawSession> {
+    let mut by_user: BTreeMap<Option<String>, Vec<LogEvent>> = BTreeMap::new();
+    for e in apply_corrections(events) {
+        if DateTime::parse_from_rfc3339(&e.ts).is_ok() {
+            by_user
+                .entry(e.user().map(str::to_string))
+                .or_default()
+                .push(e);
+        }
+    }
+    let mut sessions = Vec::new();
+    for (_, mut events) in by_user {
+        events.sort_by_key(|e| e.ts.clone());
+        let mut active: Option<RawSession> = None;
+        for e in events {
+            match effective_type(&e).0 {
+                "start" if active.is_none() => {
+                    active = Some(RawSession {
+                        start: e,
+                        breaks: Vec::new(),
+                        finish: None,
+                    });
+                }
+                "break_start" => {
+                    if let Some(a) = active.as_mut()
+                        && a.breaks.last().is_none_or(|(_, end)| end.is_some())
+                    {
+                        a.breaks.push((e, None));
+                    }
+                }
+                "break_end" => {
+                    if let Some((_, end @ None)) = active.as_mut().and_then(|a| a.breaks.last_mut())
+                    {
+                        *end = Some(e);
+                    }
+                }
+                "finish" => {
+                    if let Some(mut a) = active.take() {
+                        a.finish = Some(e);
+                        sessions.push(a);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sessions.extend(active);
+    }
+    sessions.sort_by_key(|s| when(&s.start));
+    sessions
+}
+
+/// Whether every character of `query` appears in `text` in order, ignoring
+/// case, so `0501doc` finds `2024/05/01 ... Docs`.
+fn fuzzy_match(text: &str, query: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut chars = text.chars();
+    query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|q| chars.any(|c| c == q))
+}
+
+/// Prints `prompt` and reads a line; `None` at the end of input.
+pub fn ask(prompt: &str) -> anyhow::Result<Option<String>> {
+    let mut err = io::stderr();
+    write!(err, "{prompt}")?;
+    err.flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+/// Lets the user narrow the list by typing and pick a session by number.
+fn choose(sessions: &[RawSession]) -> anyhow::Result<Option<&RawSession>> {
+    let mut query = String::new();
+    loop {
+        let shown: Vec<&RawSession> = sessions
+            .iter()
+            .filter(|s| fuzzy_match(&s.line(), &query))
+            .collect();
+        if shown.is_empty() {
+            eprintln!("No session matches {query:?}.");
+        }
+        for (i, s) in shown.iter().enumerate() {
+            eprintln!("{:>3}. {}", i + 1, s.line());
+        }
+        let Some(answer) = ask("Fix which session? [number, text to filter, empty to quit] ")?
+        else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        if let Ok(n) = answer.parse::<usize>()
+            && let Some(s) = n.checked_sub(1).and_then(|i| shown.get(i))
+        {
+            return Ok(Some(s));
+        }
+        query = answer;
+    }
+}
+
+pub enum Change<'a> {
+    Amend(&'a LogEvent, Option<DateTime<FixedOffset>>, Option<String>),
+    Void(&'a LogEvent),
+    /// A break from and to the given times in the session begun by the event.
+    Break(&'a LogEvent, DateTime<FixedOffset>, DateTime<FixedOffset>),
+}
+
+/// Asks for a new time for `event`; an empty answer keeps it.
+fn ask_time(label: &str, event: &LogEvent) -> anyhow::Result<Option<DateTime<FixedOffset>>> {
+    let old = when(event);
+    loop {
+        let Some(answer) = ask(&format!("{label} [{}]: ", old.format("%H:%M")))? else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match parse_when(&answer, old) {
+            Ok(t) if t == old => return Ok(None),
+            Ok(t) => return Ok(Some(t)),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+/// Asks for a new description of a finished `session`, which is kept on
+/// its `finish` event.
+pub fn edit_content(session: &RawSession) -> anyhow::Result<Option<Change<'_>>> {
+    let Some(finish) = &session.finish else {
+        return Ok(None);
+    };
+    let old = finish.content.as_deref().unwrap_or_default();
+    Ok(ask(&format!("Content [{old}]: "))?
+        .filter(|c| !c.is_empty() && c != old)
+        .map(|c| Change::Amend(finish, None, Some(c))))
+}
+
+/// Asks for the start and end of a break to add to `session`, which must
+/// fall within it.
+pub fn add_break(session: &RawSession) -> anyhow::Result<Option<Change<'_>>> {
+    let Some(begin) = ask_time("Break start", &session.start)? else {
+        return Ok(None);
+    };
+    let Some(end) = ask("Break end: ")?.filter(|a| !a.is_empty()) else {
+        return Ok(None);
+    };
+    let end = parse_when(&end, begin)?;
+    let last = session.finish.as_ref().map_or_else(state::now, when);
+    if end <= begin || begin < when(&session.start) || end > last {
+        anyhow::bail!(
+            "a break from {} to {} does not fit in the session; nothing changed",
+            begin.format("%H:%M"),
+            end.format("%H:%M")
+        );
+    }
+    Ok(Some(Change::Break(&session.start, begin, end)))
+}
+
+/// Prompts for each part of `session` and collects what the user changed.
+pub fn edit(session: &RawSession) -> anyhow::Result<Vec<Change<'_>>> {
+    let mut changes = Vec::new();
+    let mut times = Vec::new();
+    let start = ask_time("Start", &session.start)?;
+    changes.extend(start.map(|t| Change::Amend(&session.start, Some(t), None)));
+    times.push(("start", start.unwrap_or_else(|| when(&session.start))));
+    for (i, (begin, end)) in session.breaks.iter().enumerate() {
+        let n = i + 1;
+        let range = match end {
+            Some(e) => format!(
+                "{}~{}",
+                when(begin).format("%H:%M"),
+                when(e).format("%H:%M")
+            ),
+            None => format!("{}~", when(begin).format("%H:%M")),
+        };
+        let remove = ask(&format!("Break {n} ({range}): keep or remove? [K/r] "))?
+            .is_some_and(|a| a.eq_ignore_ascii_case("r"));
+        if remove {
+            changes.push(Change::Void(begin));
+            changes.extend(end.as_ref().map(Change::Void));
+            continue;
+        }
+        let t = ask_time(&format!("Break {n} start"), begin)?;
+        changes.extend(t.map(|t| Change::Amend(begin, Some(t), None)));
+        times.push(("a break", t.unwrap_or_else(|| when(begin))));
+        if let Some(end) = end {
+            let t = ask_time(&format!("Break {n} end"), end)?;
+            changes.extend(t.map(|t| Change::Amend(end, Some(t), None)));
+            times.push(("a break", t.unwrap_or_else(|| when(end))));
+        }
+    }
+    if let Some(finish) = &session.finish {
+        let t = ask_time("End", finish)?;
+        changes.extend(t.map(|t| Change::Amend(finish, Some(t), None)));
+        times.push(("end", t.unwrap_or_else(|| when(finish))));
+        if let Some(Change::Amend(_, _, Some(content))) = edit_content(session)? {
+            // Amend the end time and content together when both changed.
+            match changes.last_mut() {
+                Some(Change::Amend(e, _, c)) if e.id() == finish.id() => *c = Some(content),
+                _ => changes.push(Change::Amend(finish, None, Some(content))),
+            }
+        }
+    }
+    for pair in times.windows(2) {
+        if pair[1].1 < pair[0].1 {
+            anyhow::bail!(
+                "{} at {} would come before {} at {}; nothing changed",
+                pair[1].0,
+                pair[1].1.format("%H:%M"),
+                pair[0].0,
+                pair[0].1.format("%H:%M")
+            );
+        }
+    }
+    Ok(changes)
+}
+
+/// `kintai fix`: choose one of the last `limit` sessions and correct it.
+pub fn fix(log: &Path, user: Option<&str>, limit: usize, force: bool) -> anyhow::Result<()> {
+    if !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "`kintai fix` is interactive; use `kintai amend` and `kintai void` in scripts"
+        );
+    }
+    let mut sessions = raw_sessions(filter_user(read_log(log)?, user));
+    if sessions.is_empty() {
+        println!("No sessions to fix.");
+        return Ok(());
+    }
+    sessions.reverse();
+    sessions.truncate(limit);
+    let Some(session) = choose(&sessions)? else {
+        return Ok(());
+    };
+    let changes = edit(session)?;
+    if changes.is_empty() {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+    apply(log, user, changes, force)?;
+    Ok(())
+}
+
+/// Asks to confirm `changes` and records them. Returns whether anything was
+/// recorded.
+pub fn apply(
+    log: &Path,
+    user: Option<&str>,
+    changes: Vec<Change>,
+    force: bool,
+) -> anyhow::Result<bool> {
+    let answer = ask(&format!(
+        "Record {} correction{}? [y/N] ",
+        changes.len(),
+        plural(changes.len())
+    ))?;
+    if !answer.is_some_and(|a| a.eq_ignore_ascii_case("y")) {
+        println!("Nothing changed.");
+        return Ok(false);
+    }
+    for change in changes {
+        match change {
+            Change::Amend(e, t, c) => {
+                let t = t.map(|t| t.to_rfc3339());
+                correct::amend(log, user, e.id(), t.as_deref(), c, force)?
+            }
+            Change::Void(e) => correct::void(log, user, e.id(), force)?,
+            Change::Break(start, begin, end) => {
+                for (ty, t) in [("break_start", begin), ("break_end", end)] {
+                    let ts = t.to_rfc3339();
+                    let mut fields = user_field(start.user().or(user));
+                    fields.push(("manual", "1"));
+                    fields.extend(close::guard(log, user, &ts, force)?);
+                    record_event_at(Some(log), &ts, ty, &fields, None)?;
+                }
+                println!(
+                    "Added a break from {} to {}.",
+                    begin.format("%H:%M"),
+                    end.format("%H:%M")
+                );
+            }
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,91 @@
+//! Flextime core-hours check: days on which the sessions did not cover the
+//! configured core window (`flex.core = "11:00-15:00"`). Breaks inside a
+//! session count as present.
+
+use chrono::NaiveTime;
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use crate::{
+    config::Config,
+    event::{filter_user, read_events},
+    session::build_sessions,
+};
+
+/// A `HH:MM-HH:MM` window within one day.
+pub fn parse_window(s: &str) -> anyhow::Result<(NaiveTime, NaiveTime)> {
+    let parsed = s.split_once('-').and_then(|(a, b)| {
+        let a = NaiveTime::parse_from_str(a.trim(), "%H:%M").ok()?;
+        let b = NaiveTime::parse_from_str(b.trim(), "%H:%M").ok()?;
+        (a < b).then_some((a, b))
+    });
+    parsed.ok_or_else(|| anyhow::anyhow!("expected a time window as HH:MM-HH:MM, got {s:?}"))
+}
+
+/// Parts of `core` not covered by `spans`.
+fn gaps(
+    core: (NaiveTime, NaiveTime),
+    mut spans: Vec<(NaiveTime, NaiveTime)>,
+) -> Vec<(NaiveTime, NaiveTime)> {
+    spans.sort();
+    let mut gaps = Vec::new();
+    let mut cursor = core.0;
+    for (s, e) in spans {
+        if s > cursor && cursor < core.1 {
+            gaps.push((cursor, s.min(core.1)));
+        }
+        cursor = cursor.max(e);
+    }
+    if cursor < core.1 {
+        gaps.push((cursor, core.1));
+    }
+    gaps
+}
+
+pub fn check(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: Option<&str>,
+    core: Option<&str>,
+) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let core = core.or(config.str("flex.core")).ok_or_else(|| {
+        anyhow::anyhow!("set flex.core = \"HH:MM-HH:MM\" in the config or pass --core")
+    })?;
+    let window = parse_window(core)?;
+    let month = month.map(|m| m.replace('-', "/"));
+
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let mut days: BTreeMap<&str, Vec<(NaiveTime, NaiveTime)>> = BTreeMap::new();
+    for s in &sessions {
+        if month
+            .as_ref()
+            .is_some_and(|m| !s.date.starts_with(m.as_str()))
+        {
+            continue;
+        }
+        days.entry(&s.date).or_default().extend(s.span());
+    }
+
+    let mut out = String::new();
+    writeln!(out, "| date | missed core time |").unwrap();
+    writeln!(out, "|------|------------------|").unwrap();
+    let mut flagged = 0;
+    for (date, spans) in &days {
+        let missed = gaps(window, spans.clone());
+        if missed.is_empty() {
+            continue;
+        }
+        flagged += 1;
+        let parts: Vec<String> = missed
+            .iter()
+            .map(|(a, b)| format!("{}~{}", a.format("%H:%M"), b.format("%H:%M")))
+            .collect();
+        writeln!(out, "| {date} | {} |", parts.join(",")).unwrap();
+    }
+    println!("{out}");
+    println!(
+        "{flagged} of {} worked days did not cover the core hours {core}.",
+        days.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! Compensatory days off (振替休日): work on a holiday is recorded with
+//! `type=holiday_work date=...` and swapped for a rest day with
+//! `type=holiday_swap date=<worked day> rest=<rest day>`. Holiday work that
+//! has not been swapped earns the holiday premium (`premium.holiday`,
+//! default 1.35×); swapped days are paid as ordinary workdays.
+
+use chrono::NaiveDate;
+use std::{collections::BTreeMap, fmt::Write, path::Path, path::PathBuf};
+
+use crate::{
+    config::Config,
+    correct::apply_corrections,
+    display::Formats,
+    event::{LogEvent, filter_user, read_events, record},
+    overtime::daily_hours,
+    report::format_hm,
+    session::{Session, build_sessions},
+    state,
+};
+
+pub const DEFAULT_HOLIDAY_PREMIUM: f64 = 1.35;
+
+/// Worked holidays (`YYYY-MM-DD`) and the rest day each was swapped for.
+pub fn holidays_worked(events: &[LogEvent]) -> BTreeMap<String, Option<String>> {
+    let mut days = BTreeMap::new();
+    let mut events = apply_corrections(events.to_vec());
+    events.sort_by(|a, b| a.ts.cmp(&b.ts));
+    for e in events {
+        let Some(date) = e.field("date") else {
+            continue;
+        };
+        match e.ty.as_str() {
+            "holiday_work" => {
+                let swap = e.field("rest").map(str::to_string);
+                let entry = days.entry(date.to_string()).or_insert(None);
+                if swap.is_some() {
+                    *entry = swap;
+                }
+            }
+            "holiday_swap" => {
+                days.insert(date.to_string(), e.field("rest").map(str::to_string));
+            }
+            _ => {}
+        }
+    }
+    days
+}
+
+fn parse_date(d: &str) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(d, "%Y-%m-%d")
+        .map(|_| ())
+        .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {d:?}"))
+}
+
+/// Records that `date` was a holiday worked, optionally already swapped for
+/// the rest day `rest`.
+pub fn work(log: &Path, user: Option<&str>, date: &str, rest: Option<&str>) -> anyhow::Result<()> {
+    parse_date(date)?;
+    let mut extra = vec![("date", date)];
+    if let Some(r) = rest {
+        parse_date(r)?;
+        extra.push(("rest", r));
+    }
+    record(Some(log), user, "holiday_work", &extra, None, false)?;
+    println!("Recorded holiday work on {date}.");
+    Ok(())
+}
+
+/// Swaps the worked holiday `date` for the rest day `rest`.
+pub fn swap(log: &Path, user: Option<&str>, date: &str, rest: &str) -> anyhow::Result<()> {
+    parse_date(date)?;
+    parse_date(rest)?;
+    let events = filter_user(crate::event::read_log(log)?, user);
+    if !holidays_worked(&events).contains_key(date) {
+        anyhow::bail!(
+            "{date} is not recorded as holiday work; run `kintai furikae work {date}` first"
+        );
+    }
+    record(
+        Some(log),
+        user,
+        "holiday_swap",
+        &[("date", date), ("rest", rest)],
+        None,
+        false,
+    )?;
+    println!("Swapped holiday work on {date} for a day off on {rest}.");
+    Ok(())
+}
+
+/// Lists worked holidays with their rest days and the days still owed.
+pub fn balance(input: Option<PathBuf>, user: Option<&str>) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let days = holidays_worked(&events);
+    let hours = daily_hours(&build_sessions(events));
+    let today = state::now().format("%Y-%m-%d").to_string();
+
+    let mut out = String::new();
+    writeln!(out, "| holiday worked | hours | day off | status |").unwrap();
+    writeln!(out, "|----------------|-------|---------|--------|").unwrap();
+    let mut owed = 0;
+    for (date, rest) in &days {
+        let status = match rest {
+            Some(r) if *r <= today => "taken",
+            Some(_) => "planned",
+            None => {
+                owed += 1;
+                "owed"
+            }
+        };
+        writeln!(
+            out,
+            "| {date} | {} | {} | {status} |",
+            format_hm(hours.get(date).copied().unwrap_or(0.0)),
+            rest.as_deref().unwrap_or("-")
+        )
+        .unwrap();
+    }
+    println!("{out}");
+    println!("{owed} compensatory days off not yet scheduled.");
+    Ok(())
+}
+
+/// Holiday premium per month: hours on worked holidays that were not
+/// swapped, paid at `premium` times the rate (the extra part is shown).
+pub fn premium_table(
+    sessions: &[Session],
+    days: &BTreeMap<String, Option<String>>,
+    rate: Option<f64>,
+    config: &Config,
+    formats: &Formats,
+) -> String {
+    let premium = config
+        .get("premium.holiday")
+        .and_then(|v| v.as_num())
+        .unwrap_or(DEFAULT_HOLIDAY_PREMIUM);
+    let hours = daily_hours(sessions);
+    let mut months: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+    for (date, rest) in days {
+        let h = hours.get(date).copied().unwrap_or(0.0);
+        let m = months.entry(&date[..7]).or_default();
+        if rest.is_some() {
+            m.0 += h;
+        } else {
+            m.1 += h;
+        }
+    }
+    let mut out = String::new();
+    writeln!(
+        out,
+        "| month | swapped holiday work | holiday work | premium |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|-------|----------------------|--------------|---------|"
+    )
+    .unwrap();
+    for (month, (swapped, unswapped)) in months {
+        let extra = (unswapped * rate.unwrap_or(0.0) * (premium - 1.0)).round() as u64;
+        writeln!(
+            out,
+            "| {} | {} | {} | {extra} |",
+            formats.month(month),
+            format_hm(swapped),
+            format_hm(unswapped)
+        )
+        .unwrap();
+    }
+    out
+}
@@ -0,0 +1,114 @@
+//! `kintai finish --from-git`: suggests the finish description from the
+//! commits authored in the current session, read with the `git` command.
+
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::state::{self, Phase};
+
+fn git(repo: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("failed to run git: {e}"))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git {} in {}: {}",
+            args.join(" "),
+            repo.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Subjects of the commits by the repository's `user.email` since `since`
+/// (RFC 3339), oldest first, on any branch.
+fn commit_subjects(repo: &Path, since: &str) -> anyhow::Result<Vec<String>> {
+    let email = git(repo, &["config", "user.email"])?;
+    let log = git(
+        repo,
+        &[
+            "log",
+            "--all",
+            "--reverse",
+            "--no-merges",
+            &format!("--since={since}"),
+            &format!("--author={}", email.trim()),
+            "--format=%s",
+        ],
+    )?;
+    Ok(log.lines().map(str::to_string).collect())
+}
+
+/// Lets the user pick commits by number; an empty answer takes them all.
+fn choose(subjects: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let mut err = io::stderr();
+    for (i, s) in subjects.iter().enumerate() {
+        writeln!(err, "{:>3}. {s}", i + 1)?;
+    }
+    write!(err, "Use which commits? [all, or e.g. 1,3; n for none] ")?;
+    err.flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(subjects);
+    }
+    if answer.eq_ignore_ascii_case("n") {
+        return Ok(Vec::new());
+    }
+    answer
+        .split(',')
+        .map(|n| {
+            n.trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| subjects.get(n.checked_sub(1)?).cloned())
+                .ok_or_else(|| anyhow::anyhow!("no commit numbered {:?}", n.trim()))
+        })
+        .collect()
+}
+
+/// The description for `finish`: the subjects of the commits made in
+/// `repos` (default: the current directory) since the running session
+/// started, joined with `; `. On a terminal the commits can be picked first.
+pub fn finish_content(
+    log: Option<&Path>,
+    user: Option<&str>,
+    repos: &[PathBuf],
+) -> anyhow::Result<Option<String>> {
+    let log = log.ok_or_else(|| anyhow::anyhow!("--from-git needs a log (--log or KINTAI_LOG)"))?;
+    let st = state::load(log, user)?;
+    let (Phase::Working | Phase::OnBreak, Some(start)) = (st.phase, st.session_start) else {
+        anyhow::bail!("no running session to take commits for");
+    };
+    let since = start.to_rfc3339();
+    let default = [PathBuf::from(".")];
+    let repos = if repos.is_empty() {
+        &default[..]
+    } else {
+        repos
+    };
+    let mut subjects = Vec::new();
+    for repo in repos {
+        for s in commit_subjects(repo, &since)? {
+            if !subjects.contains(&s) {
+                subjects.push(s);
+            }
+        }
+    }
+    if subjects.is_empty() {
+        eprintln!("No commits since {}.", start.format("%H:%M"));
+        return Ok(None);
+    }
+    if io::stdin().is_terminal() && io::stderr().is_terminal() {
+        subjects = choose(subjects)?;
+    }
+    Ok((!subjects.is_empty()).then(|| subjects.join("; ")))
+}
@@ -0,0 +1,279 @@
+//! `kintai import github`: reconstructs untracked days from your GitHub
+//! activity (pushes, pull requests, reviews, comments), read from the
+//! events API with `curl`. Only the last 90 days (and at most 300 events)
+//! are available from GitHub.
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    close,
+    config::Config,
+    event::{filter_user, read_log, record_event_at, user_field},
+    report::plural,
+    session::build_sessions,
+};
+
+const DEFAULT_API: &str = "https://api.github.com";
+
+/// Minutes of work assumed before the first activity of a day.
+const DEFAULT_LEAD_MINUTES: f64 = 30.0;
+
+/// A token: `$KINTAI_GITHUB_TOKEN`, `github.token` in the config, or
+/// whatever `gh auth token` prints.
+fn access_token(config: &Config) -> anyhow::Result<String> {
+    if let Ok(t) = std::env::var("KINTAI_GITHUB_TOKEN")
+        && !t.is_empty()
+    {
+        return Ok(t);
+    }
+    if let Some(t) = config.str("github.token") {
+        return Ok(t.to_string());
+    }
+    let out = Command::new("gh")
+        .args(["auth", "token"])
+        .output()
+        .map_err(|e| {
+            anyhow::anyhow!("no GitHub token: set KINTAI_GITHUB_TOKEN or install gh ({e})")
+        })?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "gh auth token failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(out.stdout)?.trim().to_string())
+}
+
+/// Sends a GET request with `curl`; the token is passed on stdin so it does
+/// not show up in the process list.
+fn get(url: &str, token: &str) -> anyhow::Result<Value> {
+    let mut child = Command::new("curl")
+        .args(["-sS", "--fail-with-body", "-H", "@-"])
+        .args(["-H", "Accept: application/vnd.github+json"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("cannot run curl: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "Authorization: Bearer {token}")?;
+    }
+    let out = child.wait_with_output()?;
+    let reply: Value = serde_json::from_slice(&out.stdout).unwrap_or(Value::Null);
+    if !out.status.success() {
+        let msg = reply["message"].as_str().unwrap_or("request failed");
+        anyhow::bail!("GitHub API: {msg}");
+    }
+    Ok(reply)
+}
+
+/// One line describing an event, or `None` for kinds that say nothing about
+/// work done (stars, forks, ...).
+fn describe(e: &Value) -> Option<String> {
+    let repo = e["repo"]["name"].as_str().unwrap_or("?");
+    let p = &e["payload"];
+    let number = |v: &Value| v["number"].as_u64().unwrap_or_default();
+    let what = match e["type"].as_str()? {
+        "PushEvent" => {
+            let subjects: Vec<&str> = p["commits"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c["message"].as_str()?.lines().next())
+                .collect();
+            if subjects.is_empty() {
+                let branch = p["ref"].as_str().unwrap_or_default();
+                format!("pushed to {}", branch.trim_start_matches("refs/heads/"))
+            } else {
+                subjects.join("; ")
+            }
+        }
+        "PullRequestEvent" => format!(
+            "{} PR #{} {}",
+            p["action"].as_str().unwrap_or("updated"),
+            number(&p["pull_request"]),
+            p["pull_request"]["title"].as_str().unwrap_or_default()
+        ),
+        "PullRequestReviewEvent" | "PullRequestReviewCommentEvent" => format!(
+            "reviewed PR #{} {}",
+            number(&p["pull_request"]),
+            p["pull_request"]["title"].as_str().unwrap_or_default()
+        ),
+        "IssuesEvent" => format!(
+            "{} issue #{} {}",
+            p["action"].as_str().unwrap_or("updated"),
+            number(&p["issue"]),
+            p["issue"]["title"].as_str().unwrap_or_default()
+        ),
+        "IssueCommentEvent" => format!(
+            "commented on #{} {}",
+            number(&p["issue"]),
+            p["issue"]["title"].as_str().unwrap_or_default()
+        ),
+        _ => return None,
+    };
+    Some(format!("{repo}: {}", what.trim()))
+}
+
+/// Activity of one local day: its first and last timestamps and what was done.
+#[derive(Default)]
+struct Day {
+    first: Option<DateTime<FixedOffset>>,
+    last: Option<DateTime<FixedOffset>>,
+    items: Vec<String>,
+}
+
+fn parse_day(day: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("invalid date {day:?} (expected YYYY-MM-DD)"))
+}
+
+/// Fetches `login`'s events and groups those between `from` and `to`
+/// (inclusive, local time) by day.
+fn activity(
+    api: &str,
+    login: &str,
+    token: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> anyhow::Result<BTreeMap<NaiveDate, Day>> {
+    let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+    for page in 1..=3 {
+        let url = format!("{api}/users/{login}/events?per_page=100&page={page}");
+        let events = get(&url, token)?;
+        let events = events.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut older = false;
+        for e in events {
+            let Some(at) = e["created_at"]
+                .as_str()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            else {
+                continue;
+            };
+            let at = at.with_timezone(&Local).fixed_offset();
+            let date = at.date_naive();
+            older |= date < from;
+            if date < from || date > to {
+                continue;
+            }
+            let Some(item) = describe(e) else {
+                continue;
+            };
+            let day = days.entry(date).or_default();
+            day.first = Some(day.first.map_or(at, |f| f.min(at)));
+            day.last = Some(day.last.map_or(at, |l| l.max(at)));
+            if !day.items.contains(&item) {
+                day.items.push(item);
+            }
+        }
+        if events.len() < 100 || older {
+            break;
+        }
+    }
+    // The API lists newest first.
+    for day in days.values_mut() {
+        day.items.reverse();
+    }
+    Ok(days)
+}
+
+/// Proposes a session for every day between `from` and `to` with GitHub
+/// activity but nothing in the log: from `github.lead_minutes` (default 30)
+/// before the first activity to the last one, described by what was done.
+/// Days already tracked are listed with their activity for reference. With
+/// `apply` the proposed sessions are recorded, tagged `source=github`.
+pub fn import(
+    log: &Path,
+    user: Option<&str>,
+    from: &str,
+    to: &str,
+    apply: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    let (from, to) = (parse_day(from)?, parse_day(to)?);
+    let config = Config::load()?;
+    let api = config
+        .str("github.api")
+        .unwrap_or(DEFAULT_API)
+        .trim_end_matches('/');
+    let token = access_token(&config)?;
+    let login = match config.str("github.user") {
+        Some(login) => login.to_string(),
+        None => get(&format!("{api}/user"), &token)?["login"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("GitHub API: no login in /user"))?
+            .to_string(),
+    };
+    let lead = config
+        .get("github.lead_minutes")
+        .and_then(|v| v.as_num())
+        .unwrap_or(DEFAULT_LEAD_MINUTES);
+    let days = activity(api, &login, &token, from, to)?;
+
+    let tracked: BTreeSet<String> = build_sessions(filter_user(read_log(log)?, user))
+        .into_iter()
+        .map(|s| s.date)
+        .collect();
+    let mut proposals = Vec::new();
+    let mut out = String::new();
+    writeln!(out, "| date | session | activity |").unwrap();
+    writeln!(out, "|------|---------|----------|").unwrap();
+    for (date, day) in &days {
+        let (Some(first), Some(last)) = (day.first, day.last) else {
+            continue;
+        };
+        let activity = day.items.join("; ");
+        if tracked.contains(&date.format("%Y/%m/%d").to_string()) {
+            writeln!(out, "| {date} | (already tracked) | {activity} |").unwrap();
+            continue;
+        }
+        let start = first - Duration::minutes(lead as i64);
+        writeln!(
+            out,
+            "| {date} | {}~{} | {activity} |",
+            start.format("%H:%M"),
+            last.format("%H:%M")
+        )
+        .unwrap();
+        proposals.push((start, last, activity));
+    }
+    println!("{out}");
+    if proposals.is_empty() {
+        println!("No untracked days with GitHub activity.");
+        return Ok(());
+    }
+    if !apply {
+        println!(
+            "Run again with --apply to record {} session{}.",
+            proposals.len(),
+            plural(proposals.len())
+        );
+        return Ok(());
+    }
+    for (start, finish, activity) in &proposals {
+        for (ty, ts, content) in [
+            ("start", start, None),
+            ("finish", finish, Some(activity.clone())),
+        ] {
+            let ts = ts.to_rfc3339();
+            let mut fields = user_field(user);
+            fields.push(("source", "github"));
+            fields.extend(close::guard(log, user, &ts, force)?);
+            record_event_at(Some(log), &ts, ty, &fields, content)?;
+        }
+    }
+    println!(
+        "Recorded {} session{}.",
+        proposals.len(),
+        plural(proposals.len())
+    );
+    Ok(())
+}
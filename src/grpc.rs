@@ -0,0 +1,157 @@
+//! gRPC server for the `kintai.v1.Kintai` service described in
+//! `proto/kintai/v1/kintai.proto`, from which the messages and the service
+//! trait are generated at build time. The server speaks native gRPC over
+//! HTTP/2 and also accepts gRPC-Web over HTTP/1.1, so browser dashboards can
+//! call it without a proxy.
+
+use std::{
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+};
+use tonic::{
+    Request, Response,
+    codegen::http::HeaderName,
+    transport::{Server, server::TcpIncoming},
+};
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::{
+    event::{self, LogEvent, filter_user},
+    report::monthly_hours,
+    session::build_sessions,
+    state,
+};
+
+mod pb {
+    tonic::include_proto!("kintai.v1");
+}
+
+use pb::kintai_server::{Kintai, KintaiServer};
+
+type Reply<T> = Result<Response<T>, tonic::Status>;
+
+struct Service {
+    log: PathBuf,
+    user: Option<String>,
+}
+
+fn internal(e: anyhow::Error) -> tonic::Status {
+    tonic::Status::internal(e.to_string())
+}
+
+/// Whether a timestamp or date falls in `month` ("YYYY-MM", empty for all),
+/// whatever separator the date uses.
+fn matches(month: &str, date: &str) -> bool {
+    month.is_empty() || date.len() >= 7 && date[..7].replace('/', "-") == month
+}
+
+impl Service {
+    fn read_log(&self) -> Result<Vec<LogEvent>, tonic::Status> {
+        Ok(filter_user(
+            event::read_log(&self.log).map_err(internal)?,
+            self.user.as_deref(),
+        ))
+    }
+}
+
+#[tonic::async_trait]
+impl Kintai for Service {
+    async fn get_status(&self, _: Request<pb::GetStatusRequest>) -> Reply<pb::Status> {
+        let st = state::load(&self.log, self.user.as_deref()).map_err(internal)?;
+        let rfc = |d: Option<chrono::DateTime<chrono::FixedOffset>>| {
+            d.map(|d| d.to_rfc3339()).unwrap_or_default()
+        };
+        Ok(Response::new(pb::Status {
+            phase: st.phase.as_str().to_string(),
+            session_start: rfc(st.session_start),
+            break_since: rfc(st.break_since),
+            worked_seconds: st.worked_secs(state::now()),
+        }))
+    }
+
+    async fn list_events(
+        &self,
+        req: Request<pb::ListEventsRequest>,
+    ) -> Reply<pb::ListEventsResponse> {
+        let month = req.into_inner().month;
+        let mut events = self.read_log()?;
+        events.sort_by_key(|e| e.ts.clone());
+        let events = events
+            .into_iter()
+            .filter(|e| matches(&month, &e.ts))
+            .map(|e| pb::Event {
+                user: e.user().unwrap_or_default().to_string(),
+                ts: e.ts,
+                r#type: e.ty,
+                content: e.content.unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(pb::ListEventsResponse { events }))
+    }
+
+    async fn list_sessions(
+        &self,
+        req: Request<pb::ListSessionsRequest>,
+    ) -> Reply<pb::ListSessionsResponse> {
+        let month = req.into_inner().month;
+        let sessions = build_sessions(self.read_log()?)
+            .into_iter()
+            .filter(|s| matches(&month, &s.date))
+            .map(|s| pb::Session {
+                minutes: s.minutes(),
+                date: s.date,
+                time_range: s.time_range,
+                content: s.content.unwrap_or_default(),
+                user: s.user.unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(pb::ListSessionsResponse { sessions }))
+    }
+
+    async fn get_summary(
+        &self,
+        req: Request<pb::GetSummaryRequest>,
+    ) -> Reply<pb::GetSummaryResponse> {
+        let req = req.into_inner();
+        let months = monthly_hours(&build_sessions(self.read_log()?))
+            .into_iter()
+            .filter(|(month, _)| matches(&req.month, month))
+            .map(|(month, hours)| pb::MonthSummary {
+                month: month.replace('/', "-"),
+                hours,
+                salary: (hours * req.rate).round() as i64,
+            })
+            .collect();
+        Ok(Response::new(pb::GetSummaryResponse { months }))
+    }
+}
+
+pub fn serve(log: &Path, user: Option<&str>, listen: &str) -> anyhow::Result<()> {
+    let addr = listen
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{listen}: no address to listen on"))?;
+    let service = Service {
+        log: log.to_path_buf(),
+        user: user.map(str::to_string),
+    };
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+        ]);
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let incoming = TcpIncoming::bind(addr)?;
+        eprintln!("kintai listening on grpc://{}", incoming.local_addr()?);
+        Server::builder()
+            .accept_http1(true)
+            .layer(cors)
+            .layer(tonic_web::GrpcWebLayer::new())
+            .add_service(KintaiServer::new(service))
+            .serve_with_incoming(incoming)
+            .await?;
+        Ok(())
+    })
+}
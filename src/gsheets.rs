@@ -0,0 +1,131 @@
+//! Uploads the monthly table to a Google Sheet through the Sheets API,
+//! using `curl` for the HTTPS requests.
+
+use serde_json::{Value, json};
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    config::Config,
+    excel::{MonthSheet, month_sheet},
+};
+
+const API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+
+/// An OAuth access token: `$KINTAI_GSHEETS_TOKEN`, `gsheets.token` in the
+/// config, or whatever `gcloud auth print-access-token` prints.
+fn access_token(config: &Config) -> anyhow::Result<String> {
+    if let Ok(t) = std::env::var("KINTAI_GSHEETS_TOKEN")
+        && !t.is_empty()
+    {
+        return Ok(t);
+    }
+    if let Some(t) = config.str("gsheets.token") {
+        return Ok(t.to_string());
+    }
+    let out = Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .output()
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "no Google access token: set KINTAI_GSHEETS_TOKEN or install gcloud ({e})"
+            )
+        })?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "gcloud auth print-access-token failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(out.stdout)?.trim().to_string())
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Sends one request with `curl`; the token is passed on stdin so it does
+/// not show up in the process list.
+fn call(method: &str, url: &str, token: &str, body: &Value) -> anyhow::Result<Value> {
+    let mut child = Command::new("curl")
+        .args(["-sS", "--fail-with-body", "-X", method, "-H", "@-"])
+        .args(["-H", "Content-Type: application/json"])
+        .arg("--data-binary")
+        .arg(body.to_string())
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("cannot run curl: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "Authorization: Bearer {token}")?;
+    }
+    let out = child.wait_with_output()?;
+    let reply: Value = serde_json::from_slice(&out.stdout).unwrap_or(Value::Null);
+    if !out.status.success() {
+        let msg = reply["error"]["message"]
+            .as_str()
+            .unwrap_or("request failed");
+        anyhow::bail!("Sheets API: {msg}");
+    }
+    Ok(reply)
+}
+
+fn values(sheet: &MonthSheet) -> Value {
+    let mut rows = vec![json!([sheet.title]), json!([])];
+    rows.push(json!(["日付", "勤務時間", "作業内容"]));
+    for r in &sheet.rows {
+        rows.push(json!([r.date, r.time, r.content]));
+    }
+    rows.push(json!([]));
+    rows.push(json!(["勤務時間の合計"]));
+    rows.push(json!([sheet.total_label]));
+    Value::Array(rows)
+}
+
+/// Replaces the contents of `tab` in the spreadsheet with the first month
+/// in the log.
+pub fn upload(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    spreadsheet_id: Option<String>,
+    tab: &str,
+) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let id = spreadsheet_id
+        .or_else(|| config.str("gsheets.spreadsheet_id").map(str::to_string))
+        .ok_or_else(|| {
+            anyhow::anyhow!("pass --spreadsheet-id or set gsheets.spreadsheet_id in the config")
+        })?;
+    let Some(sheet) = month_sheet(input, user, false, None)? else {
+        return Ok(());
+    };
+    let token = access_token(&config)?;
+
+    let quoted = format!("'{}'", tab.replace('\'', "''"));
+    let clear = format!(
+        "{API}/{id}/values/{}:clear",
+        percent_encode(&format!("{quoted}!A:C"))
+    );
+    call("POST", &clear, &token, &json!({}))?;
+    let update = format!(
+        "{API}/{id}/values/{}?valueInputOption=RAW",
+        percent_encode(&format!("{quoted}!A1"))
+    );
+    let reply = call("PUT", &update, &token, &json!({ "values": values(&sheet) }))?;
+    println!(
+        "Updated {} cells in https://docs.google.com/spreadsheets/d/{id} ({tab})",
+        reply["updatedCells"].as_u64().unwrap_or_default()
+    );
+    Ok(())
+}
@@ -0,0 +1,377 @@
+//! The undo history of a log. Every event a command appends is noted in
+//! `journal-<key>.tsv` under the data directory, grouped into operations:
+//! one per command run, or per request in long-running commands such as the
+//! daemon. `kintai undo` voids the events of an operation, and `kintai redo`
+//! voids the voids of the last undo, which brings the events back.
+//!
+//! The journal holds event IDs and the name of the command only, never its
+//! arguments, so no descriptions end up outside the log; `privacy purge`
+//! drops the entries of the events it deletes (see [`scrub`]).
+
+use chrono::DateTime;
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    alias::VALUE_OPTIONS,
+    close,
+    event::{read_log, record_event_at, user_field},
+    paths, privacy,
+    report::plural,
+    state,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Op,
+    Undo,
+    Redo,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Op => "op",
+            Kind::Undo => "undo",
+            Kind::Redo => "redo",
+        }
+    }
+}
+
+/// The operation the events recorded by this process belong to.
+struct Current {
+    /// Assigned when the first event is noted.
+    number: Option<usize>,
+    kind: Kind,
+    /// The operation an undo or redo reverts.
+    reverts: Option<usize>,
+    /// Defaults to the command's name.
+    label: Option<String>,
+}
+
+static CURRENT: Mutex<Current> = Mutex::new(Current {
+    number: None,
+    kind: Kind::Op,
+    reverts: None,
+    label: None,
+});
+
+/// Starts a new operation: later events are undone separately from earlier
+/// ones recorded by the same process.
+pub fn begin(label: String) {
+    start(Kind::Op, None, label);
+}
+
+fn start(kind: Kind, reverts: Option<usize>, label: String) {
+    let mut current = CURRENT.lock().unwrap();
+    *current = Current {
+        number: None,
+        kind,
+        reverts,
+        label: Some(label),
+    };
+}
+
+fn journal_path(log: &Path) -> PathBuf {
+    paths::data_dir().join(format!("journal-{}.tsv", state::log_key(log, None)))
+}
+
+/// The subcommand (or alias) of a command line, without options and
+/// arguments, which may hold descriptions.
+fn command_name<'a>(args: impl IntoIterator<Item = &'a str>) -> String {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if VALUE_OPTIONS.contains(&arg) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            return arg.replace(['\t', '\n'], " ");
+        }
+    }
+    String::new()
+}
+
+/// A label as journaled now. Older versions journaled the whole command
+/// line, which is cut down to the command's name.
+fn clean_label(label: &str) -> String {
+    let words: Vec<&str> = label.split_whitespace().collect();
+    match words[..] {
+        [kind @ ("undo" | "redo"), n, ..] if n.starts_with('#') => format!("{kind} {n}"),
+        [source @ ("daemon:" | "mcp:"), ty, ..] => format!("{source} {ty}"),
+        ["tui:", ..] => "tui".to_string(),
+        _ => command_name(words),
+    }
+}
+
+struct Entry {
+    number: usize,
+    ts: String,
+    kind: Kind,
+    reverts: Option<usize>,
+    ids: Vec<String>,
+    label: String,
+}
+
+/// The operations journaled for `log`, oldest first.
+fn entries(log: &Path) -> anyhow::Result<Vec<Entry>> {
+    let text = match fs::read_to_string(journal_path(log)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries: BTreeMap<usize, Entry> = BTreeMap::new();
+    for line in text.lines() {
+        let cols: Vec<&str> = line.splitn(6, '\t').collect();
+        let [number, ts, kind, reverts, id, label] = cols[..] else {
+            continue;
+        };
+        let Ok(number) = number.parse() else {
+            continue;
+        };
+        let kind = match kind {
+            "undo" => Kind::Undo,
+            "redo" => Kind::Redo,
+            _ => Kind::Op,
+        };
+        entries
+            .entry(number)
+            .or_insert_with(|| Entry {
+                number,
+                ts: ts.to_string(),
+                kind,
+                reverts: reverts.parse().ok(),
+                ids: Vec::new(),
+                label: clean_label(label),
+            })
+            .ids
+            .push(id.to_string());
+    }
+    Ok(entries.into_values().collect())
+}
+
+/// Rewrites the journal of `log` without the events in `purged`, dropping
+/// operations left empty and cutting old command-line labels down to the
+/// command's name. The old journal is overwritten with zeros first. Returns
+/// the number of entries removed.
+pub fn scrub(log: &Path, purged: &HashSet<String>) -> anyhow::Result<usize> {
+    let path = journal_path(log);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut removed = 0;
+    let mut new = String::new();
+    for line in text.lines() {
+        let cols: Vec<&str> = line.splitn(6, '\t').collect();
+        let [number, ts, kind, reverts, id, label] = cols[..] else {
+            continue;
+        };
+        if purged.contains(id) {
+            removed += 1;
+            continue;
+        }
+        writeln!(
+            new,
+            "{number}\t{ts}\t{kind}\t{reverts}\t{id}\t{}",
+            clean_label(label)
+        )
+        .unwrap();
+    }
+    if new != text {
+        privacy::replace(&path, new.as_bytes())?;
+    }
+    Ok(removed)
+}
+
+/// The journaled operations that involve `ids`, listing only those, as in
+/// `privacy export`.
+pub fn export_json(log: &Path, ids: &HashSet<&str>) -> anyhow::Result<Vec<Value>> {
+    Ok(entries(log)?
+        .into_iter()
+        .filter_map(|e| {
+            let mine: Vec<&String> = e
+                .ids
+                .iter()
+                .filter(|id| ids.contains(id.as_str()))
+                .collect();
+            (!mine.is_empty()).then(|| {
+                json!({
+                    "operation": e.number,
+                    "ts": e.ts,
+                    "kind": e.kind.as_str(),
+                    "reverts": e.reverts,
+                    "command": e.label,
+                    "ids": mine,
+                })
+            })
+        })
+        .collect())
+}
+
+/// Notes that event `id` was appended to `log`, as part of the current
+/// operation.
+pub fn note(log: &Path, id: &str) -> anyhow::Result<()> {
+    let mut current = CURRENT.lock().unwrap();
+    let number = match current.number {
+        Some(n) => n,
+        None => {
+            let n = entries(log)?.last().map_or(1, |e| e.number + 1);
+            current.number = Some(n);
+            n
+        }
+    };
+    let label = current.label.clone().unwrap_or_else(|| {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        command_name(args.iter().map(String::as_str))
+    });
+    let path = journal_path(log);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(
+        f,
+        "{number}\t{}\t{}\t{}\t{id}\t{label}",
+        state::now().to_rfc3339(),
+        current.kind.as_str(),
+        current.reverts.map(|n| n.to_string()).unwrap_or_default()
+    )?;
+    Ok(())
+}
+
+/// Whether operation `number` is currently undone: some undo or redo that
+/// reverts it is not itself undone.
+fn is_undone(entries: &[Entry], number: usize) -> bool {
+    entries
+        .iter()
+        .any(|e| e.reverts == Some(number) && e.number > number && !is_undone(entries, e.number))
+}
+
+/// Voids the events of `entry` that are still in the log, newest first.
+fn revert(
+    log: &Path,
+    user: Option<&str>,
+    entry: &Entry,
+    kind: Kind,
+    force: bool,
+) -> anyhow::Result<usize> {
+    start(
+        kind,
+        Some(entry.number),
+        format!("{} #{}", kind.as_str(), entry.number),
+    );
+    let events = read_log(log)?;
+    let mut count = 0;
+    for id in entry.ids.iter().rev() {
+        // Events archived or purged since can no longer be voided.
+        let Some(e) = events.iter().find(|e| e.id() == id) else {
+            continue;
+        };
+        let now = state::now().to_rfc3339();
+        let mut fields = user_field(e.user().or(user));
+        fields.push(("ref", id));
+        for ts in [e.ts.as_str(), now.as_str()] {
+            for f in close::guard(log, user, ts, force)? {
+                if !fields.contains(&f) {
+                    fields.push(f);
+                }
+            }
+        }
+        record_event_at(Some(log), &now, "void", &fields, None)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// `kintai history`: the journaled operations of `log`, newest last.
+pub fn show(log: &Path) -> anyhow::Result<()> {
+    let entries = entries(log)?;
+    if entries.is_empty() {
+        println!("No history for {}.", log.display());
+        return Ok(());
+    }
+    let mut out = String::new();
+    writeln!(out, "| # | time | command | events | state |").unwrap();
+    writeln!(out, "|---|------|---------|--------|-------|").unwrap();
+    for e in &entries {
+        let time = DateTime::parse_from_rfc3339(&e.ts)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| e.ts.clone());
+        let state = if is_undone(&entries, e.number) {
+            "undone"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "| {} | {time} | {} | {} | {state} |",
+            e.number,
+            e.label,
+            e.ids.len()
+        )
+        .unwrap();
+    }
+    println!("{out}");
+    Ok(())
+}
+
+/// `kintai undo`: reverts operation `number`, or the latest operation that
+/// is not undone.
+pub fn undo(
+    log: &Path,
+    user: Option<&str>,
+    number: Option<usize>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let entries = entries(log)?;
+    let entry = match number {
+        Some(n) => {
+            let entry = entries
+                .iter()
+                .find(|e| e.number == n)
+                .ok_or_else(|| anyhow::anyhow!("no operation #{n}; see `kintai history`"))?;
+            if is_undone(&entries, n) {
+                anyhow::bail!("#{n} is already undone; `kintai redo` restores it");
+            }
+            entry
+        }
+        None => entries
+            .iter()
+            .rev()
+            .find(|e| e.kind == Kind::Op && !is_undone(&entries, e.number))
+            .ok_or_else(|| anyhow::anyhow!("nothing to undo"))?,
+    };
+    let count = revert(log, user, entry, Kind::Undo, force)?;
+    println!(
+        "Undid #{} ({}): voided {count} event{}.",
+        entry.number,
+        entry.label,
+        plural(count)
+    );
+    Ok(())
+}
+
+/// `kintai redo`: reverts the latest undo that is still in effect.
+pub fn redo(log: &Path, user: Option<&str>, force: bool) -> anyhow::Result<()> {
+    let entries = entries(log)?;
+    let undo = entries
+        .iter()
+        .rev()
+        .find(|e| e.kind == Kind::Undo && !is_undone(&entries, e.number))
+        .ok_or_else(|| anyhow::anyhow!("nothing to redo"))?;
+    revert(log, user, undo, Kind::Redo, force)?;
+    let target = undo.reverts.unwrap_or_default();
+    let label = entries
+        .iter()
+        .find(|e| e.number == target)
+        .map_or("", |e| e.label.as_str());
+    println!("Redid #{target} ({label}).");
+    Ok(())
+}
@@ -0,0 +1,255 @@
+//! Japanese national holidays (国民の祝日), computed from the rules of the
+//! Holidays Act rather than listed, so `calendar.national = "jp"` keeps
+//! working in later years. The rules follow the act's amendments: fixed
+//! dates before the Happy Monday moves of 2000 and 2003, the substitute
+//! holiday only on the Monday before 2007, and the one-off holidays of the
+//! imperial ceremonies. The equinoxes use the usual approximation, which
+//! matches the official announcements from 1980 to 2099, so only those
+//! years ([`YEARS`]) have holidays.
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::ops::RangeInclusive;
+
+/// The years whose holidays are computed; others have none.
+pub const YEARS: RangeInclusive<i32> = 1980..=2099;
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid holiday date")
+}
+
+/// The `n`th Monday of `month`.
+fn monday(year: i32, month: u32, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, n).expect("valid Monday")
+}
+
+fn equinox(year: i32, base: f64) -> u32 {
+    let y = (year - 1980) as f64;
+    (base + 0.242194 * y - (y / 4.0).floor()).floor() as u32
+}
+
+/// Holidays named by the act for `year`, before substitute and in-between
+/// days are added.
+fn named(year: i32) -> Vec<NaiveDate> {
+    let mut days = vec![
+        date(year, 1, 1),
+        date(year, 2, 11),
+        date(year, 3, equinox(year, 20.8431)),
+        // The Emperor's Birthday until 1988, then Greenery and Shōwa Day.
+        date(year, 4, 29),
+        date(year, 5, 3),
+        date(year, 5, 5),
+        date(year, 9, equinox(year, 23.2488)),
+        date(year, 11, 3),
+        date(year, 11, 23),
+    ];
+    // Coming of Age Day and Sports Day moved to Mondays in 2000, Marine Day
+    // (from 1996) and Respect for the Aged Day in 2003.
+    if year < 2000 {
+        days.extend([date(year, 1, 15), date(year, 10, 10)]);
+    } else {
+        days.push(monday(year, 1, 2));
+    }
+    if year < 2003 {
+        days.push(date(year, 9, 15));
+        if year >= 1996 {
+            days.push(date(year, 7, 20));
+        }
+    } else {
+        days.push(monday(year, 9, 3));
+    }
+    // Greenery Day moved to May 4th, until then a citizens' holiday.
+    if year >= 2007 {
+        days.push(date(year, 5, 4));
+    }
+    match year {
+        1989..=2018 => days.push(date(year, 12, 23)),
+        2019 => days.extend([date(2019, 5, 1), date(2019, 10, 22)]),
+        2020.. => days.push(date(year, 2, 23)),
+        _ => {}
+    }
+    // Marine, Mountain and Sports Day moved for the Tokyo Olympics.
+    match year {
+        2020 => days.extend([date(2020, 7, 23), date(2020, 7, 24), date(2020, 8, 10)]),
+        2021 => days.extend([date(2021, 7, 22), date(2021, 7, 23), date(2021, 8, 8)]),
+        2003.. => {
+            days.push(monday(year, 7, 3));
+            if year >= 2016 {
+                days.push(date(year, 8, 11));
+            }
+        }
+        _ => {}
+    }
+    if (2000..2020).contains(&year) || year >= 2022 {
+        days.push(monday(year, 10, 2));
+    }
+    // Imperial funeral, enthronement ceremony and wedding.
+    match year {
+        1989 => days.push(date(1989, 2, 24)),
+        1990 => days.push(date(1990, 11, 12)),
+        1993 => days.push(date(1993, 6, 9)),
+        _ => {}
+    }
+    days.sort();
+    days
+}
+
+/// All holidays of `year`, sorted: the named ones, a substitute holiday
+/// (振替休日) after each one on a Sunday, and a citizens' holiday (国民の
+/// 休日, from 1986) on a day between two holidays. The substitute is the
+/// next day that is not a holiday, and before 2007 the Monday only, if it
+/// is not one already. Empty outside [`YEARS`].
+pub fn japan(year: i32) -> Vec<NaiveDate> {
+    if !YEARS.contains(&year) {
+        return Vec::new();
+    }
+    let named = named(year);
+    let mut days = named.clone();
+    for d in &named {
+        if d.weekday() == Weekday::Sun {
+            let mut sub = d.succ_opt().expect("date in range");
+            while named.contains(&sub) && year >= 2007 {
+                sub = sub.succ_opt().expect("date in range");
+            }
+            days.push(sub);
+        }
+    }
+    for pair in named.windows(2) {
+        if let [a, b] = pair
+            && year >= 1986
+            && (*b - *a).num_days() == 2
+        {
+            let between = a.succ_opt().expect("date in range");
+            if between.weekday() != Weekday::Sun && !days.contains(&between) {
+                days.push(between);
+            }
+        }
+    }
+    days.sort();
+    days.dedup();
+    days
+}
+
+/// Whether `day` is a Japanese national holiday.
+pub fn is_japanese(day: NaiveDate) -> bool {
+    japan(day.year()).contains(&day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `year`'s holidays as `MM-DD`.
+    fn days(year: i32) -> Vec<String> {
+        japan(year)
+            .iter()
+            .map(|d| d.format("%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn matches_1989_with_the_imperial_funeral() {
+        assert_eq!(
+            days(1989),
+            [
+                "01-01", "01-02", "01-15", "01-16", "02-11", "02-24", "03-21", "04-29", "05-03",
+                "05-04", "05-05", "09-15", "09-23", "10-10", "11-03", "11-23", "12-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_1999_before_the_happy_mondays() {
+        assert_eq!(
+            days(1999),
+            [
+                "01-01", "01-15", "02-11", "03-21", "03-22", "04-29", "05-03", "05-04", "05-05",
+                "07-20", "09-15", "09-23", "10-10", "10-11", "11-03", "11-23", "12-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2002_with_the_first_happy_mondays() {
+        assert_eq!(
+            days(2002),
+            [
+                "01-01", "01-14", "02-11", "03-21", "04-29", "05-03", "05-04", "05-05", "05-06",
+                "07-20", "09-15", "09-16", "09-23", "10-14", "11-03", "11-04", "11-23", "12-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2006_before_greenery_day_moved() {
+        assert_eq!(
+            days(2006),
+            [
+                "01-01", "01-02", "01-09", "02-11", "03-21", "04-29", "05-03", "05-04", "05-05",
+                "07-17", "09-18", "09-23", "10-09", "11-03", "11-23", "12-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2019_with_the_enthronement_holidays() {
+        // Ten days off around the enthronement on May 1st, and the
+        // enthronement ceremony on October 22nd.
+        assert_eq!(
+            days(2019),
+            [
+                "01-01", "01-14", "02-11", "03-21", "04-29", "04-30", "05-01", "05-02", "05-03",
+                "05-04", "05-05", "05-06", "07-15", "08-11", "08-12", "09-16", "09-23", "10-14",
+                "10-22", "11-03", "11-04", "11-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2020_with_the_olympic_moves() {
+        assert_eq!(
+            days(2020),
+            [
+                "01-01", "01-13", "02-11", "02-23", "02-24", "03-20", "04-29", "05-03", "05-04",
+                "05-05", "05-06", "07-23", "07-24", "08-10", "09-21", "09-22", "11-03", "11-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2021_with_the_postponed_olympic_moves() {
+        assert_eq!(
+            days(2021),
+            [
+                "01-01", "01-11", "02-11", "02-23", "03-20", "04-29", "05-03", "05-04", "05-05",
+                "07-22", "07-23", "08-08", "08-09", "09-20", "09-23", "11-03", "11-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_2026_with_a_citizens_holiday() {
+        assert_eq!(
+            days(2026),
+            [
+                "01-01", "01-12", "02-11", "02-23", "03-20", "04-29", "05-03", "05-04", "05-05",
+                "05-06", "07-20", "08-11", "09-21", "09-22", "09-23", "10-12", "11-03", "11-23",
+            ]
+        );
+    }
+
+    #[test]
+    fn has_no_holidays_outside_the_supported_years() {
+        assert!(japan(1979).is_empty());
+        assert!(japan(2100).is_empty());
+        assert!(!is_japanese(date(2100, 1, 1)));
+    }
+
+    #[test]
+    fn keeps_the_emperors_birthday_of_each_era() {
+        assert!(!is_japanese(date(1988, 12, 23)));
+        assert!(is_japanese(date(2018, 12, 23)));
+        assert!(!is_japanese(date(2019, 12, 23)));
+        assert!(!is_japanese(date(2019, 2, 23)));
+        assert!(is_japanese(date(2020, 2, 23)));
+    }
+}
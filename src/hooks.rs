@@ -0,0 +1,83 @@
+//! Shell commands run after events are recorded, from the `[hooks]` config
+//! section: the key is an event type (or `"*"` for every event) and the
+//! value a command for `sh -c` (`cmd /C` on Windows), e.g.
+//!
+//! ```toml
+//! [hooks]
+//! start = "slack-status 'Working'"
+//! finish = "slack-status ''"
+//! ```
+//!
+//! The event is passed in `KINTAI_TS`, `KINTAI_TYPE`, `KINTAI_ID`,
+//! `KINTAI_CONTENT` and `KINTAI_USER`, plus `KINTAI_LOG` when there is a log.
+//! Hooks run in the background; a failing hook never undoes the event.
+//! Their output goes to stderr, so it cannot mix with what kintai prints
+//! (or, under `kintai mcp`, with its protocol on stdout).
+
+use std::{
+    io,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::config::Config;
+
+/// The event just recorded.
+pub struct HookEvent<'a> {
+    pub ts: &'a str,
+    pub ty: &'a str,
+    pub id: &'a str,
+    pub content: Option<&'a str>,
+    pub user: Option<&'a str>,
+}
+
+/// A `Command` running `command` in the platform's shell.
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    // cmd parses its command line itself, so it must not be quoted again.
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").raw_arg(command);
+    cmd
+}
+
+/// Starts the hooks configured for `event`'s type.
+pub fn run(log: Option<&Path>, event: &HookEvent) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let commands = [event.ty, "*"]
+        .into_iter()
+        .filter_map(|key| config.str(&format!("hooks.{key}")));
+    for command in commands {
+        let mut cmd = shell(command);
+        cmd.env("KINTAI_TS", event.ts)
+            .env("KINTAI_TYPE", event.ty)
+            .env("KINTAI_ID", event.id)
+            .env("KINTAI_CONTENT", event.content.unwrap_or_default())
+            .env("KINTAI_USER", event.user.unwrap_or_default())
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(io::stderr()));
+        if let Some(log) = log {
+            cmd.env("KINTAI_LOG", log);
+        }
+        let ty = event.ty.to_string();
+        match cmd.spawn() {
+            Ok(mut child) => {
+                thread::spawn(move || match child.wait() {
+                    Ok(s) if !s.success() => eprintln!("kintai: {ty} hook: {s}"),
+                    Err(e) => eprintln!("kintai: {ty} hook: {e}"),
+                    _ => {}
+                });
+            }
+            Err(e) => eprintln!("kintai: {ty} hook: {e}"),
+        }
+    }
+    Ok(())
+}
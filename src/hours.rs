@@ -0,0 +1,166 @@
+//! `kintai hours [today|week|month]`: the time worked so far in the period,
+//! as one figure for status bars and prompts. A running daemon answers
+//! directly; otherwise the finished sessions' totals are cached per log and
+//! only recomputed when the log, the config, `--overnight` or
+//! `--include-archived` changes.
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use clap::ValueEnum;
+use std::{fs, path::Path};
+
+use crate::{
+    calendar::Weeks,
+    config::Config,
+    event::{filter_user, read_log},
+    paths,
+    report::format_hm,
+    session::{Session, build_sessions},
+    state::{self, TrackingState, inputs_stamp, log_key},
+};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    /// Today so far
+    #[default]
+    Today,
+    /// This week so far (weeks as `weeks.numbering`, else ISO weeks)
+    Week,
+    /// This month so far
+    Month,
+}
+
+impl Period {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Period::Today => "today",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    /// The day, week or month `date` is in, such as `2026-W42`.
+    fn label(self, weeks: &Weeks, date: NaiveDate) -> String {
+        match self {
+            Period::Today => date.format("%Y/%m/%d").to_string(),
+            Period::Week => weeks.label(date),
+            Period::Month => date.format("%Y/%m").to_string(),
+        }
+    }
+}
+
+const PERIODS: [Period; 3] = [Period::Today, Period::Week, Period::Month];
+
+fn weeks() -> anyhow::Result<Weeks> {
+    Ok(Weeks::load(&Config::load()?)?.unwrap_or(Weeks::Iso))
+}
+
+/// Seconds worked in finished sessions in `label` of `period`.
+fn finished_secs(sessions: &[Session], period: Period, weeks: &Weeks, label: &str) -> i64 {
+    sessions
+        .iter()
+        .filter(|s| {
+            NaiveDate::parse_from_str(&s.date, "%Y/%m/%d")
+                .is_ok_and(|d| period.label(weeks, d) == label)
+        })
+        .map(|s| s.minutes() * 60)
+        .sum()
+}
+
+/// Seconds worked in `period` up to `now`: finished sessions plus the
+/// running one, if it started in the same period.
+pub fn secs(
+    st: &TrackingState,
+    sessions: &[Session],
+    period: Period,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<i64> {
+    let weeks = weeks()?;
+    let label = period.label(&weeks, now.date_naive());
+    Ok(finished_secs(sessions, period, &weeks, &label) + running_secs(st, period, &weeks, now))
+}
+
+fn running_secs(
+    st: &TrackingState,
+    period: Period,
+    weeks: &Weeks,
+    now: DateTime<FixedOffset>,
+) -> i64 {
+    match st.session_start {
+        Some(start)
+            if period.label(weeks, start.date_naive()) == period.label(weeks, now.date_naive()) =>
+        {
+            st.worked_secs(now)
+        }
+        _ => 0,
+    }
+}
+
+/// Finished seconds in the `period` containing `now`, from the cache while
+/// its inputs (see [`state::inputs_stamp`]) are unchanged. The cache line holds
+/// `<period>=<label>:<secs>` for all periods, so a new day, week or month
+/// is noticed.
+fn cached_finished(
+    log: &Path,
+    user: Option<&str>,
+    period: Period,
+    weeks: &Weeks,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<i64> {
+    let Some(stamp) = inputs_stamp(log)? else {
+        return Ok(0);
+    };
+    let label = period.label(weeks, now.date_naive());
+    let cache = paths::cache_dir().join(format!("hours-{}", log_key(log, user)));
+    if let Ok(cached) = fs::read_to_string(&cache)
+        && let Some(fields) = cached.trim().strip_prefix(&stamp)
+    {
+        let key = format!("{}={label}:", period.as_str());
+        if let Some(secs) = fields
+            .split(' ')
+            .find_map(|f| f.strip_prefix(&key)?.parse().ok())
+        {
+            return Ok(secs);
+        }
+    }
+
+    let sessions = build_sessions(filter_user(read_log(log)?, user));
+    let mut line = stamp;
+    let mut result = 0;
+    for p in PERIODS {
+        let l = p.label(weeks, now.date_naive());
+        let secs = finished_secs(&sessions, p, weeks, &l);
+        if p == period {
+            result = secs;
+        }
+        line.push_str(&format!("{}={l}:{secs} ", p.as_str()));
+    }
+    // As with the state cache, failing to write it is not an error.
+    if let Some(dir) = cache.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(&cache, line.trim_end());
+    Ok(result)
+}
+
+/// Seconds worked in `period` so far, from a running daemon or the cache.
+pub fn total(log: &Path, user: Option<&str>, period: Period) -> anyhow::Result<i64> {
+    // A daemon keeps its own clock, so it cannot answer for `--now`.
+    #[cfg(unix)]
+    if !crate::time::clock().is_fixed()
+        && let Ok(secs) = crate::daemon::request(log, user, &format!("hours {}", period.as_str()))
+        && let Ok(secs) = secs.parse::<i64>()
+    {
+        return Ok(secs);
+    }
+    let now = state::now();
+    let weeks = weeks()?;
+    let st = state::load(log, user)?;
+    Ok(cached_finished(log, user, period, &weeks, now)? + running_secs(&st, period, &weeks, now))
+}
+
+/// Prints the time worked in `period` so far, such as `6h15m`.
+pub fn print(log: &Path, user: Option<&str>, period: Period) -> anyhow::Result<()> {
+    let secs = total(log, user, period)?;
+    println!("{}", format_hm(secs as f64 / 3600.0));
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//! `kintai import ics`: turns the events of an iCalendar file (as exported
+//! by most calendar apps) into sessions, or into breaks inside the sessions
+//! already in the log.
+//!
+//! Only what meetings need is read: `DTSTART`, `DTEND` or `DURATION`,
+//! `SUMMARY` and `STATUS`. Times with a `TZID` are taken as local time;
+//! all-day events and recurrence rules are skipped.
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{
+    close,
+    event::{filter_user, read_log, record_event_at, user_field},
+    report::plural,
+    session::build_sessions,
+};
+
+/// What calendar events become.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ImportAs {
+    /// A session of its own, where none was logged at the time
+    Session,
+    /// A break inside the session logged at the time
+    Break,
+}
+
+/// A timed calendar entry, from a file or a calendar service.
+pub struct CalendarEvent {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub summary: String,
+}
+
+/// Joins folded lines (continuations start with a space or tab).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// A `DTSTART`/`DTEND` value: UTC with a trailing `Z`, else local time.
+/// `None` for dates without a time (all-day events).
+fn parse_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        let t = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(
+            Utc.from_utc_datetime(&t)
+                .with_timezone(&Local)
+                .fixed_offset(),
+        );
+    }
+    let t = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(Local.from_local_datetime(&t).earliest()?.fixed_offset())
+}
+
+/// A `DURATION` such as `PT1H30M` or `P1D`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.strip_prefix('P')?;
+    let (days, time) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut total = Duration::zero();
+    let mut num = String::new();
+    for (part, units) in [(days, "WD"), (time, "HMS")] {
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                num.push(c);
+                continue;
+            }
+            if !units.contains(c) {
+                return None;
+            }
+            let n: i64 = std::mem::take(&mut num).parse().ok()?;
+            total += match c {
+                'W' => Duration::weeks(n),
+                'D' => Duration::days(n),
+                'H' => Duration::hours(n),
+                'M' => Duration::minutes(n),
+                _ => Duration::seconds(n),
+            };
+        }
+    }
+    Some(total)
+}
+
+/// Timed, non-cancelled events of the calendar, in start order.
+fn parse_calendar(text: &str) -> Vec<CalendarEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<Vec<(String, String)>> = None;
+    for line in unfold(text) {
+        match line.trim_end() {
+            "BEGIN:VEVENT" => current = Some(Vec::new()),
+            "END:VEVENT" => {
+                if let Some(props) = current.take()
+                    && let Some(e) = event_of(&props)
+                {
+                    events.push(e);
+                }
+            }
+            line => {
+                if let Some(props) = current.as_mut()
+                    && let Some((name, value)) = line.split_once(':')
+                {
+                    // Drop parameters such as `;TZID=Asia/Tokyo`.
+                    let name = name.split(';').next().unwrap_or_default();
+                    props.push((name.to_ascii_uppercase(), value.to_string()));
+                }
+            }
+        }
+    }
+    events.sort_by_key(|e| e.start);
+    events
+}
+
+fn event_of(props: &[(String, String)]) -> Option<CalendarEvent> {
+    let prop = |name: &str| {
+        props
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+    if prop("RRULE").is_some() || prop("STATUS") == Some("CANCELLED") {
+        return None;
+    }
+    let start = parse_time(prop("DTSTART")?)?;
+    let end = match (prop("DTEND"), prop("DURATION")) {
+        (Some(end), _) => parse_time(end)?,
+        (None, Some(d)) => start + parse_duration(d)?,
+        (None, None) => return None,
+    };
+    (end > start).then(|| CalendarEvent {
+        start,
+        end,
+        summary: unescape(prop("SUMMARY").unwrap_or_default())
+            .trim()
+            .to_string(),
+    })
+}
+
+fn parse_day(day: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("invalid date {day:?} (expected YYYY-MM-DD)"))
+}
+
+/// How `import ics` (and `import outlook`) converts and records calendar
+/// events.
+pub struct IcsOptions {
+    pub import_as: ImportAs,
+    /// Project of the imported sessions, or kind of the imported breaks
+    /// (default `meeting`).
+    pub tag: Option<String>,
+    /// First and last day to import, as `YYYY-MM-DD`.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Record the events instead of only listing them.
+    pub apply: bool,
+    /// Record even into a closed month.
+    pub force: bool,
+}
+
+/// Imports the events of the iCalendar `file`; see [`import_events`].
+pub fn import(
+    log: &Path,
+    user: Option<&str>,
+    file: &Path,
+    options: &IcsOptions,
+) -> anyhow::Result<()> {
+    let text = fs::read_to_string(file).map_err(|e| anyhow::anyhow!("{}: {e}", file.display()))?;
+    import_events(log, user, parse_calendar(&text), options, "ics")
+}
+
+/// Proposes an event pair for every calendar event: a session for each
+/// event outside the logged sessions, or a break for each event inside one.
+/// With `apply` the events are recorded, tagged `source=<source>`.
+pub fn import_events(
+    log: &Path,
+    user: Option<&str>,
+    events: Vec<CalendarEvent>,
+    options: &IcsOptions,
+    source: &str,
+) -> anyhow::Result<()> {
+    let from = options.from.as_deref().map(parse_day).transpose()?;
+    let to = options.to.as_deref().map(parse_day).transpose()?;
+    let tag = options.tag.as_deref();
+    let logged = filter_user(read_log(log)?, user);
+    let breaks: Vec<DateTime<FixedOffset>> = logged
+        .iter()
+        .filter(|e| e.ty == "break_start")
+        .filter_map(|e| DateTime::parse_from_rfc3339(&e.ts).ok())
+        .collect();
+    let sessions: Vec<_> = build_sessions(logged)
+        .iter()
+        .map(|s| (s.start, s.end()))
+        .collect();
+
+    let mut proposals = Vec::new();
+    let mut out = String::new();
+    writeln!(out, "| date | time | event | import |").unwrap();
+    writeln!(out, "|------|------|-------|--------|").unwrap();
+    for e in events {
+        let date = e.start.date_naive();
+        if from.is_some_and(|f| date < f) || to.is_some_and(|t| date > t) {
+            continue;
+        }
+        let inside = sessions
+            .iter()
+            .any(|(s, end)| *s <= e.start && e.end <= *end);
+        let overlaps = sessions.iter().any(|(s, end)| *s < e.end && e.start < *end);
+        let action = match options.import_as {
+            ImportAs::Session if overlaps => "skip (already tracked)",
+            ImportAs::Session => "session",
+            ImportAs::Break if breaks.contains(&e.start) => "skip (already imported)",
+            ImportAs::Break if inside => "break",
+            ImportAs::Break => "skip (not inside a session)",
+        };
+        writeln!(
+            out,
+            "| {date} | {}~{} | {} | {action} |",
+            e.start.format("%H:%M"),
+            e.end.format("%H:%M"),
+            e.summary
+        )
+        .unwrap();
+        if matches!(action, "session" | "break") {
+            proposals.push(e);
+        }
+    }
+    println!("{out}");
+    let what = match options.import_as {
+        ImportAs::Session => "session",
+        ImportAs::Break => "break",
+    };
+    if proposals.is_empty() {
+        println!("Nothing to import.");
+        return Ok(());
+    }
+    if !options.apply {
+        println!(
+            "Run again with --apply to record {} {what}{}.",
+            proposals.len(),
+            plural(proposals.len())
+        );
+        return Ok(());
+    }
+    for e in &proposals {
+        let content = (!e.summary.is_empty()).then(|| e.summary.clone());
+        let events = match options.import_as {
+            ImportAs::Session => [
+                ("start", e.start, tag.map(|t| ("project", t)), None),
+                ("finish", e.end, None, content),
+            ],
+            ImportAs::Break => [
+                (
+                    "break_start",
+                    e.start,
+                    Some(("kind", tag.unwrap_or("meeting"))),
+                    content,
+                ),
+                ("break_end", e.end, None, None),
+            ],
+        };
+        for (ty, ts, extra, content) in events {
+            let ts = ts.to_rfc3339();
+            let mut fields = user_field(user);
+            fields.extend(extra);
+            fields.push(("source", source));
+            fields.extend(close::guard(log, user, &ts, options.force)?);
+            record_event_at(Some(log), &ts, ty, &fields, content)?;
+        }
+    }
+    println!(
+        "Recorded {} {what}{}.",
+        proposals.len(),
+        plural(proposals.len())
+    );
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! What `--input` names: a log file, a directory of logs, or a glob pattern
+//! such as `logs/2024-*.log` (`*`, `?` and `[...]`, in any component), and
+//! whether events piped to stdin are read along with it (`--stdin`).
+
+use regex::Regex;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+static STDIN: OnceLock<bool> = OnceLock::new();
+
+/// Makes reports read the events on stdin along with the log (`--stdin`).
+pub fn set_stdin(stdin: bool) {
+    let _ = STDIN.set(stdin);
+}
+
+pub fn with_stdin() -> bool {
+    STDIN.get().copied().unwrap_or(false)
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// A glob component as an anchored regex.
+fn component_regex(pattern: &str) -> anyhow::Result<Regex> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                re.push('[');
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '!' if re.ends_with('[') => re.push('^'),
+                        '\\' => re.push_str("\\\\"),
+                        c => re.push(c),
+                    }
+                }
+                re.push(']');
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| anyhow::anyhow!("invalid pattern {pattern:?}: {e}"))
+}
+
+/// Regular files directly inside `dir`, except hidden ones, sorted by name.
+fn dir_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("{}: {e}", dir.display()))?
+        .filter_map(|e| Some(e.ok()?.path()))
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            !p.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The files to read for `path`: the file itself, the files in a directory,
+/// or the files matching a pattern, in name order.
+pub fn files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if path.is_dir() {
+        return dir_files(path);
+    }
+    if path.exists() || !is_pattern(&path.to_string_lossy()) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut matches = vec![PathBuf::new()];
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            for m in &mut matches {
+                m.push(component);
+            }
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if !is_pattern(&name) {
+            for m in &mut matches {
+                m.push(name.as_ref());
+            }
+            continue;
+        }
+        let re = component_regex(&name)?;
+        let mut next = Vec::new();
+        for dir in matches {
+            let listing = if dir.as_os_str().is_empty() {
+                fs::read_dir(".")
+            } else {
+                fs::read_dir(&dir)
+            };
+            let Ok(entries) = listing else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                if !file_name.starts_with('.') && re.is_match(&file_name) {
+                    next.push(dir.join(file_name.as_ref()));
+                }
+            }
+        }
+        matches = next;
+    }
+    matches.retain(|p| p.is_file());
+    if matches.is_empty() {
+        anyhow::bail!("no files match {}", path.display());
+    }
+    matches.sort();
+    Ok(matches)
+}
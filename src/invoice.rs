@@ -0,0 +1,347 @@
+//! `kintai invoice <client>`: a Markdown invoice for a month of work for one
+//! of the clients under `[clients.<name>]`. Each client's projects (the
+//! `projects` list, else the client's own name) become invoice lines, billed
+//! at the client's `rate` in `billing.increment` blocks when set.
+//!
+//! With `invoice.registration_number` set, the invoice follows the
+//! qualified invoice (適格請求書) format: the registration number, each
+//! line's tax rate (`invoice.tax_rates.<project>`, else `invoice.tax_rate`,
+//! else 10%) with reduced-rate lines marked ※, and per rate the subtotal and
+//! the tax, rounded once per rate as `invoice.tax_rounding` says.
+//!
+//! Invoice numbers are `<invoice_prefix><NNNN>`, counted per prefix across
+//! runs in `invoices.tsv` in the data directory; invoicing the same client
+//! and month again reuses its number.
+
+use chrono::{Duration, NaiveDate};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    event::{filter_user, read_events},
+    paths,
+    report::billed_minutes,
+    session::build_sessions,
+    state,
+};
+
+/// A client's details from `[clients.<name>]`.
+pub struct Client {
+    /// Key of the config section.
+    pub key: String,
+    /// Name on invoices (`name`, else the key).
+    pub name: String,
+    pub address: Option<String>,
+    pub contact: Option<String>,
+    /// Days from issue to the due date.
+    pub payment_terms: Option<i64>,
+    pub invoice_prefix: String,
+    pub rate: Option<f64>,
+    /// Projects billed to the client.
+    pub projects: Vec<String>,
+}
+
+impl Client {
+    pub fn load(config: &Config, key: &str) -> anyhow::Result<Client> {
+        if !config.sections("clients").contains(&key) {
+            anyhow::bail!("no client {key:?}; add a [clients.{key}] section to the config");
+        }
+        let get = |k: &str| config.get(&format!("clients.{key}.{k}"));
+        let text = |k: &str| get(k).and_then(|v| v.as_str()).map(str::to_string);
+        let num = |k: &str| match get(k) {
+            None => Ok(None),
+            Some(v) => v
+                .as_num()
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("clients.{key}.{k} must be a number")),
+        };
+        Ok(Client {
+            key: key.to_string(),
+            name: text("name").unwrap_or_else(|| key.to_string()),
+            address: text("address"),
+            contact: text("contact"),
+            payment_terms: num("payment_terms")?.map(|d| d as i64),
+            invoice_prefix: text("invoice_prefix").unwrap_or_default(),
+            rate: num("rate")?,
+            projects: config
+                .strs(&format!("clients.{key}.projects"))
+                .map(|p| p.into_iter().map(str::to_string).collect())
+                .unwrap_or_else(|| vec![key.to_string()]),
+        })
+    }
+}
+
+/// How the tax of each rate is rounded to a whole amount.
+#[derive(Clone, Copy)]
+enum TaxRounding {
+    Floor,
+    Round,
+    Ceil,
+}
+
+impl TaxRounding {
+    fn load(config: &Config) -> anyhow::Result<TaxRounding> {
+        match config.str("invoice.tax_rounding") {
+            None | Some("floor") => Ok(TaxRounding::Floor),
+            Some("round") => Ok(TaxRounding::Round),
+            Some("ceil") => Ok(TaxRounding::Ceil),
+            Some(v) => anyhow::bail!(
+                "invoice.tax_rounding must be \"floor\", \"round\" or \"ceil\", not {v:?}"
+            ),
+        }
+    }
+
+    fn apply(self, amount: f64) -> u64 {
+        match self {
+            TaxRounding::Floor => amount.floor() as u64,
+            TaxRounding::Round => amount.round() as u64,
+            TaxRounding::Ceil => amount.ceil() as u64,
+        }
+    }
+}
+
+/// The standard consumption tax rate, in percent.
+const STANDARD_TAX_RATE: f64 = 10.0;
+
+/// Tax rate of a project's line, in percent.
+fn tax_rate(config: &Config, project: &str) -> anyhow::Result<f64> {
+    let key = format!("invoice.tax_rates.{project}");
+    let value = config.get(&key).or_else(|| config.get("invoice.tax_rate"));
+    match value {
+        None => Ok(STANDARD_TAX_RATE),
+        Some(v) => v
+            .as_num()
+            .ok_or_else(|| anyhow::anyhow!("tax rates must be numbers (percent), see {key}")),
+    }
+}
+
+/// `T` followed by 13 digits.
+fn check_registration_number(number: &str) -> anyhow::Result<()> {
+    let digits = number.strip_prefix('T').unwrap_or_default();
+    if digits.len() != 13 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        anyhow::bail!(
+            "invoice.registration_number must be T and 13 digits (e.g. T1234567890123), not {number:?}"
+        );
+    }
+    Ok(())
+}
+
+/// Invoice lines with their tax rates, then the subtotal and tax per rate,
+/// each tax rounded once, as a qualified invoice requires.
+fn qualified_lines(
+    out: &mut String,
+    config: &Config,
+    lines: &[(String, f64)],
+    rate: f64,
+) -> anyhow::Result<()> {
+    let rounding = TaxRounding::load(config)?;
+    // Subtotals per tax rate, highest rate first; rates are kept in tenths
+    // of a percent so they can be map keys.
+    let mut by_rate: BTreeMap<Reverse<u32>, u64> = BTreeMap::new();
+    writeln!(out, "\n| item | hours | rate | amount | tax rate |").unwrap();
+    writeln!(out, "|------|-------|------|--------|----------|").unwrap();
+    let mut reduced = false;
+    for (project, hours) in lines {
+        let tax = tax_rate(config, project)?;
+        let amount = (hours * rate).round() as u64;
+        *by_rate
+            .entry(Reverse((tax * 10.0).round() as u32))
+            .or_default() += amount;
+        let mark = if tax < STANDARD_TAX_RATE {
+            reduced = true;
+            " ※"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "| {project}{mark} | {hours:.2} | {rate} | {amount} | {tax}% |"
+        )
+        .unwrap();
+    }
+    writeln!(out, "\n| tax rate | subtotal (excl. tax) | tax |").unwrap();
+    writeln!(out, "|----------|----------------------|-----|").unwrap();
+    let (mut subtotal, mut total_tax) = (0, 0);
+    for (Reverse(tenths), amount) in by_rate {
+        let percent = tenths as f64 / 10.0;
+        let tax = rounding.apply(amount as f64 * percent / 100.0);
+        subtotal += amount;
+        total_tax += tax;
+        writeln!(out, "| {percent}% | {amount} | {tax} |").unwrap();
+    }
+    writeln!(out, "| **Total** | {subtotal} | {total_tax} |").unwrap();
+    writeln!(
+        out,
+        "\n**Amount due (incl. tax): {}**",
+        subtotal + total_tax
+    )
+    .unwrap();
+    if reduced {
+        writeln!(out, "\n※ Reduced tax rate (軽減税率対象)").unwrap();
+    }
+    Ok(())
+}
+
+fn ledger_path() -> PathBuf {
+    paths::data_dir().join("invoices.tsv")
+}
+
+/// The number of the invoice for `client` and `month`: the one issued
+/// before, else the next free one for the client's prefix, saved to the
+/// ledger.
+pub fn invoice_number(client: &Client, month: &str) -> anyhow::Result<String> {
+    let path = ledger_path();
+    let ledger = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(anyhow::anyhow!("{}: {e}", path.display())),
+    };
+    let mut last = 0;
+    for line in ledger.lines() {
+        let mut cols = line.split('\t');
+        let (Some(key), Some(m), Some(number)) = (cols.next(), cols.next(), cols.next()) else {
+            continue;
+        };
+        if key == client.key && m == month {
+            return Ok(number.to_string());
+        }
+        if let Some(n) = number
+            .strip_prefix(&client.invoice_prefix)
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            last = last.max(n);
+        }
+    }
+    let number = format!("{}{:04}", client.invoice_prefix, last + 1);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut text = ledger;
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    writeln!(text, "{}\t{month}\t{number}", client.key).unwrap();
+    fs::write(&path, text)?;
+    Ok(number)
+}
+
+/// Hours billed per project of `client` in `month` (`YYYY-MM`), in the
+/// order of the client's projects; projects without sessions are left out.
+fn lines(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    client: &Client,
+    month: &str,
+    increment: Option<i64>,
+) -> anyhow::Result<Vec<(String, f64)>> {
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let month = month.replace('-', "/");
+    Ok(client
+        .projects
+        .iter()
+        .filter_map(|p| {
+            let minutes: i64 = sessions
+                .iter()
+                .filter(|s| s.date.starts_with(&month) && s.project.as_deref() == Some(p))
+                .map(|s| increment.map_or(s.minutes(), |i| billed_minutes(s.minutes(), i)))
+                .sum();
+            (minutes > 0).then(|| (p.clone(), minutes as f64 / 60.0))
+        })
+        .collect())
+}
+
+pub fn invoice(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    client: &str,
+    month: Option<String>,
+    rate: Option<f64>,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let config = Config::load()?;
+    let client = Client::load(&config, client)?;
+    let today = state::now().date_naive();
+    let month = month.unwrap_or_else(|| today.format("%Y-%m").to_string());
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a month as YYYY-MM, got {month:?}"))?;
+    let rate = rate.or(client.rate).ok_or_else(|| {
+        anyhow::anyhow!(
+            "no rate for {}: set clients.{}.rate or pass --rate",
+            client.name,
+            client.key
+        )
+    })?;
+    let increment = config
+        .get("billing.increment")
+        .and_then(|v| v.as_num())
+        .map(|m| m as i64);
+    let lines = lines(input, user, &client, &month, increment)?;
+    if lines.is_empty() {
+        anyhow::bail!(
+            "no sessions for {} ({}) in {month}",
+            client.name,
+            client.projects.join(", ")
+        );
+    }
+    let registration = config.str("invoice.registration_number");
+    if let Some(number) = registration {
+        check_registration_number(number)?;
+        if config.str("invoice.issuer").is_none() {
+            anyhow::bail!("a qualified invoice needs the issuer's name: set invoice.issuer");
+        }
+    }
+    let number = invoice_number(&client, &month)?;
+
+    let mut out = String::new();
+    writeln!(out, "# Invoice {number}\n").unwrap();
+    writeln!(out, "Issued: {today}").unwrap();
+    if let Some(days) = client.payment_terms {
+        writeln!(out, "Due: {} ({days} days)", today + Duration::days(days)).unwrap();
+    }
+    writeln!(out, "Period: {month}\n").unwrap();
+    writeln!(out, "**Bill to:** {}", client.name).unwrap();
+    for line in [&client.address, &client.contact].into_iter().flatten() {
+        writeln!(out, "{line}").unwrap();
+    }
+    if let Some(from) = config.str("invoice.issuer") {
+        writeln!(out, "\n**From:** {from}").unwrap();
+        if let Some(address) = config.str("invoice.address") {
+            writeln!(out, "{address}").unwrap();
+        }
+    }
+    if let Some(number) = registration {
+        writeln!(out, "Registration number (登録番号): {number}").unwrap();
+    }
+    match registration {
+        None => {
+            writeln!(out, "\n| item | hours | rate | amount |").unwrap();
+            writeln!(out, "|------|-------|------|--------|").unwrap();
+            let mut total = 0;
+            for (project, hours) in &lines {
+                let amount = (hours * rate).round() as u64;
+                total += amount;
+                writeln!(out, "| {project} | {hours:.2} | {rate} | {amount} |").unwrap();
+            }
+            writeln!(out, "| **Total** | | | **{total}** |").unwrap();
+        }
+        Some(_) => qualified_lines(&mut out, &config, &lines, rate)?,
+    }
+    if let Some(bank) = config.str("invoice.bank") {
+        writeln!(out, "\nPayment to: {bank}").unwrap();
+    }
+
+    match output {
+        Some(path) => {
+            fs::write(path, out)?;
+            println!("Wrote invoice {number} to {}", path.display());
+        }
+        None => print!("{out}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Issue references (`#123`, `PROJ-456`) found in session descriptions.
+//! They are kept on each session as `refs`, rendered as links where a URL
+//! template is configured, and totalled per issue.
+
+use regex::Regex;
+use std::{collections::BTreeMap, fmt::Write, sync::OnceLock};
+
+use crate::{config::Config, report::format_hm, session::Session};
+
+/// Default `issues.pattern`: GitHub-style `#42` and Jira-style `ABC-123`.
+const DEFAULT_PATTERN: &str = r"#[0-9]+|\b[A-Z][A-Z0-9]+-[0-9]+\b";
+
+/// How issue references are recognised and linked (the `[issues]` config
+/// section).
+pub struct Issues {
+    pattern: Regex,
+    /// URL templates by ID prefix (`#` or the part before `-`), from
+    /// `[issues.links]`. `{}` is replaced by the ID without a leading `#`.
+    links: BTreeMap<String, String>,
+}
+
+impl Default for Issues {
+    fn default() -> Self {
+        Issues {
+            pattern: Regex::new(DEFAULT_PATTERN).unwrap(),
+            links: BTreeMap::new(),
+        }
+    }
+}
+
+impl Issues {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let pattern = config.str("issues.pattern").unwrap_or(DEFAULT_PATTERN);
+        let pattern = Regex::new(pattern)
+            .map_err(|e| anyhow::anyhow!("invalid issues.pattern {pattern:?}: {e}"))?;
+        let links = config
+            .entries("issues.links")
+            .into_iter()
+            .filter_map(|(prefix, v)| Some((prefix.to_string(), v.as_str()?.to_string())))
+            .collect();
+        Ok(Issues { pattern, links })
+    }
+
+    /// Distinct issue IDs mentioned in `content`, in order of appearance.
+    pub fn refs(&self, content: &str) -> Vec<String> {
+        let mut refs: Vec<String> = Vec::new();
+        for m in self.pattern.find_iter(content) {
+            if !refs.iter().any(|r| r == m.as_str()) {
+                refs.push(m.as_str().to_string());
+            }
+        }
+        refs
+    }
+
+    /// URL of issue `id`, if its prefix has a link template.
+    pub fn link(&self, id: &str) -> Option<String> {
+        let prefix = if id.starts_with('#') {
+            "#"
+        } else {
+            id.split_once('-')?.0
+        };
+        let template = self.links.get(prefix)?;
+        Some(template.replace("{}", id.trim_start_matches('#')))
+    }
+
+    /// `content` with every linkable issue ID turned into a Markdown link.
+    pub fn linkify(&self, content: &str) -> String {
+        self.pattern
+            .replace_all(content, |c: &regex::Captures| {
+                let id = &c[0];
+                match self.link(id) {
+                    Some(url) => format!("[{id}]({url})"),
+                    None => id.to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+static ISSUES: OnceLock<Issues> = OnceLock::new();
+
+/// Sets how issue references are recognised; call before any sessions are built.
+pub fn set(issues: Issues) {
+    let _ = ISSUES.set(issues);
+}
+
+pub fn get() -> &'static Issues {
+    ISSUES.get_or_init(Issues::default)
+}
+
+/// Hours per referenced issue. A session mentioning several issues is split
+/// evenly between them.
+pub fn issue_table(sessions: &[Session]) -> String {
+    let mut hours: BTreeMap<&str, (f64, usize)> = BTreeMap::new();
+    for s in sessions {
+        let share = s.minutes() as f64 / 60.0 / s.refs.len() as f64;
+        for r in &s.refs {
+            let (h, count) = hours.entry(r).or_default();
+            *h += share;
+            *count += 1;
+        }
+    }
+    let issues = get();
+    let mut out = String::new();
+    writeln!(out, "| issue | sessions | hours |").unwrap();
+    writeln!(out, "|-------|----------|-------|").unwrap();
+    for (id, (h, count)) in hours {
+        let id = match issues.link(id) {
+            Some(url) => format!("[{id}]({url})"),
+            None => id.to_string(),
+        };
+        writeln!(out, "| {id} | {count} | {} |", format_hm(h)).unwrap();
+    }
+    out
+}
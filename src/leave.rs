@@ -0,0 +1,219 @@
+//! Paid-leave (有給) ledger. Days are granted either by the anniversary rules
+//! in the config or by `type=leave_grant` events, and consumed by
+//! `type=leave` events, oldest grant first. Grants expire after
+//! `leave.expiry_years` (default 2).
+//!
+//! With `leave.hire_date` set, grants follow the statutory schedule: the
+//! first `leave.grants` entry after `leave.first_grant_months` (default 6),
+//! then one entry per year, repeating the last.
+
+use chrono::{Months, NaiveDate};
+use std::{fmt::Write, path::Path, path::PathBuf};
+
+use crate::{
+    close,
+    config::{Config, Value},
+    correct::apply_corrections,
+    event::{LogEvent, filter_user, read_events, record},
+    state,
+};
+
+const STATUTORY_GRANTS: [f64; 7] = [10.0, 11.0, 12.0, 14.0, 16.0, 18.0, 20.0];
+/// Grants expiring within this many days are warned about.
+const EXPIRY_WARNING_DAYS: i64 = 60;
+
+struct Grant {
+    date: NaiveDate,
+    days: f64,
+    expires: NaiveDate,
+    used: f64,
+}
+
+fn parse_date(d: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(d, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {d:?}"))
+}
+
+fn expiry_years(config: &Config) -> u32 {
+    config
+        .get("leave.expiry_years")
+        .and_then(|v| v.as_num())
+        .unwrap_or(2.0) as u32
+}
+
+/// Grants due under the anniversary rules up to `today`.
+fn rule_grants(config: &Config, today: NaiveDate) -> anyhow::Result<Vec<Grant>> {
+    let Some(hired) = config.str("leave.hire_date") else {
+        return Ok(Vec::new());
+    };
+    let hired = parse_date(hired)?;
+    let amounts: Vec<f64> = match config.get("leave.grants") {
+        Some(Value::List(items)) => items.iter().filter_map(|v| v.as_num()).collect(),
+        _ => STATUTORY_GRANTS.to_vec(),
+    };
+    let first = config
+        .get("leave.first_grant_months")
+        .and_then(|v| v.as_num())
+        .unwrap_or(6.0) as u32;
+    let years = expiry_years(config);
+    let mut grants = Vec::new();
+    for n in 0.. {
+        let Some(date) = hired.checked_add_months(Months::new(first + 12 * n)) else {
+            break;
+        };
+        if date > today {
+            break;
+        }
+        let days = amounts
+            .get(n as usize)
+            .or(amounts.last())
+            .copied()
+            .unwrap_or(0.0);
+        grants.push(Grant {
+            date,
+            days,
+            expires: date + Months::new(12 * years),
+            used: 0.0,
+        });
+    }
+    Ok(grants)
+}
+
+/// Every grant with the days used from it, and the days taken that no grant
+/// covered.
+fn ledger(
+    events: &[LogEvent],
+    config: &Config,
+    today: NaiveDate,
+) -> anyhow::Result<(Vec<Grant>, f64)> {
+    let events = apply_corrections(events.to_vec());
+    let years = expiry_years(config);
+    let mut grants = rule_grants(config, today)?;
+    let mut takes = Vec::new();
+    for e in &events {
+        let date = e.field("date").and_then(|d| parse_date(d).ok());
+        let days = e.field("days").and_then(|d| d.parse::<f64>().ok());
+        match (e.ty.as_str(), date) {
+            ("leave_grant", Some(date)) => grants.push(Grant {
+                date,
+                days: days.unwrap_or(0.0),
+                expires: e
+                    .field("expires")
+                    .and_then(|d| parse_date(d).ok())
+                    .unwrap_or(date + Months::new(12 * years)),
+                used: 0.0,
+            }),
+            ("leave", Some(date)) => takes.push((date, days.unwrap_or(1.0))),
+            _ => {}
+        }
+    }
+    grants.sort_by_key(|g| g.date);
+    takes.sort_by_key(|t| t.0);
+
+    let mut uncovered = 0.0;
+    for (date, mut days) in takes {
+        for g in grants
+            .iter_mut()
+            .filter(|g| g.date <= date && date < g.expires)
+        {
+            let n = days.min(g.days - g.used);
+            g.used += n;
+            days -= n;
+            if days <= 0.0 {
+                break;
+            }
+        }
+        uncovered += days;
+    }
+    Ok((grants, uncovered))
+}
+
+pub fn grant(
+    log: &Path,
+    user: Option<&str>,
+    days: f64,
+    date: Option<&str>,
+    expires: Option<&str>,
+) -> anyhow::Result<()> {
+    let today = state::now().format("%Y-%m-%d").to_string();
+    let date = date.unwrap_or(&today);
+    parse_date(date)?;
+    let days = days.to_string();
+    let mut extra = vec![("days", days.as_str()), ("date", date)];
+    if let Some(x) = expires {
+        parse_date(x)?;
+        extra.push(("expires", x));
+    }
+    record(Some(log), user, "leave_grant", &extra, None, false)?;
+    println!("Granted {days} days of paid leave on {date}.");
+    Ok(())
+}
+
+pub fn take(
+    log: &Path,
+    user: Option<&str>,
+    date: &str,
+    days: f64,
+    force: bool,
+) -> anyhow::Result<()> {
+    parse_date(date)?;
+    if days <= 0.0 {
+        anyhow::bail!("--days must be positive");
+    }
+    let days = days.to_string();
+    let mut extra = vec![("date", date), ("days", days.as_str())];
+    if date[..7] != state::now().format("%Y-%m").to_string() {
+        extra.extend(close::guard(log, user, date, force)?);
+    }
+    record(Some(log), user, "leave", &extra, None, force)?;
+    println!("Recorded {days} days of paid leave on {date}.");
+    Ok(())
+}
+
+pub fn balance(input: Option<PathBuf>, user: Option<&str>) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let config = Config::load()?;
+    let today = state::now().date_naive();
+    let (grants, uncovered) = ledger(&events, &config, today)?;
+
+    let mut out = String::new();
+    writeln!(out, "| granted | days | used | expires | remaining |").unwrap();
+    writeln!(out, "|---------|------|------|---------|-----------|").unwrap();
+    let mut remaining = 0.0;
+    let mut warnings = Vec::new();
+    for g in &grants {
+        let left = if g.expires <= today {
+            0.0
+        } else {
+            g.days - g.used
+        };
+        remaining += left;
+        let expires = if g.expires <= today {
+            format!("{} (expired)", g.expires)
+        } else {
+            g.expires.to_string()
+        };
+        writeln!(
+            out,
+            "| {} | {} | {} | {expires} | {left} |",
+            g.date, g.days, g.used
+        )
+        .unwrap();
+        let days_left = (g.expires - today).num_days();
+        if left > 0.0 && (0..=EXPIRY_WARNING_DAYS).contains(&days_left) {
+            warnings.push(format!(
+                "warning: {left} days granted on {} expire on {} (in {days_left} days)",
+                g.date, g.expires
+            ));
+        }
+    }
+    println!("{out}");
+    println!("Remaining: {remaining} days");
+    if uncovered > 0.0 {
+        println!("warning: {uncovered} days of leave taken were not covered by any grant");
+    }
+    for w in warnings {
+        println!("{w}");
+    }
+    Ok(())
+}
@@ -1,359 +1,1200 @@
-use chrono::{DateTime, FixedOffset, Local, NaiveTime};
-use clap::{Parser, Subcommand};
-use regex::Regex;
-use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
-    path::PathBuf,
-};
+mod alias;
+mod archive;
+mod audit;
+mod calendar;
+mod chain;
+mod close;
+mod columns;
+mod completions;
+mod compliance;
+mod config;
+mod console;
+mod correct;
+#[cfg(unix)]
+mod daemon;
+mod display;
+mod event;
+mod excel;
+mod expense;
+mod fix;
+mod flex;
+mod furikae;
+mod git;
+mod github;
+mod grpc;
+mod gsheets;
+mod history;
+mod holidays;
+mod hooks;
+mod hours;
+mod http;
+mod ics;
+mod input;
+mod invoice;
+mod issues;
+mod leave;
+mod mcp;
+#[cfg(unix)]
+mod metrics;
+mod migrate;
+mod ods;
+mod outlook;
+mod overtime;
+mod paths;
+mod payroll;
+mod plugin;
+mod privacy;
+mod query;
+mod report;
+mod schedule;
+mod schema;
+#[cfg(feature = "screenlock")]
+mod screenlock;
+mod script;
+mod search;
+#[cfg(unix)]
+mod service;
+mod session;
+mod shifts;
+mod sign;
+mod skew;
+mod state;
+mod stats;
+mod statusbar;
+mod tasks;
+mod text;
+mod time;
+#[cfg(feature = "tray")]
+mod tray;
+#[cfg(unix)]
+mod tui;
+mod units;
+mod validate;
+mod watch;
+#[cfg(unix)]
+mod wifi;
+mod yayoi;
 
-use umya_spreadsheet::{Spreadsheet, Worksheet, new_file, structs::Style, writer::xlsx::write};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::{ffi::OsString, io::IsTerminal, path::PathBuf};
+
+use event::{LogFormat, record};
+use excel::{Approval, ExcelOptions, export_excel};
+use report::summary_markdown;
+use session::SessionOptions;
 
 #[derive(Parser)]
 #[command(name = "attendance")]
 #[command(about = "kintai: Attendance Record Manager", long_about = None)]
 struct Cli {
+    /// Log file to append events to (events are printed to stdout if unset)
+    #[arg(short, long, global = true, env = "KINTAI_LOG")]
+    log: Option<PathBuf>,
+    /// Person the events belong to, for logs shared by several people.
+    /// Recorded as `user=` and used to filter reports
+    #[arg(short, long, global = true, env = "KINTAI_USER")]
+    user: Option<String>,
+    /// Day overnight sessions count toward (overrides `sessions.overnight`)
+    #[arg(long, global = true, value_enum)]
+    overnight: Option<session::Overnight>,
+    /// Also read the events moved out of the log by `kintai archive`
+    #[arg(long, global = true)]
+    include_archived: bool,
+    /// Reports read the events piped to stdin along with the log, merged and
+    /// without duplicates, e.g. to preview events not recorded yet
+    #[arg(long, global = true)]
+    stdin: bool,
+    /// Use this time as the current time instead of the system clock, for
+    /// scripts and tests: RFC 3339, "YYYY-MM-DD HH:MM" or HH:MM today
+    #[arg(long, global = true, env = "KINTAI_NOW", value_name = "TIME")]
+    now: Option<String>,
+    /// Keep the config, data and caches in this directory instead of the
+    /// usual places, e.g. for a portable install
+    #[arg(long, global = true, env = "KINTAI_HOME", value_name = "DIR")]
+    home: Option<PathBuf>,
     #[command(subcommand)]
     cmd: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Start,
+    /// Record the start of a session
+    #[command(visible_alias = "in")]
+    Start {
+        /// Where you work this session, e.g. office, remote or a client site
+        #[arg(long)]
+        location: Option<String>,
+        /// Project the session is for, checked against `[budget]`
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Record even into a closed month (tagged as a correction)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Record the end of a session
+    #[command(visible_alias = "out")]
     Finish {
         content: Option<String>,
+        /// Describe the session with the commits made in these repositories
+        /// (default: the current one) since it started
+        #[arg(long, value_name = "REPO", num_args = 0.., conflicts_with = "content")]
+        from_git: Option<Vec<PathBuf>>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Start a break
+    #[command(visible_alias = "brb")]
+    BreakStart {
+        /// Kind of break, e.g. lunch or rest, reported separately
+        #[arg(long)]
+        kind: Option<String>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// End the current break
+    #[command(visible_alias = "back")]
+    BreakEnd {
+        #[arg(long)]
+        force: bool,
+    },
+    /// Record an event of a custom type from `[intervals.<name>]`, e.g.
+    /// `kintai track oncall_start`
+    Track {
+        #[arg(value_name = "TYPE")]
+        ty: String,
+        content: Option<String>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Record work units done in the current session, e.g. `kintai count 3 "translated pages"`
+    Count {
+        count: String,
+        /// What was counted; paid at its rate under `[unit_rate]`
+        unit: String,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Record an expense, e.g. `kintai expense 1500 "taxi to client"`
+    Expense {
+        amount: String,
+        description: Option<String>,
+        /// Day the expense belongs to, as YYYY-MM-DD (default: today)
+        #[arg(long)]
+        date: Option<String>,
+        #[arg(long)]
+        force: bool,
     },
-    BreakStart,
-    BreakEnd,
     Summary {
         #[arg(short, long)]
         input: Option<PathBuf>,
         #[arg(short, long)]
         rate: Option<f64>,
+        /// Bill each session in blocks of this many minutes, rounded up
+        /// (default: `billing.increment`); the salary follows billed time
+        #[arg(long, value_name = "MINUTES")]
+        increment: Option<i64>,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Write a detached signature next to the report (needs --output)
+        #[arg(long, requires = "output")]
+        sign: bool,
+        /// Append a bar chart of hours per day (or per month)
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "day")]
+        chart: Option<report::ChartBy>,
+        /// Show the summary again whenever the log changes
+        #[arg(long, conflicts_with = "output")]
+        watch: bool,
     },
     Excel {
         #[arg(short, long)]
         input: Option<PathBuf>,
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Write a detached signature next to the workbook
+        #[arg(long)]
+        sign: bool,
+        /// Protect the sheet against edits, optionally with a password
+        #[arg(long, value_name = "PASSWORD", num_args = 0..=1, default_missing_value = "")]
+        protect: Option<String>,
+        /// Also encrypt the workbook with the --protect password
+        #[arg(long, requires = "protect")]
+        encrypt: bool,
+        /// Spreadsheet format (default: from the --output extension, else xlsx)
+        #[arg(long, value_enum)]
+        format: Option<SheetFormat>,
+        /// One row for every day of the month, marking days off with 休
+        #[arg(long)]
+        full_month: bool,
+        /// Append a sign-off block below the totals (default: `excel.approval`)
+        #[arg(long, value_enum)]
+        approval: Option<Approval>,
+        /// Write start, end and worked time as Excel time values
+        #[arg(long)]
+        native_times: bool,
+        /// Add a "Summary" sheet with the month's totals before the "Detail" sheet
+        #[arg(long)]
+        summary_sheet: bool,
+        /// Hourly rate for the salary on the summary sheet
+        #[arg(short, long)]
+        rate: Option<f64>,
+        /// Add a bar chart of hours per day below the table
+        #[arg(long)]
+        chart: bool,
+    },
+    /// Send the monthly table somewhere other than a local file
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Propose sessions from activity elsewhere, for days you forgot to track
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Pre-approve overtime on a day (all of it, or up to --hours)
+    ApproveOt {
+        /// Day, as YYYY-MM-DD
+        date: String,
+        #[arg(long)]
+        hours: Option<f64>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show overtime per day, split into approved and unapproved hours
+    Overtime {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Only this month, as YYYY-MM
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+    /// Show hours per task (session description or ticket ID)
+    Tasks {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// First day, as YYYY-MM-DD
+        #[arg(long)]
+        from: Option<String>,
+        /// Last day, as YYYY-MM-DD
+        #[arg(long)]
+        to: Option<String>,
+        /// Group by description or by ticket ID
+        #[arg(long, value_enum, default_value = "content")]
+        by: tasks::TaskBy,
+    },
+    /// Flag days whose sessions did not cover the flextime core hours
+    CoreHours {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Only this month, as YYYY-MM
+        #[arg(short, long)]
+        month: Option<String>,
+        /// Core window, e.g. 11:00-15:00 (default: flex.core from the config)
+        #[arg(long)]
+        core: Option<String>,
+    },
+    /// Track holidays worked and the compensatory days off (振替休日) for them
+    Furikae {
+        #[command(subcommand)]
+        cmd: FurikaeCommand,
+    },
+    /// Paid-leave (有給) grants, days taken and the remaining balance
+    Leave {
+        #[command(subcommand)]
+        cmd: LeaveCommand,
+    },
+    /// Planned shifts and how the actual sessions compare
+    Shifts {
+        #[command(subcommand)]
+        cmd: ShiftsCommand,
+    },
+    /// Write an invoice for a month of work for a client under `[clients.<name>]`
+    Invoice {
+        /// Client, as named in the config
+        client: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Month to bill, as YYYY-MM (default: this month)
+        #[arg(short, long)]
+        month: Option<String>,
+        /// Hourly rate (default: the client's `rate`)
+        #[arg(short, long)]
+        rate: Option<f64>,
+        /// Write the invoice to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare two months side by side, e.g. `kintai compare 2024-05 2024-06`
+    Compare {
+        /// First month, as YYYY-MM
+        from: String,
+        /// Second month, as YYYY-MM
+        to: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        #[arg(short, long)]
+        rate: Option<f64>,
+    },
+    /// Compare the same month across years (hours, days worked, average day)
+    Yoy {
+        /// Month number, 1-12 (default: this month)
+        month: Option<u32>,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Move events before a month into a compressed archive next to the log,
+    /// or list the archives
+    Archive {
+        /// First month to keep in the log, as YYYY-MM
+        #[arg(long)]
+        before: Option<String>,
+        /// Compression of the archive file (default: `archive.compression`, else gzip)
+        #[arg(long, value_enum)]
+        compress: Option<archive::ArchiveFormat>,
+        /// Archive months that are not closed yet
+        #[arg(long)]
+        force: bool,
+        /// Actually move the events (otherwise only reports what would move)
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Close a month: record who closed it and when, print its final report,
+    /// and refuse later events in it unless forced
+    Close {
+        /// Month to close, as YYYY-MM
+        month: String,
+        /// Close again even if the month is already closed
+        #[arg(long)]
+        force: bool,
+    },
+    /// Month-end report for payroll: regular, overtime, late-night and
+    /// holiday hours, leave taken, and pay lines
+    CloseReport {
+        /// Month, as YYYY-MM
+        month: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Hourly rate for the pay lines
+        #[arg(short, long)]
+        rate: Option<f64>,
+    },
+    /// Check a month against break, days-off, 36協定 overtime and rest-interval rules
+    Compliance {
+        /// Month, as YYYY-MM (defaults to this month)
+        #[arg(short, long)]
+        month: Option<String>,
+        /// Log to read (defaults to --log, then stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// List corrections, automatic events and other after-the-fact edits
+    Audit {
+        /// Log to read (defaults to --log, then stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Only events in this month, as YYYY-MM
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+    /// Check the log for problems, such as long stretches without a break
+    Validate {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Find the sessions whose content mentions some text
+    Search {
+        /// Text to look for (case-insensitive)
+        text: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Check the log's hash chain for edited, inserted or removed events
+    Verify,
+    /// Rewrite the log in another format, keeping a backup
+    Migrate {
+        /// Format to convert to
+        #[arg(long, value_enum, default_value = "jsonl")]
+        to: LogFormat,
+    },
+    /// Combine one log per team member into hours per person and month
+    TeamSummary {
+        /// Directory containing one log file per member
+        #[arg(short, long)]
+        dir: PathBuf,
+    },
+    /// List raw events with their IDs, for use with `amend` and `void`
+    Events {
+        /// Log to read (defaults to --log, then stdin)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Correct an earlier event by appending an amendment
+    Amend {
+        /// ID of the event to correct (see `kintai events`)
+        id: String,
+        /// New time: HH:MM on the event's day, "YYYY-MM-DD HH:MM" or RFC 3339
+        #[arg(long)]
+        ts: Option<String>,
+        /// New content
+        #[arg(long)]
+        content: Option<String>,
+        /// Correct an event in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Set or add to the content of a finished session
+    Annotate {
+        /// What was done
+        content: String,
+        /// Day of the session, as YYYY-MM-DD (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+        /// With several sessions that day, the one running at HH:MM (default: the last)
+        #[arg(long)]
+        at: Option<String>,
+        /// Add to the existing content instead of replacing it
+        #[arg(short, long)]
+        append: bool,
+        /// Correct a session in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Pick a recent session and correct its times, breaks or content
+    Fix {
+        /// How many recent sessions to offer
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// Correct a session in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Browse a month's days full-screen and correct their sessions
+    #[cfg(unix)]
+    Tui {
+        /// Month to open, as YYYY-MM (default: this month)
+        #[arg(short, long)]
+        month: Option<String>,
+        /// Correct sessions in closed months
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the operations that recorded events, for `undo` and `redo`
+    History,
+    /// Revert an operation from `kintai history` by voiding its events
+    Undo {
+        /// Operation number (default: the latest one not undone)
+        number: Option<usize>,
+        /// Revert events in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Restore what the latest `undo` reverted
+    Redo {
+        /// Restore events in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Cancel an earlier event (or amendment) by appending a void marker
+    Void {
+        /// ID of the event to cancel (see `kintai events`)
+        id: String,
+        /// Cancel an event in a closed month
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print a compact status segment for shell prompts and status bars
+    Prompt {
+        #[arg(long, default_value = "⏱")]
+        icon: String,
+    },
+    /// Print the state as JSON for a status bar (Waybar, i3blocks) or launcher
+    Statusbar {
+        #[arg(long, value_enum, default_value_t)]
+        style: statusbar::Style,
+        #[arg(long, default_value = "⏱")]
+        icon: String,
+    },
+    /// Print the time worked today, this week or this month as one figure
+    Hours {
+        #[arg(value_enum, default_value_t)]
+        period: hours::Period,
+    },
+    /// Show whether you are clocked in, on break, or clocked out
+    Status {
+        /// Show the status as a desktop notification instead of printing it
+        #[arg(long)]
+        notify: bool,
+        /// Print nothing; exit 0 when clocked in, 1 on a break, 2 when clocked out
+        #[arg(short, long, conflicts_with = "notify")]
+        quiet: bool,
+    },
+    /// Keep the tracking state in memory and serve it over a local socket
+    #[cfg(unix)]
+    Daemon {
+        /// Event to record when the daemon is stopped mid-session
+        #[arg(long, value_enum, default_value_t)]
+        on_shutdown: daemon::ShutdownAction,
+        /// Also serve Prometheus metrics at http://<addr>/metrics
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+    },
+    /// Hand over or delete personal data recorded in the log
+    Privacy {
+        #[command(subcommand)]
+        cmd: PrivacyCommand,
+    },
+    /// List the jobs the daemon runs from [schedule.<name>] config entries
+    Schedule,
+    /// Show the config file location and its values
+    Config,
+    /// Print a shell completion script, e.g. `kintai completions bash >
+    /// ~/.local/share/bash-completion/completions/kintai`
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// List the project or break-kind names used in the log and config
+    Names {
+        #[arg(value_enum, default_value = "projects")]
+        what: completions::Names,
+    },
+    /// Print the JSON Schema of `privacy export` output
+    Schema,
+    /// List the plugins (`kintai-<name>` executables in PATH) usable as commands
+    Plugins,
+    /// Run plugin `kintai-<name>` with the log's sessions as JSON on stdin
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
+    /// Print the `[columns]` computed columns per day and month
+    Columns {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        #[arg(short, long)]
+        rate: Option<f64>,
+        /// Write CSV instead of a Markdown table
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Run a Lua script over the sessions for a custom report
+    Report {
+        /// The script; it sees `sessions`, `months` and `arg`
+        #[arg(long)]
+        script: PathBuf,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Arguments passed to the script as `arg`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Run SQL over the `sessions` and `events` tables
+    Query {
+        /// e.g. "SELECT date, sum(hours) FROM sessions GROUP BY date"
+        sql: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
+    /// Serve events, sessions and summaries over gRPC (and gRPC-Web)
+    Grpc {
+        #[arg(long, default_value = "127.0.0.1:50051")]
+        listen: String,
+    },
+    /// Serve the log to AI assistants over the Model Context Protocol (stdio)
+    Mcp,
+    /// Generate systemd user units for the daemon and reminders
+    #[cfg(unix)]
+    InstallService {
+        #[arg(long, value_enum, default_value_t = daemon::ShutdownAction::Break)]
+        on_shutdown: daemon::ShutdownAction,
+        /// systemd calendar expression for a status reminder (repeatable),
+        /// e.g. "Mon..Fri 18:30"
+        #[arg(long)]
+        remind: Vec<String>,
+        /// Print the units instead of writing them
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
-struct LogEvent {
-    ts: String,
-    ty: String,
-    content: Option<String>,
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SheetFormat {
+    Xlsx,
+    Ods,
 }
 
-struct ActiveSession {
-    start: DateTime<FixedOffset>,
-    breaks: Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>)>,
-    last_break_start: Option<DateTime<FixedOffset>>,
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Write the first month in the log into a Google Sheet (Sheets API)
+    Gsheets {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// ID from the spreadsheet's URL (default: gsheets.spreadsheet_id)
+        #[arg(long)]
+        spreadsheet_id: Option<String>,
+        /// Tab to overwrite
+        #[arg(long, default_value = "Sheet1")]
+        sheet: String,
+    },
+    /// Write a month's attendance as CSV for import into 弥生給与
+    Yayoi {
+        /// Month, as YYYY-MM
+        month: String,
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// File to write (default: yayoi-YYYY-MM.csv)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
-struct Session {
-    date: String,
-    time_range: String,
-    content: Option<String>,
+#[derive(Subcommand)]
+enum ImportSource {
+    /// Your GitHub events (pushes, pull requests, reviews) of the last 90 days
+    Github {
+        /// First day, as YYYY-MM-DD
+        #[arg(long)]
+        from: String,
+        /// Last day, as YYYY-MM-DD
+        #[arg(long)]
+        to: String,
+        /// Record the proposed sessions (otherwise only show them)
+        #[arg(long)]
+        apply: bool,
+        /// Record even into a closed month (tagged as a correction)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Meetings you accepted in your Outlook/Exchange calendar (Microsoft Graph)
+    Outlook {
+        /// Day to import, as YYYY-MM-DD (default: today)
+        #[arg(long)]
+        date: Option<String>,
+        /// Project of the sessions (default: outlook.tag)
+        #[arg(long)]
+        tag: Option<String>,
+        /// Record the proposed sessions (otherwise only show them)
+        #[arg(long)]
+        apply: bool,
+        /// Record even into a closed month (tagged as a correction)
+        #[arg(long)]
+        force: bool,
+    },
+    /// Events of an iCalendar (.ics) file, e.g. meetings
+    Ics {
+        file: PathBuf,
+        /// Import events as sessions of their own, or as breaks in logged sessions
+        #[arg(long = "as", value_enum, default_value = "session")]
+        import_as: ics::ImportAs,
+        /// Project of the sessions, or kind of the breaks (default: meeting)
+        #[arg(long)]
+        tag: Option<String>,
+        /// First day, as YYYY-MM-DD
+        #[arg(long)]
+        from: Option<String>,
+        /// Last day, as YYYY-MM-DD
+        #[arg(long)]
+        to: Option<String>,
+        /// Record the proposed events (otherwise only show them)
+        #[arg(long)]
+        apply: bool,
+        /// Record even into a closed month (tagged as a correction)
+        #[arg(long)]
+        force: bool,
+    },
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    match cli.cmd {
-        Commands::Start => record_event("start", None)?,
-        Commands::Finish { content } => record_event("finish", content)?,
-        Commands::BreakStart => record_event("break_start", None)?,
-        Commands::BreakEnd => record_event("break_end", None)?,
-        Commands::Summary { input, rate } => {
-            export_markdown(input.clone())?;
-            summary_markdown(input, rate)?
-        }
-        Commands::Excel { input, output } => export_excel(input, output)?,
-    }
-    Ok(())
+#[derive(Subcommand)]
+enum FurikaeCommand {
+    /// Record that you worked on a holiday
+    Work {
+        /// The holiday, as YYYY-MM-DD
+        date: String,
+        /// Rest day it is swapped for, if already known
+        #[arg(long)]
+        rest: Option<String>,
+    },
+    /// Swap a worked holiday for a rest day
+    Swap {
+        /// The worked holiday, as YYYY-MM-DD
+        date: String,
+        /// The day off instead, as YYYY-MM-DD
+        rest: String,
+    },
+    /// List worked holidays and the days off still owed
+    Balance {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
 }
 
-fn record_event(event_type: &str, content: Option<String>) -> anyhow::Result<()> {
-    let ts = Local::now().to_rfc3339();
-    let mut line = format!("ts={ts} type={event_type}");
-    if let Some(c) = content {
-        let esc = c.replace('"', "\\\"");
-        line.push_str(&format!(" content=\"{esc}\""));
-    }
-    println!("{line}");
-    Ok(())
+#[derive(Subcommand)]
+enum LeaveCommand {
+    /// Record a grant of paid-leave days outside the configured rules
+    Grant {
+        days: f64,
+        /// Day of the grant, as YYYY-MM-DD (default: today)
+        #[arg(long)]
+        date: Option<String>,
+        /// Expiry date (default: leave.expiry_years after the grant)
+        #[arg(long)]
+        expires: Option<String>,
+    },
+    /// Record a day of paid leave
+    Take {
+        /// Day off, as YYYY-MM-DD
+        date: String,
+        /// Days used, e.g. 0.5 for a half day
+        #[arg(long, default_value_t = 1.0)]
+        days: f64,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show grants, days used and the remaining balance
+    Balance {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+    },
 }
 
-fn build_sessions(mut events: Vec<LogEvent>) -> Vec<Session> {
-    events.sort_by_key(|e| e.ts.clone());
-    let mut sessions = Vec::new();
-    let mut active: Option<ActiveSession> = None;
+#[derive(Subcommand)]
+enum ShiftsCommand {
+    /// Plan (or with `off`, cancel) the shift on one day
+    Add {
+        /// Day, as YYYY-MM-DD
+        date: String,
+        /// HH:MM-HH:MM, or `off`
+        plan: String,
+    },
+    /// Show planned vs actual start and finish, and missed shifts
+    Diff {
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        /// Month to compare, as YYYY-MM (default: this month)
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+}
 
-    for e in events {
-        let dt = DateTime::parse_from_rfc3339(&e.ts).unwrap();
-        match e.ty.as_str() {
-            "start" => {
-                active = Some(ActiveSession {
-                    start: dt,
-                    breaks: Vec::new(),
-                    last_break_start: None,
-                });
+#[derive(Subcommand)]
+enum PrivacyCommand {
+    /// Dump every event and session as JSON
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Permanently delete events dated before a day, leaving a tombstone
+    Purge {
+        /// First day to keep, as YYYY-MM-DD
+        #[arg(long)]
+        before: String,
+        /// Actually delete (otherwise only report what would be deleted)
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    console::init();
+    let args: Vec<OsString> = std::env::args_os().collect();
+    if let Some(home) = paths::home_arg(&args) {
+        paths::set_home(home);
+    }
+    let config = config::Config::load()?;
+    let args = alias::expand(args, &config, &Cli::command());
+    let cli = Cli::parse_from(args);
+    if let Some(home) = cli.home.clone() {
+        paths::set_home(home);
+    }
+    if let Some(now) = &cli.now {
+        let now = time::parse_when(now, chrono::Local::now().fixed_offset())
+            .map_err(|e| anyhow::anyhow!("--now: {e}"))?;
+        time::set_clock(Box::new(time::FixedClock(now)));
+    }
+    let log = cli.log.as_deref();
+    let user = cli.user.as_deref();
+    let mut session_options = SessionOptions::from_config(&config)?;
+    if let Some(overnight) = cli.overnight {
+        session_options.overnight = overnight;
+    }
+    session::set_options(session_options);
+    archive::set_include(cli.include_archived);
+    input::set_stdin(cli.stdin);
+    issues::set(issues::Issues::from_config(&config)?);
+    display::set_hours(display::Hours::from_config(&config)?);
+    match cli.cmd {
+        Commands::Start {
+            location,
+            project,
+            force,
+        } => {
+            // Without --location, the standard pattern's place for today.
+            let calendar = calendar::Calendar::load(&config)?;
+            let location = location
+                .as_deref()
+                .or_else(|| calendar.location(state::now().date_naive()));
+            let mut extra: Vec<_> = location.map(|l| ("location", l)).into_iter().collect();
+            extra.extend(project.as_deref().map(|p| ("project", p)));
+            record(log, user, "start", &extra, None, force)?
+        }
+        Commands::Finish {
+            content,
+            from_git,
+            force,
+        } => {
+            let content = match from_git {
+                Some(repos) => git::finish_content(log, user, &repos)?,
+                None => content,
+            };
+            record(log, user, "finish", &[], content, force)?
+        }
+        Commands::BreakStart { kind, force } => {
+            let extra: Vec<_> = kind.as_deref().map(|k| ("kind", k)).into_iter().collect();
+            record(log, user, "break_start", &extra, None, force)?
+        }
+        Commands::BreakEnd { force } => record(log, user, "break_end", &[], None, force)?,
+        Commands::Track { ty, content, force } => {
+            if session::interval_type(&ty).is_none() {
+                anyhow::bail!(
+                    "{ty:?} is not the start or end of any [intervals.<name>] in the config"
+                );
             }
-            "break_start" => {
-                if let Some(a) = active.as_mut() {
-                    a.last_break_start = Some(dt);
-                }
+            record(log, user, &ty, &[], content, force)?
+        }
+        Commands::Count { count, unit, force } => {
+            units::record_count(log, user, &count, unit, force)?
+        }
+        Commands::Expense {
+            amount,
+            description,
+            date,
+            force,
+        } => expense::record_expense(log, user, &amount, description, date.as_deref(), force)?,
+        Commands::Summary {
+            input,
+            rate,
+            increment,
+            output,
+            sign,
+            chart,
+            watch,
+        } => {
+            let color = output.is_none() && std::io::stdout().is_terminal();
+            let input = input.or(log.map(PathBuf::from));
+            if watch {
+                let path = input.ok_or_else(|| {
+                    anyhow::anyhow!("--watch needs a log to watch: --input, --log or KINTAI_LOG")
+                })?;
+                return watch::watch(&path, || {
+                    summary_markdown(Some(path.clone()), rate, increment, user, color, chart)
+                });
             }
-            "break_end" => {
-                if let Some(a) = active.as_mut() {
-                    if let Some(bs) = a.last_break_start.take() {
-                        a.breaks.push((bs, dt));
+            let report = summary_markdown(input, rate, increment, user, color, chart)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, report)?;
+                    println!("Wrote {}", path.display());
+                    if sign {
+                        sign::sign(&path)?;
                     }
                 }
+                None => print!("{report}"),
             }
-            "finish" => {
-                if let Some(a) = active.take() {
-                    let finish = dt;
-                    let mut intervals = Vec::new();
-                    let mut cursor = a.start;
-                    for (bs, be) in &a.breaks {
-                        intervals.push((cursor, *bs));
-                        cursor = *be;
+        }
+        Commands::Excel {
+            input,
+            output,
+            sign,
+            protect,
+            encrypt,
+            format,
+            full_month,
+            approval,
+            native_times,
+            summary_sheet,
+            rate,
+            chart,
+        } => {
+            let format =
+                format.unwrap_or_else(|| match output.as_ref().and_then(|o| o.extension()) {
+                    Some(ext) if ext.eq_ignore_ascii_case("ods") => SheetFormat::Ods,
+                    _ => SheetFormat::Xlsx,
+                });
+            let input = input.or(log.map(PathBuf::from));
+            let written = match format {
+                SheetFormat::Xlsx => export_excel(
+                    input,
+                    output,
+                    user,
+                    &ExcelOptions {
+                        protect,
+                        encrypt,
+                        full_month,
+                        approval,
+                        native_times,
+                        summary_sheet,
+                        rate,
+                        chart,
+                    },
+                )?,
+                SheetFormat::Ods => {
+                    if protect.is_some() {
+                        anyhow::bail!("--protect and --encrypt are only supported for xlsx");
                     }
-                    intervals.push((cursor, finish));
-                    let parts: Vec<String> = intervals
-                        .into_iter()
-                        .map(|(s, e)| format!("{}~{}", s.format("%H:%M"), e.format("%H:%M")))
-                        .collect();
-                    sessions.push(Session {
-                        date: a.start.format("%Y/%m/%d").to_string(),
-                        time_range: parts.join(","),
-                        content: e.content,
-                    });
+                    ods::export_ods(input, output, user, full_month)?
                 }
+            };
+            if let Some(path) = written
+                && sign
+            {
+                sign::sign(&path)?;
             }
-            _ => {}
         }
-    }
-    sessions
-}
-
-fn export_markdown(input: Option<PathBuf>) -> anyhow::Result<()> {
-    let events = read_events(input)?;
-    let sessions = build_sessions(events);
-    println!("| date | time | content |");
-    println!("|------|------|---------|");
-    for s in sessions {
-        println!(
-            "| {} | {} | {} |",
-            s.date,
-            s.time_range,
-            s.content.unwrap_or_default()
-        );
-    }
-    println!();
-    Ok(())
-}
-
-fn summary_markdown(input: Option<PathBuf>, rate: Option<f64>) -> anyhow::Result<()> {
-    let events = read_events(input)?;
-    let sessions = build_sessions(events);
-    use std::collections::BTreeMap;
-    let mut monthly: BTreeMap<String, f64> = BTreeMap::new();
-    for s in &sessions {
-        let month = &s.date[..7];
-        let mut total = 0f64;
-        for part in s.time_range.split(',') {
-            let times: Vec<&str> = part.split('~').collect();
-            if let [start, end] = &times[..] {
-                let st = NaiveTime::parse_from_str(start, "%H:%M").unwrap();
-                let en = NaiveTime::parse_from_str(end, "%H:%M").unwrap();
-                total += (en - st).num_minutes() as f64 / 60.0;
+        Commands::Export { target } => match target {
+            ExportTarget::Gsheets {
+                input,
+                spreadsheet_id,
+                sheet,
+            } => gsheets::upload(
+                input.or(log.map(PathBuf::from)),
+                user,
+                spreadsheet_id,
+                &sheet,
+            )?,
+            ExportTarget::Yayoi {
+                month,
+                input,
+                output,
+            } => yayoi::export(input.or(log.map(PathBuf::from)), user, &month, output)?,
+        },
+        Commands::Import { source } => match source {
+            ImportSource::Github {
+                from,
+                to,
+                apply,
+                force,
+            } => github::import(require_log(log)?, user, &from, &to, apply, force)?,
+            ImportSource::Outlook {
+                date,
+                tag,
+                apply,
+                force,
+            } => outlook::import(require_log(log)?, user, date.as_deref(), tag, apply, force)?,
+            ImportSource::Ics {
+                file,
+                import_as,
+                tag,
+                from,
+                to,
+                apply,
+                force,
+            } => ics::import(
+                require_log(log)?,
+                user,
+                &file,
+                &ics::IcsOptions {
+                    import_as,
+                    tag,
+                    from,
+                    to,
+                    apply,
+                    force,
+                },
+            )?,
+        },
+        Commands::ApproveOt { date, hours, force } => {
+            overtime::approve(require_log(log)?, user, &date, hours, force)?
+        }
+        Commands::Overtime { input, month } => {
+            overtime::report(input.or(log.map(PathBuf::from)), user, month.as_deref())?
+        }
+        Commands::Tasks {
+            input,
+            from,
+            to,
+            by,
+        } => tasks::report(
+            input.or(log.map(PathBuf::from)),
+            user,
+            from.as_deref(),
+            to.as_deref(),
+            by,
+        )?,
+        Commands::CoreHours { input, month, core } => flex::check(
+            input.or(log.map(PathBuf::from)),
+            user,
+            month.as_deref(),
+            core.as_deref(),
+        )?,
+        Commands::Furikae { cmd } => match cmd {
+            FurikaeCommand::Work { date, rest } => {
+                furikae::work(require_log(log)?, user, &date, rest.as_deref())?
+            }
+            FurikaeCommand::Swap { date, rest } => {
+                furikae::swap(require_log(log)?, user, &date, &rest)?
+            }
+            FurikaeCommand::Balance { input } => {
+                furikae::balance(input.or(log.map(PathBuf::from)), user)?
+            }
+        },
+        Commands::Leave { cmd } => match cmd {
+            LeaveCommand::Grant {
+                days,
+                date,
+                expires,
+            } => leave::grant(
+                require_log(log)?,
+                user,
+                days,
+                date.as_deref(),
+                expires.as_deref(),
+            )?,
+            LeaveCommand::Take { date, days, force } => {
+                leave::take(require_log(log)?, user, &date, days, force)?
             }
+            LeaveCommand::Balance { input } => {
+                leave::balance(input.or(log.map(PathBuf::from)), user)?
+            }
+        },
+        Commands::Shifts { cmd } => match cmd {
+            ShiftsCommand::Add { date, plan } => {
+                shifts::add(require_log(log)?, user, &date, &plan)?
+            }
+            ShiftsCommand::Diff { input, month } => {
+                shifts::diff(input.or(log.map(PathBuf::from)), user, month.as_deref())?
+            }
+        },
+        Commands::Close { month, force } => close::close(require_log(log)?, user, &month, force)?,
+        Commands::CloseReport { month, input, rate } => {
+            payroll::close_report(input.or(log.map(PathBuf::from)), user, &month, rate)?
         }
-        *monthly.entry(month.to_string()).or_default() += total;
-    }
-    let rate = rate.unwrap_or(0.0);
-    println!("| month | hours | salary |");
-    println!("|-------|-------|--------|");
-    for (m, h) in monthly {
-        let hours_i = h.floor() as u64;
-        let mins = ((h - hours_i as f64) * 60.0).round() as u64;
-        let dec_str = format!("{h:.2}h");
-        let hours_str = format!("{hours_i}h{mins:02}m");
-        let salary = (h * rate).round() as u64;
-        println!("| {m} | {hours_str} ({dec_str}) | {salary} |");
-    }
-    println!();
-    Ok(())
-}
-
-fn read_events(input: Option<PathBuf>) -> anyhow::Result<Vec<LogEvent>> {
-    let reader: Box<dyn BufRead> = if let Some(path) = input {
-        Box::new(BufReader::new(File::open(path)?))
-    } else {
-        Box::new(BufReader::new(io::stdin()))
-    };
-
-    let re =
-        Regex::new(r#"ts=(?P<ts>[^ ]+) type=(?P<ty>[^ ]+)(?: content="(?P<ct>.*)")?"#).unwrap();
-    let mut events: Vec<LogEvent> = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(caps) = re.captures(&line) {
-            events.push(LogEvent {
-                ts: caps["ts"].to_string(),
-                ty: caps["ty"].to_string(),
-                content: caps.name("ct").map(|m| m.as_str().to_string()),
-            });
+        Commands::Compliance { month, input } => {
+            compliance::report(input.or(log.map(PathBuf::from)), user, month.as_deref())?
         }
-    }
-    Ok(events)
-}
-
-fn export_excel(input: Option<PathBuf>, output: Option<PathBuf>) -> anyhow::Result<()> {
-    let events = read_events(input)?;
-    let sessions = build_sessions(events);
-
-    if sessions.is_empty() {
-        println!("Log data is empty. Skipping Excel output.");
-        return Ok(());
-    }
-
-    let first_date = &sessions[0].date;
-    let first_ym = &first_date[..7];
-    let parts: Vec<&str> = first_ym.split('/').collect();
-    let year = parts[0];
-    let month = parts[1];
-    let title_text = format!("{year}年{}月の勤務時間記録", month.trim_start_matches('0'));
-
-    let filtered: Vec<&Session> = sessions
-        .iter()
-        .filter(|s| s.date.starts_with(first_ym))
-        .collect();
-
-    let mut rows: Vec<(String, String, String)> = Vec::new();
-    let mut total_minutes: i64 = 0;
-
-    for s in &filtered {
-        let parts: Vec<&str> = s.date.split('/').collect();
-        let mm: &str = parts[1];
-        let dd: &str = parts[2];
-        let month_jp = format!(
-            "{}月{}日",
-            mm.trim_start_matches('0'),
-            dd.trim_start_matches('0')
-        );
-
-        let time_str = s.time_range.clone();
-        let content_str = s.content.clone().unwrap_or_default();
-
-        for segment in s.time_range.split(',') {
-            let times: Vec<&str> = segment.split('~').collect();
-            if let [start, end] = &times[..] {
-                let st = NaiveTime::parse_from_str(start, "%H:%M").unwrap();
-                let en = NaiveTime::parse_from_str(end, "%H:%M").unwrap();
-                total_minutes += (en - st).num_minutes();
+        Commands::Audit { input, month } => {
+            audit::audit(input.or(log.map(PathBuf::from)), user, month.as_deref())?
+        }
+        Commands::Validate { input } => validate::validate(input.or(log.map(PathBuf::from)), user)?,
+        Commands::Search { text, input } => {
+            search::search(input.or(log.map(PathBuf::from)), user, &text)?
+        }
+        Commands::Verify => chain::verify(require_log(log)?)?,
+        Commands::Migrate { to } => migrate::migrate(require_log(log)?, to)?,
+        Commands::TeamSummary { dir } => report::team_summary(&dir)?,
+        Commands::Events { input } => report::list_events(input.or(log.map(PathBuf::from)), user)?,
+        Commands::Amend {
+            id,
+            ts,
+            content,
+            force,
+        } => correct::amend(require_log(log)?, user, &id, ts.as_deref(), content, force)?,
+        Commands::Annotate {
+            content,
+            date,
+            at,
+            append,
+            force,
+        } => correct::annotate(
+            require_log(log)?,
+            user,
+            date.as_deref(),
+            at.as_deref(),
+            content,
+            append,
+            force,
+        )?,
+        Commands::Fix { limit, force } => fix::fix(require_log(log)?, user, limit, force)?,
+        #[cfg(unix)]
+        Commands::Tui { month, force } => {
+            tui::run(require_log(log)?, user, month.as_deref(), force)?
+        }
+        Commands::History => history::show(require_log(log)?)?,
+        Commands::Undo { number, force } => history::undo(require_log(log)?, user, number, force)?,
+        Commands::Redo { force } => history::redo(require_log(log)?, user, force)?,
+        Commands::Void { id, force } => correct::void(require_log(log)?, user, &id, force)?,
+        Commands::Prompt { icon } => state::prompt(require_log(log)?, user, &icon)?,
+        Commands::Statusbar { style, icon } => {
+            statusbar::print(require_log(log)?, user, style, &icon)?
+        }
+        Commands::Hours { period } => hours::print(require_log(log)?, user, period)?,
+        Commands::Status { notify, quiet } => {
+            state::status(require_log(log)?, user, notify, quiet)?
+        }
+        #[cfg(unix)]
+        Commands::Daemon { on_shutdown, http } => {
+            daemon::run(require_log(log)?, user, on_shutdown, http.as_deref())?
+        }
+        Commands::Privacy { cmd } => match cmd {
+            PrivacyCommand::Export { output } => privacy::export(require_log(log)?, user, output)?,
+            PrivacyCommand::Purge { before, yes } => {
+                privacy::purge(require_log(log)?, user, &before, yes)?
             }
+        },
+        Commands::Schedule => schedule::show()?,
+        Commands::Config => config::show()?,
+        Commands::Completions { shell } => completions::print(shell, &Cli::command()),
+        Commands::Names { what } => completions::print_names(log, what)?,
+        Commands::Schema => schema::print(),
+        Commands::Plugins => plugin::list(),
+        Commands::External(args) => plugin::run(log, user, args)?,
+        Commands::Invoice {
+            client,
+            input,
+            month,
+            rate,
+            output,
+        } => invoice::invoice(
+            input.or(log.map(PathBuf::from)),
+            user,
+            &client,
+            month,
+            rate,
+            output.as_deref(),
+        )?,
+        Commands::Compare {
+            from,
+            to,
+            input,
+            rate,
+        } => stats::compare(input.or(log.map(PathBuf::from)), user, &from, &to, rate)?,
+        Commands::Yoy { month, input } => {
+            stats::year_over_year(input.or(log.map(PathBuf::from)), user, month)?
         }
-
-        rows.push((month_jp, time_str, content_str));
-    }
-
-    let hours = total_minutes / 60;
-    let minutes = total_minutes % 60;
-    let total_label = format!("{hours}時間{minutes}分");
-
-    let mut max_b_len: usize = 0;
-    for (_date, time_str, _content) in &rows {
-        let len = time_str.chars().count(); // char 単位でカウント
-        if len > max_b_len {
-            max_b_len = len;
+        Commands::Archive {
+            before,
+            compress,
+            force,
+            yes,
+        } => {
+            let log = require_log(log)?;
+            match before {
+                Some(before) => archive::archive(log, &before, compress, force, yes)?,
+                None => archive::list(log)?,
+            }
         }
-    }
-    let header_b_len = "勤務時間".chars().count();
-    if header_b_len > max_b_len {
-        max_b_len = header_b_len;
-    }
-    let mut book: Spreadsheet = new_file();
-
-    let sheet_name = "Sheet1";
-    let sheet: &mut Worksheet = book.get_sheet_by_name_mut(sheet_name).unwrap();
-
-    let style = Style::default();
-
-    let col_b = sheet.get_column_dimension_mut("B");
-    col_b.set_width(max_b_len as f64);
-
-    fn col_to_letter(mut col: u32) -> String {
-        let mut s = String::new();
-        while col > 0 {
-            let rem = ((col - 1) % 26) as u8;
-            s.push((b'A' + rem) as char);
-            col = (col - 1) / 26;
+        Commands::Columns { input, rate, csv } => {
+            columns::print(input.or(log.map(PathBuf::from)), user, rate, csv)?
         }
-        s.chars().rev().collect()
-    }
-
-    fn coord(col: u32, row: u32) -> String {
-        format!("{}{}", col_to_letter(col), row)
-    }
-
-    {
-        let cell = coord(1, 1);
-        let c = sheet.get_cell_mut(cell.clone());
-        c.set_value(title_text.clone());
-        c.set_style(style.clone());
-    }
-
-    {
-        let headers = ["日付", "勤務時間", "作業内容"];
-        for (i, &h) in headers.iter().enumerate() {
-            let cell = coord((i as u32) + 1, 3);
-            let c = sheet.get_cell_mut(cell.clone());
-            c.set_value(h.to_string());
-            c.set_style(style.clone());
+        Commands::Report {
+            script: path,
+            input,
+            args,
+        } => script::run(input.or(log.map(PathBuf::from)), user, &path, &args)?,
+        Commands::Query { sql, input } => {
+            query::query(input.or(log.map(PathBuf::from)), user, &sql)?
         }
+        Commands::Grpc { listen } => grpc::serve(require_log(log)?, user, &listen)?,
+        Commands::Mcp => mcp::serve(require_log(log)?, user)?,
+        #[cfg(unix)]
+        Commands::InstallService {
+            on_shutdown,
+            remind,
+            dry_run,
+        } => service::install(require_log(log)?, user, on_shutdown, &remind, dry_run)?,
     }
-
-    for (i, (date_jp, time_str, content_str)) in rows.iter().enumerate() {
-        let excel_row = 4 + i as u32;
-        let cell_date = coord(1, excel_row);
-        sheet
-            .get_cell_mut(cell_date.clone())
-            .set_value(date_jp.clone());
-        let cell_time = coord(2, excel_row);
-        sheet
-            .get_cell_mut(cell_time.clone())
-            .set_value(time_str.clone());
-        let cell_content = coord(3, excel_row);
-        sheet
-            .get_cell_mut(cell_content.clone())
-            .set_value(content_str.clone());
-    }
-
-    let data_end_row = 3 + rows.len() as u32;
-    let label_row = data_end_row + 2;
-    let value_row = data_end_row + 3;
-
-    {
-        let cell_label = coord(1, label_row);
-        sheet
-            .get_cell_mut(cell_label.clone())
-            .set_value("勤務時間の合計".to_string());
-    }
-
-    {
-        let cell_total = coord(1, value_row);
-        sheet
-            .get_cell_mut(cell_total.clone())
-            .set_value(total_label.clone());
-    }
-
-    let out_path = output.unwrap_or_else(|| PathBuf::from(format!("{year}_{month}_勤務時間.xlsx")));
-    let path_str = out_path.as_os_str().to_string_lossy();
-    write(&book, path_str.as_ref())?;
-
-    println!("Generated Excel file: {}", out_path.display());
     Ok(())
 }
+
+fn require_log(log: Option<&std::path::Path>) -> anyhow::Result<&std::path::Path> {
+    log.ok_or_else(|| anyhow::anyhow!("this command needs --log or KINTAI_LOG"))
+}
@@ -0,0 +1,148 @@
+//! Model Context Protocol server over stdio (newline-delimited JSON-RPC 2.0),
+//! letting AI assistants record events and read status and summaries.
+
+use serde_json::{Value, json};
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    event::{filter_user, read_log, record},
+    history,
+    report::{format_hours, monthly_hours},
+    session::build_sessions,
+    state,
+};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "record_event",
+            "description": "Record a clock event (start, finish, break_start or break_end) at the current time.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string", "enum": ["start", "finish", "break_start", "break_end"] },
+                    "content": { "type": "string", "description": "Work description, only used with finish" }
+                },
+                "required": ["type"]
+            }
+        },
+        {
+            "name": "get_status",
+            "description": "Whether the user is clocked in, on break or clocked out, and the time worked in the current session.",
+            "inputSchema": { "type": "object", "properties": {} }
+        },
+        {
+            "name": "get_summary",
+            "description": "Worked hours (and salary if a rate is given) per month.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "month": { "type": "string", "description": "Restrict to one month, as YYYY-MM" },
+                    "rate": { "type": "number", "description": "Hourly rate for the salary estimate" }
+                }
+            }
+        }
+    ])
+}
+
+fn call_tool(log: &Path, user: Option<&str>, name: &str, args: &Value) -> anyhow::Result<String> {
+    match name {
+        "record_event" => {
+            let ty = args["type"].as_str().unwrap_or_default();
+            if !matches!(ty, "start" | "finish" | "break_start" | "break_end") {
+                anyhow::bail!("unknown event type: {ty:?}");
+            }
+            let content = args["content"].as_str().map(str::to_string);
+            history::begin(format!("mcp: {ty}"));
+            record(Some(log), user, ty, &[], content, false)?;
+            Ok(format!(
+                "Recorded {ty}. Now {}.",
+                state::load(log, user)?.describe(state::now())
+            ))
+        }
+        "get_status" => Ok(state::load(log, user)?.describe(state::now())),
+        "get_summary" => {
+            let events = filter_user(read_log(log)?, user);
+            let month = args["month"].as_str().map(|m| m.replace('-', "/"));
+            let rate = args["rate"].as_f64();
+            let mut lines = Vec::new();
+            for (m, h) in monthly_hours(&build_sessions(events)) {
+                if month.as_ref().is_some_and(|want| *want != m) {
+                    continue;
+                }
+                let mut line = format!("{m}: {}", format_hours(h));
+                if let Some(r) = rate {
+                    line.push_str(&format!(", salary {}", (h * r).round() as u64));
+                }
+                lines.push(line);
+            }
+            if lines.is_empty() {
+                lines.push("No finished sessions recorded.".to_string());
+            }
+            Ok(lines.join("\n"))
+        }
+        _ => anyhow::bail!("unknown tool: {name}"),
+    }
+}
+
+/// Handles one request; returns `None` for notifications.
+fn handle(log: &Path, user: Option<&str>, msg: &Value) -> Option<Value> {
+    let id = msg.get("id")?.clone();
+    let params = &msg["params"];
+    let result = match msg["method"].as_str().unwrap_or_default() {
+        "initialize" => json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "kintai", "version": env!("CARGO_PKG_VERSION") }
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tools() }),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            match call_tool(log, user, name, &params["arguments"]) {
+                Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                Err(e) => json!({
+                    "content": [{ "type": "text", "text": e.to_string() }],
+                    "isError": true
+                }),
+            }
+        }
+        method => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("method not found: {method}") }
+            }));
+        }
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+pub fn serve(log: &Path, user: Option<&str>) -> anyhow::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(msg) => handle(log, user, &msg),
+            Err(e) => Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": e.to_string() }
+            })),
+        };
+        if let Some(reply) = reply {
+            writeln!(stdout, "{reply}")?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
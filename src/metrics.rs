@@ -0,0 +1,73 @@
+//! Prometheus text exposition of the current tracking state.
+
+use chrono::{DateTime, FixedOffset};
+use std::fmt::Write;
+
+use crate::{
+    session::Session,
+    state::{Phase, TrackingState},
+};
+
+/// Worked hours in finished sessions whose date starts with `prefix`, plus the
+/// running session if it started in the same period.
+fn hours_in(
+    st: &TrackingState,
+    sessions: &[Session],
+    now: DateTime<FixedOffset>,
+    fmt: &str,
+) -> f64 {
+    let prefix = now.format(fmt).to_string();
+    let mut secs: i64 = sessions
+        .iter()
+        .filter(|s| s.date.starts_with(&prefix))
+        .map(|s| s.minutes() * 60)
+        .sum();
+    if st
+        .session_start
+        .is_some_and(|s| s.format(fmt).to_string() == prefix)
+    {
+        secs += st.worked_secs(now);
+    }
+    secs as f64 / 3600.0
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} gauge").unwrap();
+    writeln!(out, "{name} {value}").unwrap();
+}
+
+pub fn render(st: &TrackingState, sessions: &[Session], now: DateTime<FixedOffset>) -> String {
+    let mut out = String::new();
+    gauge(
+        &mut out,
+        "kintai_clocked_in",
+        "1 while a session is running (including breaks).",
+        u8::from(st.phase != Phase::Idle),
+    );
+    gauge(
+        &mut out,
+        "kintai_on_break",
+        "1 while on break.",
+        u8::from(st.phase == Phase::OnBreak),
+    );
+    gauge(
+        &mut out,
+        "kintai_current_session_seconds",
+        "Seconds worked in the running session, excluding breaks.",
+        st.worked_secs(now),
+    );
+    gauge(
+        &mut out,
+        "kintai_hours_today",
+        "Hours worked today.",
+        hours_in(st, sessions, now, "%Y/%m/%d"),
+    );
+    gauge(
+        &mut out,
+        "kintai_hours_month",
+        "Hours worked this month.",
+        hours_in(st, sessions, now, "%Y/%m"),
+    );
+    out
+}
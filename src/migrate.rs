@@ -0,0 +1,131 @@
+//! `kintai migrate --to <format>`: rewrites a log in another format, after
+//! copying it to a backup. JSON lines (v2) keep quotes, backslashes and
+//! line breaks in content exactly; logfmt turns line breaks into spaces.
+//! Hash-chained events are relinked to the rewritten lines.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chain,
+    event::{LogFormat, parse_line},
+    report::plural,
+    state,
+};
+
+fn name(format: LogFormat) -> &'static str {
+    match format {
+        LogFormat::Logfmt => "logfmt",
+        LogFormat::Jsonl => "JSON lines",
+    }
+}
+
+/// The backups `migrate` left of `log`, as `<log>.<stamp>.bak`.
+pub fn backups(log: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let Some(name) = log.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+        return Ok(Vec::new());
+    };
+    let dir = match log.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{name}.");
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| Some(e.ok()?.path()))
+        .filter(|p| {
+            p.file_name()
+                .map(|f| f.to_string_lossy())
+                .is_some_and(|f| f.starts_with(&prefix) && f.ends_with(".bak"))
+        })
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+pub fn migrate(log: &Path, to: LogFormat) -> anyhow::Result<()> {
+    let text = fs::read_to_string(log)?;
+    let mut out = String::new();
+    let (mut converted, mut flattened, mut chained) = (0, 0, false);
+    let (mut previous_old, mut previous_new) = ("", String::new());
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let new = match parse_line(line) {
+            Some(mut e) => {
+                // Relink events that followed the old line before them.
+                if e.field("prev") == Some(chain::digest(previous_old).as_str()) {
+                    e.fields
+                        .insert("prev".to_string(), chain::digest(&previous_new));
+                    chained = true;
+                }
+                let fields: Vec<(&str, &str)> = e
+                    .fields
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect();
+                let new = to.format(&e.ts, &e.ty, &fields, e.content.as_deref());
+                if new != line {
+                    converted += 1;
+                }
+                let values = e.fields.values().chain(&e.content);
+                if to == LogFormat::Logfmt && values.into_iter().any(|v| v.contains(['\r', '\n'])) {
+                    flattened += 1;
+                }
+                new
+            }
+            // Lines that are not events are kept as they are.
+            None => line.to_string(),
+        };
+        out.push_str(&new);
+        out.push('\n');
+        previous_old = line;
+        previous_new = new;
+    }
+    if converted == 0 {
+        println!("{} is already in the {} format.", log.display(), name(to));
+        return Ok(());
+    }
+
+    let stamp = state::now().format("%Y%m%d%H%M%S").to_string();
+    let backup = (0..)
+        .map(|n| {
+            let mut name = log.as_os_str().to_os_string();
+            match n {
+                0 => name.push(format!(".{stamp}.bak")),
+                n => name.push(format!(".{stamp}-{n}.bak")),
+            }
+            PathBuf::from(name)
+        })
+        .find(|p| !p.exists())
+        .unwrap();
+    fs::copy(log, &backup)?;
+    // Write the new log next to the old one first, so a crash cannot lose both.
+    let tmp = log.with_extension("migrate-tmp");
+    {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(out.as_bytes())?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, log)?;
+    if chained {
+        chain::save_head(log, &previous_new)?;
+    }
+    println!(
+        "Rewrote {converted} event{} of {} as {}; the old log is kept as {}.",
+        plural(converted),
+        log.display(),
+        name(to),
+        backup.display()
+    );
+    if flattened > 0 {
+        println!(
+            "{flattened} event{} had line breaks, which became spaces.",
+            plural(flattened)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! OpenDocument Spreadsheet (`.ods`) export with the same layout as the
+//! Excel sheet.
+
+use std::{fs::File, io::Write, path::PathBuf};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::excel::{MonthSheet, month_sheet};
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn row(cells: &[&str]) -> String {
+    let mut xml = String::from("<table:table-row>");
+    if cells.is_empty() {
+        // Every row needs at least one cell.
+        xml.push_str("<table:table-cell/>");
+    }
+    for c in cells {
+        xml.push_str(&format!(
+            "<table:table-cell office:value-type=\"string\"><text:p>{}</text:p></table:table-cell>",
+            escape(c)
+        ));
+    }
+    xml.push_str("</table:table-row>");
+    xml
+}
+
+fn content(sheet: &MonthSheet) -> String {
+    let mut rows = vec![row(&[&sheet.title]), row(&[])];
+    rows.push(row(&["日付", "勤務時間", "作業内容"]));
+    for r in &sheet.rows {
+        rows.push(row(&[&r.date, &r.time, &r.content]));
+    }
+    rows.push(row(&[]));
+    rows.push(row(&["勤務時間の合計"]));
+    rows.push(row(&[&sheet.total_label]));
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2">
+<office:body><office:spreadsheet><table:table table:name="Sheet1"><table:table-column table:number-columns-repeated="3"/>{}</table:table></office:spreadsheet></office:body>
+</office:document-content>
+"#,
+        rows.concat()
+    )
+}
+
+/// Writes the first month in the log to an `.ods` file and returns its path,
+/// or `None` when there is nothing to export.
+pub fn export_ods(
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    user: Option<&str>,
+    full_month: bool,
+) -> anyhow::Result<Option<PathBuf>> {
+    let Some(sheet) = month_sheet(input, user, full_month, None)? else {
+        return Ok(None);
+    };
+    let out_path = output.unwrap_or_else(|| sheet.default_path(user, "ods"));
+
+    let mut zip = ZipWriter::new(File::create(&out_path)?);
+    // The mimetype must come first and be stored uncompressed.
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/vnd.oasis.opendocument.spreadsheet")?;
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(MANIFEST.as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(content(&sheet).as_bytes())?;
+    zip.finish()?;
+
+    println!("Generated ODS file: {}", out_path.display());
+    Ok(Some(out_path))
+}
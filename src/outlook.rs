@@ -0,0 +1,163 @@
+//! `kintai import outlook`: proposes sessions for the meetings you accepted
+//! in your Outlook/Exchange calendar, read from Microsoft Graph with `curl`.
+
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+use serde_json::Value;
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    config::Config,
+    ics::{CalendarEvent, IcsOptions, ImportAs, import_events},
+    state,
+};
+
+const DEFAULT_API: &str = "https://graph.microsoft.com/v1.0";
+
+/// A Graph access token with `Calendars.Read`: `$KINTAI_GRAPH_TOKEN`,
+/// `outlook.token` in the config, or whatever the Azure CLI prints.
+fn access_token(config: &Config) -> anyhow::Result<String> {
+    if let Ok(t) = std::env::var("KINTAI_GRAPH_TOKEN")
+        && !t.is_empty()
+    {
+        return Ok(t);
+    }
+    if let Some(t) = config.str("outlook.token") {
+        return Ok(t.to_string());
+    }
+    let out = Command::new("az")
+        .args(["account", "get-access-token", "--resource-type", "ms-graph"])
+        .args(["--query", "accessToken", "-o", "tsv"])
+        .output()
+        .map_err(|e| {
+            anyhow::anyhow!("no Graph access token: set KINTAI_GRAPH_TOKEN or install az ({e})")
+        })?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "az account get-access-token failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(out.stdout)?.trim().to_string())
+}
+
+/// Sends a GET request with `curl`, with times in UTC; the token is passed
+/// on stdin so it does not show up in the process list.
+fn get(url: &str, token: &str) -> anyhow::Result<Value> {
+    let mut child = Command::new("curl")
+        .args(["-sS", "--fail-with-body", "-H", "@-"])
+        .args(["-H", "Prefer: outlook.timezone=\"UTC\""])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("cannot run curl: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "Authorization: Bearer {token}")?;
+    }
+    let out = child.wait_with_output()?;
+    let reply: Value = serde_json::from_slice(&out.stdout).unwrap_or(Value::Null);
+    if !out.status.success() {
+        let msg = reply["error"]["message"]
+            .as_str()
+            .unwrap_or("request failed");
+        anyhow::bail!("Graph API: {msg}");
+    }
+    Ok(reply)
+}
+
+/// A Graph `dateTimeTimeZone` in UTC, e.g. `2026-10-08T01:00:00.0000000`.
+fn parse_time(v: &Value) -> Option<DateTime<FixedOffset>> {
+    let t = NaiveDateTime::parse_from_str(v["dateTime"].as_str()?, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    Some(
+        Utc.from_utc_datetime(&t)
+            .with_timezone(&Local)
+            .fixed_offset(),
+    )
+}
+
+/// A meeting you organised or accepted; `None` for anything else
+/// (declined, tentative, cancelled, all-day).
+fn meeting(e: &Value) -> Option<CalendarEvent> {
+    let response = e["responseStatus"]["response"].as_str()?;
+    if !matches!(response, "accepted" | "organizer")
+        || e["isCancelled"].as_bool() == Some(true)
+        || e["isAllDay"].as_bool() == Some(true)
+    {
+        return None;
+    }
+    Some(CalendarEvent {
+        start: parse_time(&e["start"])?,
+        end: parse_time(&e["end"])?,
+        summary: e["subject"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+/// Proposes a session for each meeting you accepted on `date` (default:
+/// today), tagged with `tag` as its project; see [`import_events`].
+pub fn import(
+    log: &Path,
+    user: Option<&str>,
+    date: Option<&str>,
+    tag: Option<String>,
+    apply: bool,
+    force: bool,
+) -> anyhow::Result<()> {
+    let day = match date {
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("invalid date {d:?} (expected YYYY-MM-DD)"))?,
+        None => state::now().date_naive(),
+    };
+    let config = Config::load()?;
+    let api = config
+        .str("outlook.api")
+        .unwrap_or(DEFAULT_API)
+        .trim_end_matches('/');
+    let token = access_token(&config)?;
+    let local = |d: NaiveDate| {
+        Local
+            .from_local_datetime(&d.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|t| {
+                t.with_timezone(&Utc)
+                    .format("%Y-%m-%dT%H:%M:%SZ")
+                    .to_string()
+            })
+            .ok_or_else(|| anyhow::anyhow!("no local midnight on {d}"))
+    };
+    let mut url = Some(format!(
+        "{api}/me/calendarView?startDateTime={}&endDateTime={}&$top=100\
+         &$select=subject,start,end,isAllDay,isCancelled,responseStatus",
+        local(day)?,
+        local(day + Duration::days(1))?
+    ));
+    let mut events = Vec::new();
+    while let Some(next) = url.take() {
+        let page = get(&next, &token)?;
+        events.extend(
+            page["value"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(meeting),
+        );
+        url = page["@odata.nextLink"].as_str().map(str::to_string);
+    }
+    events.sort_by_key(|e| e.start);
+
+    let day = day.format("%Y-%m-%d").to_string();
+    let options = IcsOptions {
+        import_as: ImportAs::Session,
+        tag: tag.or_else(|| config.str("outlook.tag").map(str::to_string)),
+        from: Some(day.clone()),
+        to: Some(day),
+        apply,
+        force,
+    };
+    import_events(log, user, events, &options, "outlook")
+}
@@ -0,0 +1,157 @@
+//! Overtime per day, split into pre-approved and unapproved hours.
+//! Overtime is work beyond `overtime.daily_hours` (default 8) on a day;
+//! `kintai approve-ot <date>` records a `type=approve_ot` event for it.
+
+use chrono::NaiveDate;
+use std::{collections::BTreeMap, fmt::Write, path::Path, path::PathBuf};
+
+use crate::{
+    close,
+    config::Config,
+    correct::apply_corrections,
+    event::{LogEvent, filter_user, read_events, record},
+    report::format_hm,
+    session::{Session, build_sessions},
+    state,
+};
+
+pub const DEFAULT_DAILY_HOURS: f64 = 8.0;
+pub const DEFAULT_WEEKLY_HOURS: f64 = 40.0;
+
+pub fn daily_limit(config: &Config) -> f64 {
+    config
+        .get("overtime.daily_hours")
+        .and_then(|v| v.as_num())
+        .unwrap_or(DEFAULT_DAILY_HOURS)
+}
+
+/// Hours a week after which work counts as overtime (`overtime.weekly_hours`).
+pub fn weekly_limit(config: &Config) -> f64 {
+    config
+        .get("overtime.weekly_hours")
+        .and_then(|v| v.as_num())
+        .unwrap_or(DEFAULT_WEEKLY_HOURS)
+}
+
+/// Approved overtime per `YYYY-MM-DD`: `None` approves any amount.
+pub fn approvals(events: &[LogEvent]) -> BTreeMap<String, Option<f64>> {
+    apply_corrections(events.to_vec())
+        .into_iter()
+        .filter(|e| e.ty == "approve_ot")
+        .filter_map(|e| {
+            let hours = e.field("hours").and_then(|h| h.parse().ok());
+            Some((e.field("date")?.to_string(), hours))
+        })
+        .collect()
+}
+
+/// Worked hours per `YYYY-MM-DD`.
+pub fn daily_hours(sessions: &[Session]) -> BTreeMap<String, f64> {
+    let mut days = BTreeMap::new();
+    for s in sessions {
+        *days.entry(s.date.replace('/', "-")).or_default() += s.minutes() as f64 / 60.0;
+    }
+    days
+}
+
+pub struct OvertimeDay {
+    pub date: String,
+    pub worked: f64,
+    pub approved: f64,
+    pub unapproved: f64,
+}
+
+pub fn overtime_days(
+    sessions: &[Session],
+    approvals: &BTreeMap<String, Option<f64>>,
+    limit: f64,
+) -> Vec<OvertimeDay> {
+    daily_hours(sessions)
+        .into_iter()
+        .filter(|(_, worked)| *worked > limit)
+        .map(|(date, worked)| {
+            let over = worked - limit;
+            let approved = match approvals.get(&date) {
+                Some(Some(cap)) => over.min(*cap),
+                Some(None) => over,
+                None => 0.0,
+            };
+            OvertimeDay {
+                date,
+                worked,
+                approved,
+                unapproved: over - approved,
+            }
+        })
+        .collect()
+}
+
+pub fn approve(
+    log: &Path,
+    user: Option<&str>,
+    date: &str,
+    hours: Option<f64>,
+    force: bool,
+) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {date:?}"))?;
+    let hours = hours.map(|h| h.to_string());
+    let mut extra = vec![("date", date)];
+    extra.extend(hours.as_deref().map(|h| ("hours", h)));
+    if date[..7] != state::now().format("%Y-%m").to_string() {
+        extra.extend(close::guard(log, user, date, force)?);
+    }
+    record(Some(log), user, "approve_ot", &extra, None, force)?;
+    println!(
+        "Approved overtime on {date}{}.",
+        hours.map(|h| format!(" up to {h}h")).unwrap_or_default()
+    );
+    Ok(())
+}
+
+pub fn report(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: Option<&str>,
+) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let approvals = approvals(&events);
+    let limit = daily_limit(&Config::load()?);
+    let days = overtime_days(&build_sessions(events), &approvals, limit);
+
+    let mut out = String::new();
+    writeln!(out, "| date | worked | overtime | approved | unapproved |").unwrap();
+    writeln!(out, "|------|--------|----------|----------|------------|").unwrap();
+    let mut totals: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+    for d in days
+        .iter()
+        .filter(|d| month.is_none_or(|m| d.date.starts_with(m)))
+    {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            d.date,
+            format_hm(d.worked),
+            format_hm(d.approved + d.unapproved),
+            format_hm(d.approved),
+            format_hm(d.unapproved)
+        )
+        .unwrap();
+        let t = totals.entry(&d.date[..7]).or_default();
+        t.0 += d.approved;
+        t.1 += d.unapproved;
+    }
+    for (m, (approved, unapproved)) in totals {
+        writeln!(
+            out,
+            "| {m} total | | {} | {} | {} |",
+            format_hm(approved + unapproved),
+            format_hm(approved),
+            format_hm(unapproved)
+        )
+        .unwrap();
+    }
+    println!("{out}");
+    println!("Overtime is work beyond {limit}h a day.");
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Where kintai keeps its files. Each directory is resolved in this order:
+//!
+//! 1. `--home <dir>`, then `KINTAI_HOME`: everything under one directory
+//!    (`config.toml`, `data/`, `cache/`), for portable installs;
+//! 2. the XDG variables (`XDG_CONFIG_HOME`, `XDG_DATA_HOME`,
+//!    `XDG_CACHE_HOME`), each with `kintai` appended;
+//! 3. the platform's folders (`%APPDATA%`, `%LOCALAPPDATA%` on Windows);
+//! 4. `~/.config`, `~/.local/share` and `~/.cache`.
+//!
+//! `KINTAI_CONFIG` still names the config file itself, and sockets prefer
+//! `XDG_RUNTIME_DIR`, as a portable home may be on a filesystem without them.
+
+use std::{env, ffi::OsString, path::PathBuf, sync::OnceLock};
+
+static HOME: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the directory given with `--home`, which wins over `KINTAI_HOME`.
+pub fn set_home(dir: PathBuf) {
+    let _ = HOME.set(dir);
+}
+
+/// `--home` from raw arguments. The config, and with it the aliases, is
+/// needed before the command line can be parsed, so this looks ahead.
+pub fn home_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1).take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        let Some(arg) = arg.to_str() else {
+            continue;
+        };
+        if arg == "--home" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix("--home=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// The portable home directory, if one is set.
+fn kintai_home() -> Option<PathBuf> {
+    HOME.get().cloned().or_else(|| {
+        env::var_os("KINTAI_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `$var`, else the platform's own directory, else `fallback` under the home
+/// directory, with `kintai` appended.
+fn xdg_dir(var: &str, platform: Option<PathBuf>, fallback: &str) -> PathBuf {
+    env::var_os(var)
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or(platform)
+        .or_else(|| home_dir().map(|h| h.join(fallback)))
+        .unwrap_or_else(env::temp_dir)
+        .join("kintai")
+}
+
+/// Directory holding `config.toml`: `%APPDATA%\kintai` on Windows.
+pub fn config_dir() -> PathBuf {
+    if let Some(home) = kintai_home() {
+        return home;
+    }
+    xdg_dir("XDG_CONFIG_HOME", known_folder::roaming(), ".config")
+}
+
+/// Directory for disposable files such as the status cache:
+/// `%LOCALAPPDATA%\kintai` on Windows.
+pub fn cache_dir() -> PathBuf {
+    if let Some(home) = kintai_home() {
+        return home.join("cache");
+    }
+    xdg_dir("XDG_CACHE_HOME", known_folder::local(), ".cache")
+}
+
+/// Directory for state worth keeping, such as issued invoice numbers:
+/// `%APPDATA%\kintai\data` on Windows, so it roams with the config.
+pub fn data_dir() -> PathBuf {
+    if let Some(home) = kintai_home() {
+        return home.join("data");
+    }
+    let xdg = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty());
+    match known_folder::roaming() {
+        Some(roaming) if xdg.is_none() => roaming.join("kintai").join("data"),
+        _ => xdg_dir("XDG_DATA_HOME", None, ".local/share"),
+    }
+}
+
+/// Directory for sockets and other per-session runtime files.
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join("kintai"))
+        .unwrap_or_else(cache_dir)
+}
+
+/// Windows' per-user folders, from the known-folder API rather than the
+/// `APPDATA` variables, which may be missing or stale under services.
+#[cfg(windows)]
+mod known_folder {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf, ptr};
+
+    #[repr(C)]
+    struct Guid(u32, u16, u16, [u8; 8]);
+
+    const ROAMING_APP_DATA: Guid = Guid(
+        0x3eb685db,
+        0x65f9,
+        0x4cf6,
+        [0xa0, 0x3a, 0xe3, 0xef, 0x65, 0x72, 0x9f, 0x3d],
+    );
+    const LOCAL_APP_DATA: Guid = Guid(
+        0xf1b32785,
+        0x6fba,
+        0x4fcf,
+        [0x9d, 0x55, 0x7b, 0x8e, 0x7f, 0x15, 0x70, 0x91],
+    );
+
+    #[link(name = "shell32")]
+    unsafe extern "system" {
+        fn SHGetKnownFolderPath(
+            id: *const Guid,
+            flags: u32,
+            token: *mut std::ffi::c_void,
+            path: *mut *mut u16,
+        ) -> i32;
+    }
+
+    #[link(name = "ole32")]
+    unsafe extern "system" {
+        fn CoTaskMemFree(p: *mut std::ffi::c_void);
+    }
+
+    fn get(id: &Guid) -> Option<PathBuf> {
+        let mut path = ptr::null_mut();
+        // SAFETY: on success `path` is a NUL-terminated wide string that we
+        // copy out and then free, as the API requires (also on failure).
+        unsafe {
+            let ok = SHGetKnownFolderPath(id, 0, ptr::null_mut(), &mut path) == 0;
+            let dir = ok.then(|| {
+                let len = (0..).take_while(|&i| *path.add(i) != 0).count();
+                PathBuf::from(OsString::from_wide(std::slice::from_raw_parts(path, len)))
+            });
+            CoTaskMemFree(path.cast());
+            dir
+        }
+    }
+
+    pub fn roaming() -> Option<PathBuf> {
+        get(&ROAMING_APP_DATA)
+    }
+
+    pub fn local() -> Option<PathBuf> {
+        get(&LOCAL_APP_DATA)
+    }
+}
+
+#[cfg(not(windows))]
+mod known_folder {
+    use std::path::PathBuf;
+
+    pub fn roaming() -> Option<PathBuf> {
+        None
+    }
+
+    pub fn local() -> Option<PathBuf> {
+        None
+    }
+}
@@ -0,0 +1,475 @@
+//! The month-end breakdown payroll asks for (`kintai close-report`): regular
+//! hours, statutory overtime, late-night and holiday hours, leave taken, and
+//! pay lines at the configured premiums.
+//!
+//! Hours on worked holidays that were not swapped for a rest day (see
+//! [`furikae`](crate::furikae)) are holiday hours, paid at `premium.holiday`
+//! (default 1.35×) instead of counting as regular time or overtime. Other
+//! days split into regular hours and overtime, paid at `premium.overtime`
+//! (default 1.25×): work beyond `overtime.daily_hours` (default 8) a day, or
+//! beyond `overtime.weekly_hours` (default 40) in a week starting on
+//! `weeks.start` (default Sunday), is overtime. Work between 22:00 and 5:00
+//! also earns `premium.late_night` (default 0.25×) on top.
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use crate::{
+    calendar::week_start,
+    config::Config,
+    correct::apply_corrections,
+    event::{LogEvent, filter_user, read_events},
+    furikae::{DEFAULT_HOLIDAY_PREMIUM, holidays_worked},
+    overtime::{daily_limit, weekly_limit},
+    report::format_hm,
+    session::{Session, build_sessions, users},
+    stats::parse_month,
+};
+
+pub const DEFAULT_OVERTIME_PREMIUM: f64 = 1.25;
+pub const DEFAULT_LATE_NIGHT_PREMIUM: f64 = 0.25;
+
+/// Late-night hours (22:00 to 5:00) in minutes since midnight of a session's
+/// date, which the parts of a session running past midnight exceed.
+const LATE_NIGHT: [(i64, i64); 3] = [(0, 5 * 60), (22 * 60, 29 * 60), (46 * 60, 53 * 60)];
+
+/// Rate multipliers for overtime, late-night and holiday hours.
+pub struct Premiums {
+    pub overtime: f64,
+    pub late_night: f64,
+    pub holiday: f64,
+}
+
+impl Premiums {
+    pub fn load(config: &Config) -> Premiums {
+        let num = |key: &str, default: f64| {
+            config
+                .get(&format!("premium.{key}"))
+                .and_then(|v| v.as_num())
+                .unwrap_or(default)
+        };
+        Premiums {
+            overtime: num("overtime", DEFAULT_OVERTIME_PREMIUM),
+            late_night: num("late_night", DEFAULT_LATE_NIGHT_PREMIUM),
+            holiday: num("holiday", DEFAULT_HOLIDAY_PREMIUM),
+        }
+    }
+}
+
+/// Where overtime begins: beyond `daily` hours a day, or beyond `weekly`
+/// hours in a week beginning on `week_start`.
+pub struct Limits {
+    pub daily: f64,
+    pub weekly: f64,
+    pub week_start: Weekday,
+}
+
+impl Limits {
+    pub fn load(config: &Config) -> anyhow::Result<Limits> {
+        Ok(Limits {
+            daily: daily_limit(config),
+            weekly: weekly_limit(config),
+            week_start: week_start(config)?,
+        })
+    }
+
+    /// The first day of the week `date` is in.
+    fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        let back = (date.weekday().num_days_from_monday() + 7
+            - self.week_start.num_days_from_monday())
+            % 7;
+        date - Days::new(back.into())
+    }
+}
+
+/// One month's hours by pay category, and the days off taken.
+#[derive(Default)]
+pub struct Breakdown {
+    pub days_worked: usize,
+    pub regular: f64,
+    pub overtime: f64,
+    /// Hours between 22:00 and 5:00, also counted in one of the others.
+    pub late_night: f64,
+    /// The part of `late_night` that is overtime: late-night hours after a
+    /// day's regular hours.
+    pub late_night_overtime: f64,
+    pub holiday: f64,
+    /// Paid leave (`leave` events), in days.
+    pub leave_days: f64,
+    /// Rest days taken for worked holidays (振休).
+    pub rest_days: usize,
+}
+
+/// Minutes from `a` to `b` (minutes since midnight) between 22:00 and 5:00.
+fn late_night_between(a: i64, b: i64) -> i64 {
+    LATE_NIGHT
+        .iter()
+        .map(|(x, y)| (b.min(*y) - a.max(*x)).max(0))
+        .sum()
+}
+
+/// Minutes of `s` worked between 22:00 and 5:00.
+pub fn late_night_minutes(s: &Session) -> i64 {
+    s.parts()
+        .iter()
+        .map(|(_, a, b)| late_night_between(*a, *b))
+        .sum()
+}
+
+/// Minutes of a day's `sessions` worked between 22:00 and 5:00 once the
+/// first `skip` minutes of work are done.
+fn late_night_after(sessions: &[Session], mut skip: i64) -> i64 {
+    let mut minutes = 0;
+    for s in sessions {
+        for (_, a, b) in s.parts() {
+            let from = (a + skip).min(b);
+            skip -= from - a;
+            minutes += late_night_between(from, b);
+        }
+    }
+    minutes
+}
+
+/// The breakdown of `month` (`YYYY-MM`). Each day, hours up to
+/// `limits.daily` are regular until the week's regular hours reach
+/// `limits.weekly`, and the rest is overtime; days of the week before the
+/// month count towards the weekly limit too. Holiday hours count towards
+/// neither.
+pub fn breakdown(events: &[LogEvent], month: &str, limits: &Limits) -> Breakdown {
+    let holidays = holidays_worked(events);
+    let mut by_day: BTreeMap<NaiveDate, Vec<Session>> = BTreeMap::new();
+    let mut sessions = build_sessions(events.to_vec());
+    sessions.sort_by_key(|s| s.start);
+    for s in sessions {
+        if let Ok(date) = NaiveDate::parse_from_str(&s.date, "%Y/%m/%d") {
+            by_day.entry(date).or_default().push(s);
+        }
+    }
+    let mut b = Breakdown::default();
+    let mut week = None;
+    let mut week_left = 0.0;
+    for (date, sessions) in &by_day {
+        if week != Some(limits.week_of(*date)) {
+            week = Some(limits.week_of(*date));
+            week_left = limits.weekly;
+        }
+        let hours = sessions.iter().map(Session::minutes).sum::<i64>() as f64 / 60.0;
+        let holiday = matches!(holidays.get(&date.to_string()), Some(None));
+        let regular = if holiday {
+            0.0
+        } else {
+            hours.min(limits.daily).min(week_left)
+        };
+        week_left -= regular;
+        if date.format("%Y-%m").to_string() != month {
+            continue;
+        }
+        b.days_worked += 1;
+        b.late_night += sessions.iter().map(late_night_minutes).sum::<i64>() as f64 / 60.0;
+        if holiday {
+            b.holiday += hours;
+        } else {
+            b.regular += regular;
+            b.overtime += hours - regular;
+            let skip = (regular * 60.0).round() as i64;
+            b.late_night_overtime += late_night_after(sessions, skip) as f64 / 60.0;
+        }
+    }
+    b.leave_days = apply_corrections(events.to_vec())
+        .iter()
+        .filter(|e| e.ty == "leave" && e.field("date").is_some_and(|d| d.starts_with(month)))
+        .map(|e| e.field("days").and_then(|d| d.parse().ok()).unwrap_or(1.0))
+        .fold(0.0, |total, days| total + days);
+    b.rest_days = holidays
+        .values()
+        .flatten()
+        .filter(|r| r.starts_with(month))
+        .count();
+    b
+}
+
+/// Pay lines as `(label, hours, hourly rate)`, the rate including the premium.
+pub fn pay_lines(b: &Breakdown, premiums: &Premiums, rate: f64) -> Vec<(&'static str, f64, f64)> {
+    vec![
+        ("regular", b.regular, 1.0),
+        ("overtime", b.overtime, premiums.overtime),
+        ("late-night premium", b.late_night, premiums.late_night),
+        ("holiday", b.holiday, premiums.holiday),
+    ]
+    .into_iter()
+    .map(|(label, hours, m)| (label, hours, m * rate))
+    .collect()
+}
+
+/// `kintai close-report`: the breakdown of `month` as one Markdown document,
+/// with pay lines when `rate` is given.
+pub fn close_report(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: &str,
+    rate: Option<f64>,
+) -> anyhow::Result<()> {
+    parse_month(month)?;
+    let events = filter_user(read_events(input)?, user);
+    let people = users(&build_sessions(events.clone()))
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if people.len() > 1 {
+        anyhow::bail!(
+            "the log contains several users ({}); pick one with --user",
+            people.join(", ")
+        );
+    }
+    let config = Config::load()?;
+    let limits = Limits::load(&config)?;
+    let premiums = Premiums::load(&config);
+    let b = breakdown(&events, month, &limits);
+
+    let mut out = String::new();
+    let who = user
+        .or(people.first().map(String::as_str))
+        .map(|u| format!(" ({u})"))
+        .unwrap_or_default();
+    writeln!(out, "# Attendance for {month}{who}\n").unwrap();
+    writeln!(out, "| item | total |").unwrap();
+    writeln!(out, "|------|-------|").unwrap();
+    writeln!(out, "| days worked | {} |", b.days_worked).unwrap();
+    writeln!(out, "| regular hours | {} |", format_hm(b.regular)).unwrap();
+    writeln!(
+        out,
+        "| statutory overtime (over {}h/day or {}h/week) | {} |",
+        limits.daily,
+        limits.weekly,
+        format_hm(b.overtime)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| late-night hours (22:00-5:00) | {} |",
+        format_hm(b.late_night)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| of which overtime | {} |",
+        format_hm(b.late_night_overtime)
+    )
+    .unwrap();
+    writeln!(out, "| holiday hours | {} |", format_hm(b.holiday)).unwrap();
+    writeln!(out, "| paid leave taken | {} days |", b.leave_days).unwrap();
+    writeln!(out, "| compensatory rest days | {} |", b.rest_days).unwrap();
+
+    if let Some(rate) = rate {
+        writeln!(out, "\n| pay line | hours | rate | amount |").unwrap();
+        writeln!(out, "|----------|-------|------|--------|").unwrap();
+        let mut total = 0;
+        for (label, hours, line_rate) in pay_lines(&b, &premiums, rate) {
+            let amount = (hours * line_rate).round() as u64;
+            total += amount;
+            writeln!(
+                out,
+                "| {label} | {} | {} | {amount} |",
+                format_hm(hours),
+                line_rate
+            )
+            .unwrap();
+        }
+        writeln!(out, "| total | | | {total} |").unwrap();
+    }
+    println!("{out}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::parse_line;
+
+    const LIMITS: Limits = Limits {
+        daily: 8.0,
+        weekly: 40.0,
+        week_start: Weekday::Sun,
+    };
+
+    /// A session on `date` from `start` to `finish` (`HH:MM`, JST), the
+    /// finish on the next day when it is earlier.
+    fn session(date: &str, start: &str, finish: &str) -> Vec<LogEvent> {
+        let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let end_day = if finish < start {
+            day + Days::new(1)
+        } else {
+            day
+        };
+        [
+            format!("ts={day}T{start}:00+09:00 type=start"),
+            format!("ts={end_day}T{finish}:00+09:00 type=finish"),
+        ]
+        .iter()
+        .map(|l| parse_line(l).unwrap())
+        .collect()
+    }
+
+    fn event(line: &str) -> LogEvent {
+        parse_line(line).unwrap()
+    }
+
+    #[test]
+    fn splits_days_at_the_daily_limit() {
+        let mut events = session("2026-04-06", "09:00", "19:00");
+        events.extend(session("2026-04-07", "09:00", "15:00"));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.days_worked, 2);
+        assert_eq!(b.regular, 14.0);
+        assert_eq!(b.overtime, 2.0);
+        assert_eq!(b.holiday, 0.0);
+    }
+
+    #[test]
+    fn counts_hours_beyond_the_weekly_limit_as_overtime() {
+        // Sunday 2026-03-29 starts the week; Monday and Tuesday are in March.
+        let events: Vec<LogEvent> = ["2026-03-30", "2026-03-31", "2026-04-01"]
+            .into_iter()
+            .chain(["2026-04-02", "2026-04-03", "2026-04-04"])
+            .flat_map(|d| session(d, "09:00", "17:00"))
+            .collect();
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.regular, 24.0);
+        assert_eq!(b.overtime, 8.0);
+        let b = breakdown(&events, "2026-03", &LIMITS);
+        assert_eq!(b.regular, 16.0);
+        assert_eq!(b.overtime, 0.0);
+    }
+
+    #[test]
+    fn does_not_count_daily_overtime_twice_in_the_week() {
+        // 10 hours Monday to Friday: 2 hours a day over, and 40 regular.
+        let events: Vec<LogEvent> = (6..=10)
+            .flat_map(|d| session(&format!("2026-04-{d:02}"), "08:00", "18:00"))
+            .collect();
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.regular, 40.0);
+        assert_eq!(b.overtime, 10.0);
+    }
+
+    #[test]
+    fn starts_weeks_on_the_configured_day() {
+        // Monday 2026-04-06 to Saturday, 8 hours each: 48 hours in the week
+        // from Sunday, but 40 in the week from Tuesday, Monday falling in the
+        // week before.
+        let events: Vec<LogEvent> = (6..=11)
+            .flat_map(|d| session(&format!("2026-04-{d:02}"), "09:00", "17:00"))
+            .collect();
+        let limits = Limits {
+            week_start: Weekday::Tue,
+            ..LIMITS
+        };
+        let b = breakdown(&events, "2026-04", &limits);
+        assert_eq!(b.regular, 48.0);
+        assert_eq!(b.overtime, 0.0);
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.regular, 40.0);
+        assert_eq!(b.overtime, 8.0);
+    }
+
+    #[test]
+    fn splits_late_night_hours() {
+        let day = session("2026-04-06", "09:00", "12:00");
+        assert_eq!(late_night_minutes(&build_sessions(day)[0]), 0);
+        let night = session("2026-04-06", "20:00", "02:30");
+        assert_eq!(late_night_minutes(&build_sessions(night)[0]), 4 * 60 + 30);
+        let early = session("2026-04-06", "04:00", "07:00");
+        assert_eq!(late_night_minutes(&build_sessions(early)[0]), 60);
+
+        let mut events = session("2026-04-06", "20:00", "02:30");
+        events.extend(session("2026-04-08", "04:00", "07:00"));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.late_night, 5.5);
+        assert_eq!(b.regular + b.overtime, 9.5);
+    }
+
+    #[test]
+    fn splits_late_night_overtime() {
+        // 11 hours from 13:00: overtime from 21:00, late-night from 22:00.
+        let b = breakdown(&session("2026-04-06", "13:00", "00:00"), "2026-04", &LIMITS);
+        assert_eq!(b.overtime, 3.0);
+        assert_eq!(b.late_night, 2.0);
+        assert_eq!(b.late_night_overtime, 2.0);
+        // A night shift whose overtime hour is after 5:00.
+        let b = breakdown(&session("2026-04-07", "22:00", "07:00"), "2026-04", &LIMITS);
+        assert_eq!(b.overtime, 1.0);
+        assert_eq!(b.late_night, 7.0);
+        assert_eq!(b.late_night_overtime, 0.0);
+    }
+
+    #[test]
+    fn counts_late_night_overtime_after_the_day_s_earlier_sessions() {
+        let mut events = session("2026-04-06", "08:00", "14:00");
+        events.extend(session("2026-04-06", "20:00", "23:00"));
+        events.push(event("ts=2026-04-06T21:00:00+09:00 type=break_start"));
+        events.push(event("ts=2026-04-06T21:30:00+09:00 type=break_end"));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        // 6 hours, then 20:00-21:00 and 21:30-22:30 regular, the rest over.
+        assert_eq!(b.overtime, 0.5);
+        assert_eq!(b.late_night, 1.0);
+        assert_eq!(b.late_night_overtime, 0.5);
+    }
+
+    #[test]
+    fn keeps_breaks_out_of_late_night_hours() {
+        let mut events = session("2026-04-06", "21:00", "01:00");
+        events.push(event("ts=2026-04-06T22:00:00+09:00 type=break_start"));
+        events.push(event("ts=2026-04-06T23:00:00+09:00 type=break_end"));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.late_night, 2.0);
+    }
+
+    #[test]
+    fn classifies_holiday_work() {
+        // 2026-04-29 (Shōwa Day) worked; 2026-05-03 worked but swapped for
+        // a rest day on 2026-05-07.
+        let mut events = session("2026-04-29", "09:00", "19:00");
+        events.extend(session("2026-05-03", "09:00", "19:00"));
+        events.push(event(
+            "ts=2026-04-30T09:00:00+09:00 type=holiday_work date=2026-04-29",
+        ));
+        events.push(event(
+            "ts=2026-05-04T09:00:00+09:00 type=holiday_work date=2026-05-03 rest=2026-05-07",
+        ));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.holiday, 10.0);
+        assert_eq!(b.regular + b.overtime, 0.0);
+        assert_eq!(b.days_worked, 1);
+        let b = breakdown(&events, "2026-05", &LIMITS);
+        assert_eq!(b.holiday, 0.0);
+        assert_eq!(b.regular, 8.0);
+        assert_eq!(b.overtime, 2.0);
+        assert_eq!(b.rest_days, 1);
+    }
+
+    #[test]
+    fn leaves_holiday_hours_out_of_the_weekly_limit() {
+        // Sunday 2026-04-05 worked as a holiday, then 40 hours Monday to Friday.
+        let mut events: Vec<LogEvent> = (5..=10)
+            .flat_map(|d| session(&format!("2026-04-{d:02}"), "09:00", "17:00"))
+            .collect();
+        events.push(event(
+            "ts=2026-04-05T18:00:00+09:00 type=holiday_work date=2026-04-05",
+        ));
+        let b = breakdown(&events, "2026-04", &LIMITS);
+        assert_eq!(b.holiday, 8.0);
+        assert_eq!(b.regular, 40.0);
+        assert_eq!(b.overtime, 0.0);
+    }
+
+    #[test]
+    fn sums_leave_days_of_the_month() {
+        let events = [
+            "ts=2026-04-01T09:00:00+09:00 type=leave date=2026-04-10",
+            "ts=2026-04-01T09:00:00+09:00 type=leave date=2026-04-13 days=0.5",
+            "ts=2026-04-01T09:00:00+09:00 type=leave date=2026-05-01",
+        ]
+        .map(event);
+        assert_eq!(breakdown(&events, "2026-04", &LIMITS).leave_days, 1.5);
+    }
+}
@@ -0,0 +1,120 @@
+//! External subcommands: `kintai <name>` runs `kintai-<name>` from `PATH`
+//! (like cargo), so exporters and other tools can live outside this crate.
+//!
+//! The plugin gets the remaining arguments, and on stdin one JSON document
+//! `{"schema_version", "log", "user", "sessions"}` whose sessions have the
+//! same shape as in `privacy export` (see `kintai schema`). `KINTAI` (this
+//! binary), `KINTAI_LOG` and `KINTAI_USER` are set in its environment.
+
+use serde_json::json;
+use std::{
+    env,
+    ffi::OsString,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    event::{filter_user, read_log},
+    privacy::session_json,
+    schema,
+    session::build_sessions,
+};
+
+const PREFIX: &str = "kintai-";
+
+/// Executables named `kintai-<name>` in `PATH`, by name; the first one found
+/// wins.
+pub fn find_all() -> Vec<(String, PathBuf)> {
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    for dir in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name
+                .to_str()
+                .and_then(|f| f.strip_prefix(PREFIX))
+                .map(|n| n.strip_suffix(env::consts::EXE_SUFFIX).unwrap_or(n))
+            else {
+                continue;
+            };
+            if is_executable(&entry.path()) && !found.iter().any(|(n, _)| n == name) {
+                found.push((name.to_string(), entry.path()));
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Lists the plugins found in `PATH`.
+pub fn list() {
+    let plugins = find_all();
+    if plugins.is_empty() {
+        println!("No {PREFIX}<name> executables in PATH.");
+    }
+    for (name, path) in plugins {
+        println!("{name}\t{}", path.display());
+    }
+}
+
+/// Runs plugin `args[0]` with the rest of `args`, and exits with its status.
+pub fn run(log: Option<&Path>, user: Option<&str>, args: Vec<OsString>) -> anyhow::Result<()> {
+    let name = args[0].to_string_lossy().into_owned();
+    let Some((_, program)) = find_all().into_iter().find(|(n, _)| *n == name) else {
+        anyhow::bail!(
+            "unknown command `{name}` (and no {PREFIX}{name} in PATH); see `kintai --help`"
+        );
+    };
+    let log = log.ok_or_else(|| {
+        anyhow::anyhow!("{PREFIX}{name} needs a log to read (--log or KINTAI_LOG)")
+    })?;
+    let sessions = build_sessions(filter_user(read_log(log)?, user));
+    let doc = json!({
+        "schema_version": schema::EXPORT_VERSION,
+        "log": log.display().to_string(),
+        "user": user,
+        "sessions": sessions.iter().map(session_json).collect::<Vec<_>>(),
+    });
+
+    let mut cmd = Command::new(&program);
+    cmd.args(&args[1..])
+        .env("KINTAI_LOG", log)
+        .stdin(Stdio::piped());
+    if let Ok(exe) = env::current_exe() {
+        cmd.env("KINTAI", exe);
+    }
+    if let Some(u) = user {
+        cmd.env("KINTAI_USER", u);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("cannot run {}: {e}", program.display()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A plugin that does not read its input closes the pipe early.
+        match stdin.write_all(doc.to_string().as_bytes()) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
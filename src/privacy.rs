@@ -0,0 +1,253 @@
+//! Handling of privacy requests: handing over everything recorded about a
+//! person, and deleting old events for good.
+
+use chrono::NaiveDate;
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, OpenOptions},
+    io::{Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    archive, chain,
+    event::{LogEvent, LogFormat, filter_user, new_id, parse_line, read_log, user_field},
+    history, migrate, schema,
+    session::{Session, build_sessions},
+    state,
+};
+
+/// The events of one file, without its archives.
+fn read_log_file(path: &Path) -> anyhow::Result<Vec<LogEvent>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(parse_line)
+        .collect())
+}
+
+fn event_json(e: &LogEvent) -> Value {
+    let mut fields = Map::new();
+    for (k, v) in &e.fields {
+        fields.insert(k.clone(), Value::String(v.clone()));
+    }
+    json!({
+        "id": e.id(),
+        "ts": e.ts,
+        "type": e.ty,
+        "content": e.content,
+        "fields": fields,
+    })
+}
+
+/// A session as in the export (`$defs/session` of the schema).
+pub fn session_json(s: &Session) -> Value {
+    json!({
+        "user": s.user,
+        "date": s.date,
+        "start": s.start.to_rfc3339(),
+        "time_range": s.time_range,
+        "minutes": s.minutes(),
+        "content": s.content,
+        "refs": s.refs,
+    })
+}
+
+/// Writes every event, session and monthly total of `user` (or of everyone)
+/// as one JSON document, to `output` or stdout, archived events included.
+/// Events only left in migrate backups and the undo history of the events
+/// come along too. The format is described by
+/// `schema/export.v1.schema.json`.
+pub fn export(log: &Path, user: Option<&str>, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let mut events = read_log(log)?;
+    if !archive::included() {
+        events.splice(0..0, archive::read(log)?);
+    }
+    let events = filter_user(events, user);
+    let mut backups = Vec::new();
+    {
+        let known: HashSet<&str> = events.iter().map(LogEvent::id).collect();
+        for path in migrate::backups(log)? {
+            let only: Vec<Value> = filter_user(read_log_file(&path)?, user)
+                .iter()
+                .filter(|e| !known.contains(e.id()))
+                .map(event_json)
+                .collect();
+            if !only.is_empty() {
+                backups.push(json!({ "file": path.display().to_string(), "events": only }));
+            }
+        }
+    }
+    let ids: HashSet<&str> = events.iter().map(LogEvent::id).collect();
+    let history = history::export_json(log, &ids)?;
+    let sessions = build_sessions(events.clone());
+    let mut monthly: BTreeMap<(&str, Option<&str>), i64> = BTreeMap::new();
+    for s in &sessions {
+        *monthly
+            .entry((&s.date[..7], s.user.as_deref()))
+            .or_default() += s.minutes();
+    }
+    let summaries: Vec<Value> = monthly
+        .into_iter()
+        .map(|((month, user), minutes)| json!({ "month": month, "user": user, "minutes": minutes }))
+        .collect();
+    let sessions: Vec<Value> = sessions.iter().map(session_json).collect();
+    let doc = json!({
+        "schema_version": schema::EXPORT_VERSION,
+        "exported_at": state::now().to_rfc3339(),
+        "log": log.display().to_string(),
+        "user": user,
+        "events": events.iter().map(event_json).collect::<Vec<_>>(),
+        "sessions": sessions,
+        "summaries": summaries,
+        "backups": backups,
+        "history": history,
+    });
+    let text = serde_json::to_string_pretty(&doc)?;
+    match output {
+        Some(path) => {
+            fs::write(&path, text + "\n")?;
+            eprintln!("Wrote {}", path.display());
+        }
+        None => println!("{text}"),
+    }
+    Ok(())
+}
+
+/// The lines of `text` to keep, and how many `purges` removed.
+fn split<'a>(text: &'a str, purges: &mut impl FnMut(&LogEvent) -> bool) -> (Vec<&'a str>, usize) {
+    let mut kept = Vec::new();
+    let mut count = 0;
+    for line in text.lines() {
+        if parse_line(line).is_some_and(|e| purges(&e)) {
+            count += 1;
+        } else {
+            kept.push(line);
+        }
+    }
+    (kept, count)
+}
+
+/// Overwrites `path` with zeros, so its contents do not linger on disk.
+pub fn shred(path: &Path) -> anyhow::Result<()> {
+    let len = fs::metadata(path)?.len();
+    let mut f = OpenOptions::new().write(true).open(path)?;
+    f.rewind()?;
+    f.write_all(&vec![0; len as usize])?;
+    f.sync_all()?;
+    Ok(())
+}
+
+/// Replaces `path` with `new`, shredding the old contents. The new file is
+/// written next to the old one first, so a crash cannot lose both.
+pub fn replace(path: &Path, new: &[u8]) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".purge-tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(new)?;
+        f.sync_all()?;
+    }
+    shred(path)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Removes events dated before `before` (of `user` only, when given) and
+/// corrections that refer to them, leaving a `purge` tombstone. Archives
+/// (`<log>.archive/`) and the backups left by `kintai migrate` lose the
+/// same events, and the undo journal their entries. Old file contents are
+/// overwritten with zeros before the rewritten files replace them, so the
+/// purged events do not linger on disk. Without `yes`, only reports what
+/// would be removed.
+pub fn purge(log: &Path, user: Option<&str>, before: &str, yes: bool) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(before, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {before:?}"))?;
+    let old = fs::read_to_string(log)?;
+
+    let mut purged_ids = HashSet::new();
+    let mut purges = |e: &LogEvent| {
+        let mine = user.is_none() || e.user() == user;
+        let old = e.ts.get(..10).is_some_and(|d| d < before);
+        let orphan = e.field("ref").is_some_and(|r| purged_ids.contains(r));
+        if mine && (old || orphan) {
+            purged_ids.insert(e.id().to_string());
+            true
+        } else {
+            false
+        }
+    };
+    // Archives hold the oldest events, whose corrections may be in the log.
+    let archived = archive::purge(log, &mut purges, yes)?;
+    let (kept, count) = split(&old, &mut purges);
+    let backups = migrate::backups(log)?;
+    let mut backed_up = 0;
+    for path in &backups {
+        let text = fs::read_to_string(path)?;
+        let (lines, n) = split(&text, &mut purges);
+        if yes && n > 0 {
+            let mut new = lines.join("\n");
+            if !new.is_empty() {
+                new.push('\n');
+            }
+            replace(path, new.as_bytes())?;
+        }
+        backed_up += n;
+    }
+    let elsewhere = match (archived, backed_up) {
+        (0, 0) => String::new(),
+        (a, b) => format!(" (and {a} archived, {b} in backups)"),
+    };
+
+    if !yes {
+        println!(
+            "Would purge {count} events before {before} from {}{elsewhere}; rerun with --yes to delete them.",
+            log.display()
+        );
+        return Ok(());
+    }
+    let journal = history::scrub(log, &purged_ids)?;
+    if count == 0 {
+        match (archived, backed_up) {
+            (0, 0) => println!("No events before {before}."),
+            _ => println!("Purged no events from the log{elsewhere}."),
+        }
+        return Ok(());
+    }
+
+    let id = new_id();
+    let count_str = count.to_string();
+    let mut fields = vec![
+        ("id", id.as_str()),
+        ("before", before),
+        ("count", &count_str),
+    ];
+    let prev = chain::enabled().then(|| chain::prev_of(&kept.join("\n")));
+    fields.extend(prev.as_deref().map(|p| ("prev", p)));
+    fields.extend(user_field(user));
+    let format = match LogFormat::sniff(&old) {
+        Some(f) => f,
+        None => LogFormat::configured()?,
+    };
+    let tombstone = format.format(&state::now().to_rfc3339(), "purge", &fields, None);
+
+    let mut new = kept.join("\n");
+    if !new.is_empty() {
+        new.push('\n');
+    }
+    new.push_str(&tombstone);
+    new.push('\n');
+    replace(log, new.as_bytes())?;
+    if prev.is_some() {
+        chain::save_head(log, &tombstone)?;
+    }
+    println!("Purged {count} events before {before}{elsewhere}.");
+    if journal > 0 {
+        println!(
+            "Removed {journal} undo history entr{}.",
+            if journal == 1 { "y" } else { "ies" }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! `kintai query "<SQL>"`: ad-hoc SQL over the parsed log. The sessions and
+//! events are loaded into an in-memory SQLite database, built into kintai,
+//! which then runs the query.
+//!
+//! Tables:
+//! - `sessions(date, user, start, finish, minutes, hours, content, location, project)`
+//! - `events(id, ts, type, user, content)`
+
+use rusqlite::{Connection, params, types::ValueRef};
+use std::{fmt::Write, path::PathBuf};
+
+use crate::{
+    correct::apply_corrections,
+    event::{filter_user, read_events},
+    session::build_sessions,
+};
+
+const TABLES: &str = "\
+CREATE TABLE sessions (date TEXT, user TEXT, start TEXT, finish TEXT, minutes INTEGER, hours REAL, content TEXT, location TEXT, project TEXT);
+CREATE TABLE events (id TEXT, ts TEXT, type TEXT, user TEXT, content TEXT);
+";
+
+/// A result cell as text; `NULL` is empty.
+fn cell(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+    }
+    .replace('|', "\\|")
+    .replace('\n', " ")
+}
+
+pub fn query(input: Option<PathBuf>, user: Option<&str>, sql: &str) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let mut db = Connection::open_in_memory()?;
+    db.execute_batch(TABLES)?;
+    let tx = db.transaction()?;
+    {
+        let mut insert = tx.prepare("INSERT INTO events VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for e in apply_corrections(events.clone()) {
+            insert.execute(params![e.id(), e.ts, e.ty, e.user(), e.content])?;
+        }
+        let mut insert =
+            tx.prepare("INSERT INTO sessions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?;
+        for s in build_sessions(events) {
+            let finish = s.span().map(|(_, end)| end.format("%H:%M").to_string());
+            insert.execute(params![
+                s.date,
+                s.user,
+                s.start.to_rfc3339(),
+                finish,
+                s.minutes(),
+                s.minutes() as f64 / 60.0,
+                s.content,
+                s.location,
+                s.project,
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    let mut stmt = db.prepare(sql.trim())?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut out = String::new();
+    if !columns.is_empty() {
+        writeln!(out, "| {} |", columns.join(" | ")).unwrap();
+        writeln!(out, "|{}|", vec!["---"; columns.len()].join("|")).unwrap();
+    }
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let cells = (0..columns.len())
+            .map(|i| row.get_ref(i).map(cell))
+            .collect::<Result<Vec<_>, _>>()?;
+        writeln!(out, "| {} |", cells.join(" | ")).unwrap();
+    }
+    print!("{out}");
+    Ok(())
+}
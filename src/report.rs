@@ -0,0 +1,855 @@
+use chrono::{Datelike, NaiveDate};
+use clap::ValueEnum;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    calendar::{Calendar, Weeks},
+    columns::{Columns, columns_table},
+    config::Config,
+    correct::apply_corrections,
+    display::{self, Formats, HourStyle},
+    event::{LogEvent, filter_user, read_events},
+    expense::{expenses, expenses_table},
+    furikae::{holidays_worked, premium_table},
+    issues::{self, issue_table},
+    session::{Interval, Session, build_intervals, build_sessions, users},
+    skew, state,
+    units::{unit_counts, units_table},
+};
+
+/// Raw events with their IDs, including corrections, so that an event can
+/// be referred to by `amend` and `void`.
+pub fn list_events(input: Option<PathBuf>, user: Option<&str>) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let mut out = String::new();
+    writeln!(out, "| id | ts | type | content |").unwrap();
+    writeln!(out, "|----|----|------|---------|").unwrap();
+    for e in &events {
+        let mut ty = e.ty.clone();
+        if let Some(r) = e.field("ref") {
+            ty.push_str(&format!(" → {r}"));
+        }
+        writeln!(
+            out,
+            "| {} | {} | {ty} | {} |",
+            e.id(),
+            e.ts,
+            e.content.as_deref().unwrap_or_default()
+        )
+        .unwrap();
+    }
+    println!("{out}");
+    Ok(())
+}
+
+/// Daily session table. A `user` column is added when the sessions belong to
+/// more than one person, and a `week` column when `weeks` is set. `missing`
+/// workdays (`YYYY/MM/DD`) get a flagged row of their own.
+pub fn sessions_table(
+    sessions: &[Session],
+    missing: &[String],
+    weeks: Option<&Weeks>,
+    formats: &Formats,
+) -> String {
+    let multi_user = users(sessions).len() > 1;
+    let mut out = String::new();
+    let (week_head, week_rule) = if weeks.is_some() {
+        (" week |", "------|")
+    } else {
+        ("", "")
+    };
+    if multi_user {
+        writeln!(out, "| date |{week_head} user | time | content |").unwrap();
+        writeln!(out, "|------|{week_rule}------|------|---------|").unwrap();
+    } else {
+        writeln!(out, "| date |{week_head} time | content |").unwrap();
+        writeln!(out, "|------|{week_rule}------|---------|").unwrap();
+    }
+    let mut rows: Vec<(&str, Option<&Session>)> = sessions
+        .iter()
+        .map(|s| (s.date.as_str(), Some(s)))
+        .chain(missing.iter().map(|d| (d.as_str(), None)))
+        .collect();
+    rows.sort_by_key(|(date, _)| *date);
+    for (date, s) in rows {
+        let mut user = String::new();
+        if let Some(weeks) = weeks {
+            user.push_str(&format!(" {} |", weeks.label_of(date)));
+        }
+        if multi_user {
+            let name = s.and_then(|s| s.user.as_deref());
+            user.push_str(&format!(" {} |", name.unwrap_or_default()));
+        }
+        let (time, content) = match s {
+            Some(s) => (
+                formats.time_range(&s.time_range),
+                issues::get().linkify(s.content.as_deref().unwrap_or_default()),
+            ),
+            None => ("-".to_string(), "⚠ no clock-in".to_string()),
+        };
+        writeln!(out, "| {} |{user} {time} | {content} |", formats.date(date)).unwrap();
+    }
+    out
+}
+
+/// Days taken off (`leave` events and swapped rest days), as `YYYY-MM-DD`.
+fn days_off(events: &[LogEvent], holidays: &BTreeMap<String, Option<String>>) -> BTreeSet<String> {
+    let mut days: BTreeSet<String> = holidays.values().flatten().cloned().collect();
+    days.extend(
+        apply_corrections(events.to_vec())
+            .iter()
+            .filter(|e| e.ty == "leave")
+            .filter_map(|e| e.field("date").map(str::to_string)),
+    );
+    days
+}
+
+/// Workdays before `today` in the months of `sessions` that have no session
+/// and are not `excused` (leave or rest days, as `YYYY-MM-DD`).
+pub fn missing_workdays(
+    sessions: &[Session],
+    excused: &BTreeSet<String>,
+    calendar: &Calendar,
+    today: NaiveDate,
+) -> Vec<String> {
+    let worked: BTreeSet<&str> = sessions.iter().map(|s| s.date.as_str()).collect();
+    let months: BTreeSet<&str> = worked.iter().map(|d| &d[..7]).collect();
+    let mut missing = Vec::new();
+    for month in months {
+        let Ok(first) = NaiveDate::parse_from_str(&format!("{month}/01"), "%Y/%m/%d") else {
+            continue;
+        };
+        for day in first
+            .iter_days()
+            .take_while(|d| d.month() == first.month() && *d < today)
+        {
+            let date = day.format("%Y/%m/%d").to_string();
+            if calendar.is_workday(day)
+                && !worked.contains(date.as_str())
+                && !excused.contains(&day.format("%Y-%m-%d").to_string())
+            {
+                missing.push(date);
+            }
+        }
+    }
+    missing
+}
+
+/// Hours the standard pattern expected in each month of `sessions`, up to
+/// `today`, against the hours worked. Days off (`excused`, as `YYYY-MM-DD`)
+/// expect nothing. `None` without a pattern.
+pub fn expected_table(
+    sessions: &[Session],
+    excused: &BTreeSet<String>,
+    calendar: &Calendar,
+    today: NaiveDate,
+    formats: &Formats,
+) -> Option<String> {
+    if !calendar.has_pattern() {
+        return None;
+    }
+    let mut out = String::new();
+    writeln!(out, "| month | expected | worked | difference |").unwrap();
+    writeln!(out, "|-------|----------|--------|------------|").unwrap();
+    for (month, worked) in monthly_hours(sessions) {
+        let Ok(first) = NaiveDate::parse_from_str(&format!("{month}/01"), "%Y/%m/%d") else {
+            continue;
+        };
+        let expected: f64 = first
+            .iter_days()
+            .take_while(|d| d.month() == first.month() && *d <= today)
+            .filter(|d| !excused.contains(&d.format("%Y-%m-%d").to_string()))
+            .filter_map(|d| calendar.expected_hours(d))
+            .sum();
+        writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            formats.month(&month),
+            format_hm(expected),
+            format_hm(worked),
+            delta_hm(worked - expected)
+        )
+        .unwrap();
+    }
+    Some(out)
+}
+
+/// Period each bar of `summary --chart` covers.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ChartBy {
+    Day,
+    Month,
+}
+
+/// Width of the longest bar, in characters.
+const CHART_WIDTH: f64 = 40.0;
+
+/// Horizontal bar chart of hours per day or month, drawn with block
+/// characters in eighths.
+pub fn bar_chart(sessions: &[Session], by: ChartBy) -> String {
+    let rows: BTreeMap<String, f64> = match by {
+        ChartBy::Month => monthly_hours(sessions),
+        ChartBy::Day => {
+            let mut days = BTreeMap::new();
+            for s in sessions {
+                *days.entry(s.date.clone()).or_default() += s.minutes() as f64 / 60.0;
+            }
+            days
+        }
+    };
+    let max = rows.values().cloned().fold(0.0, f64::max);
+    let mut out = String::from("```text\n");
+    for (label, h) in &rows {
+        let eighths = if max > 0.0 {
+            (h / max * CHART_WIDTH * 8.0).round() as usize
+        } else {
+            0
+        };
+        let mut bar = "█".repeat(eighths / 8);
+        if eighths % 8 > 0 {
+            bar.push(['▏', '▎', '▍', '▌', '▋', '▊', '▉'][eighths % 8 - 1]);
+        }
+        writeln!(out, "{label} {bar} {}", format_hm(*h)).unwrap();
+    }
+    out.push_str("```\n");
+    out
+}
+
+/// The daily session table followed by the monthly summary.
+pub fn summary_markdown(
+    input: Option<PathBuf>,
+    rate: Option<f64>,
+    increment: Option<i64>,
+    user: Option<&str>,
+    color: bool,
+    chart: Option<ChartBy>,
+) -> anyhow::Result<String> {
+    let events = filter_user(read_events(input)?, user);
+    for skew in skew::find(&apply_corrections(events.clone())) {
+        eprintln!("warning: {}", skew.describe());
+    }
+    let expenses = expenses(&events);
+    let holidays = holidays_worked(&events);
+    let excused = days_off(&events, &holidays);
+    let intervals = build_intervals(events.clone());
+    let sessions = build_sessions(events.clone());
+    let counts = unit_counts(&events, &sessions);
+    let config = Config::load()?;
+    let weeks = Weeks::load(&config)?;
+    let formats = Formats::load(&config)?;
+    let calendar = Calendar::load(&config)?;
+    let increment = increment.or_else(|| {
+        config
+            .get("billing.increment")
+            .and_then(|v| v.as_num())
+            .map(|m| m as i64)
+    });
+    let today = state::now().date_naive();
+    let missing = if users(&sessions).len() > 1 {
+        Vec::new()
+    } else {
+        missing_workdays(&sessions, &excused, &calendar, today)
+    };
+    let mut out = format!(
+        "{}\n{}\n",
+        sessions_table(&sessions, &missing, weeks.as_ref(), &formats),
+        summary_table(&sessions, rate, &formats, increment)
+    );
+    if let Some(weeks) = &weeks {
+        writeln!(out, "{}", weekly_table(&sessions, weeks)).unwrap();
+    }
+    let columns = Columns::load(&config)?;
+    if !columns.is_empty() && users(&sessions).len() <= 1 {
+        let days = columns.days(&sessions, rate, &calendar)?;
+        writeln!(out, "{}", columns_table(&columns, &days, &formats)).unwrap();
+    }
+    if let Some(by) = chart {
+        writeln!(out, "{}", bar_chart(&sessions, by)).unwrap();
+    }
+    if users(&sessions).len() <= 1
+        && let Some(table) = expected_table(&sessions, &excused, &calendar, today, &formats)
+    {
+        writeln!(out, "{table}").unwrap();
+    }
+    if let Some(line) = forecast(&sessions, rate, &calendar, today) {
+        writeln!(out, "{line}\n").unwrap();
+    }
+    if sessions.iter().any(|s| s.location.is_some()) {
+        writeln!(out, "{}", location_table(&sessions, &formats)).unwrap();
+        let allowances: BTreeMap<&str, f64> = config
+            .entries("allowance.per_day")
+            .into_iter()
+            .filter_map(|(loc, v)| Some((loc, v.as_num()?)))
+            .collect();
+        if !allowances.is_empty() {
+            writeln!(
+                out,
+                "{}",
+                payout_table(&sessions, rate, &allowances, &formats)
+            )
+            .unwrap();
+        }
+    }
+    let day_rates = day_rates(&config)?;
+    if let Some(table) = day_rate_table(&sessions, &day_rates, rate, &formats) {
+        writeln!(out, "{table}").unwrap();
+    }
+    let budgets: BTreeMap<&str, f64> = config
+        .entries("budget")
+        .into_iter()
+        .filter_map(|(p, v)| Some((p, v.as_num()?)))
+        .collect();
+    if let Some(table) = budget_table(&sessions, &budgets, color, &formats) {
+        writeln!(out, "{table}").unwrap();
+    }
+    if sessions.iter().any(|s| !s.break_minutes.is_empty()) {
+        let statutory = config.strs("breaks.statutory");
+        writeln!(
+            out,
+            "{}",
+            breaks_table(&sessions, statutory.as_deref(), &formats)
+        )
+        .unwrap();
+    }
+    if !intervals.is_empty() {
+        writeln!(out, "{}", interval_table(&intervals, &formats)).unwrap();
+    }
+    if sessions.iter().any(|s| !s.refs.is_empty()) {
+        writeln!(out, "{}", issue_table(&sessions)).unwrap();
+    }
+    if !holidays.is_empty() {
+        writeln!(
+            out,
+            "{}",
+            premium_table(&sessions, &holidays, rate, &config, &formats)
+        )
+        .unwrap();
+    }
+    if !counts.is_empty() {
+        writeln!(out, "{}", units_table(&counts, &config, &formats)).unwrap();
+    }
+    if !expenses.is_empty() {
+        writeln!(out, "{}", expenses_table(&expenses, &formats)).unwrap();
+    }
+    Ok(out)
+}
+
+/// Hours worked per week, labelled by `weeks`.
+pub fn weekly_table(sessions: &[Session], weeks: &Weeks) -> String {
+    let mut rows: BTreeMap<String, f64> = BTreeMap::new();
+    for s in sessions {
+        *rows.entry(weeks.label_of(&s.date)).or_default() += s.minutes() as f64 / 60.0;
+    }
+    let mut out = String::new();
+    writeln!(out, "| week | hours |").unwrap();
+    writeln!(out, "|------|-------|").unwrap();
+    for (week, h) in rows {
+        writeln!(out, "| {week} | {} |", format_hours(h)).unwrap();
+    }
+    out
+}
+
+/// Time per month in each custom interval type (`[intervals.<name>]`), per
+/// person when the log has several.
+pub fn interval_table(intervals: &[Interval], formats: &Formats) -> String {
+    let multi_user = intervals.iter().any(|i| i.user != intervals[0].user);
+    let mut rows: BTreeMap<(String, &str, &str), (usize, i64)> = BTreeMap::new();
+    for i in intervals {
+        let month = i.start.format("%Y/%m").to_string();
+        let user = if multi_user {
+            i.user.as_deref().unwrap_or("-")
+        } else {
+            ""
+        };
+        let (count, minutes) = rows.entry((month, &i.name, user)).or_default();
+        *count += 1;
+        *minutes += i.minutes();
+    }
+    let mut out = String::new();
+    if multi_user {
+        writeln!(out, "| month | interval | user | count | hours |").unwrap();
+        writeln!(out, "|-------|----------|------|-------|-------|").unwrap();
+    } else {
+        writeln!(out, "| month | interval | count | hours |").unwrap();
+        writeln!(out, "|-------|----------|-------|-------|").unwrap();
+    }
+    for ((month, name, user), (count, minutes)) in rows {
+        let user = if multi_user {
+            format!(" {user} |")
+        } else {
+            String::new()
+        };
+        writeln!(
+            out,
+            "| {} | {name} |{user} {count} | {} |",
+            formats.month(&month),
+            format_hm(minutes as f64 / 60.0)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Days worked per month and location. A day with sessions at two locations
+/// counts for both; sessions without a location count as `-`.
+pub fn location_table(sessions: &[Session], formats: &Formats) -> String {
+    let mut locations: Vec<&str> = sessions
+        .iter()
+        .map(|s| s.location.as_deref().unwrap_or("-"))
+        .collect();
+    locations.sort_unstable();
+    locations.dedup();
+
+    let mut days: BTreeMap<&str, BTreeMap<&str, BTreeSet<&str>>> = BTreeMap::new();
+    for s in sessions {
+        days.entry(&s.date[..7])
+            .or_default()
+            .entry(s.location.as_deref().unwrap_or("-"))
+            .or_default()
+            .insert(&s.date);
+    }
+
+    let mut out = String::new();
+    writeln!(out, "| month | {} |", locations.join(" | ")).unwrap();
+    writeln!(out, "|-------|{}", "------|".repeat(locations.len())).unwrap();
+    for (month, row) in days {
+        let cells: Vec<String> = locations
+            .iter()
+            .map(|l| format!("{}d", row.get(l).map_or(0, BTreeSet::len)))
+            .collect();
+        writeln!(out, "| {} | {} |", formats.month(month), cells.join(" | ")).unwrap();
+    }
+    out
+}
+
+/// Monthly payout: salary plus a per-day allowance for each location that
+/// has one configured (e.g. a remote-work allowance).
+pub fn payout_table(
+    sessions: &[Session],
+    rate: Option<f64>,
+    allowances: &BTreeMap<&str, f64>,
+    formats: &Formats,
+) -> String {
+    let rate = rate.unwrap_or(0.0);
+    let mut months: BTreeMap<&str, (f64, BTreeMap<&str, BTreeSet<&str>>)> = BTreeMap::new();
+    for s in sessions {
+        let (hours, days) = months.entry(&s.date[..7]).or_default();
+        *hours += s.minutes() as f64 / 60.0;
+        if let Some(loc) = s.location.as_deref().filter(|l| allowances.contains_key(l)) {
+            days.entry(loc).or_default().insert(&s.date);
+        }
+    }
+
+    let mut out = String::new();
+    let headers: Vec<String> = allowances
+        .keys()
+        .map(|l| format!("{l} allowance"))
+        .collect();
+    writeln!(out, "| month | salary | {} | total |", headers.join(" | ")).unwrap();
+    writeln!(
+        out,
+        "|-------|--------|{}-------|",
+        "------|".repeat(headers.len())
+    )
+    .unwrap();
+    for (month, (hours, days)) in months {
+        let salary = (hours * rate).round() as u64;
+        let mut total = salary;
+        let mut cells = Vec::new();
+        for (loc, per_day) in allowances {
+            let n = days.get(loc).map_or(0, BTreeSet::len);
+            let amount = (n as f64 * per_day).round() as u64;
+            total += amount;
+            cells.push(format!("{amount} ({n}d)"));
+        }
+        writeln!(
+            out,
+            "| {} | {salary} | {} | {total} |",
+            formats.month(month),
+            cells.join(" | ")
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// A flat amount per day worked on a project (`[day_rate.<project>]`),
+/// instead of pay by the hour.
+pub struct DayRate {
+    pub amount: f64,
+    /// Days with fewer hours on the project earn nothing.
+    pub min_hours: Option<f64>,
+}
+
+/// The `[day_rate.<project>]` sections of the config.
+pub fn day_rates(config: &Config) -> anyhow::Result<BTreeMap<&str, DayRate>> {
+    let mut rates = BTreeMap::new();
+    for project in config.sections("day_rate") {
+        let num = |key: &str| config.get(&format!("day_rate.{project}.{key}"));
+        let amount = num("amount")
+            .and_then(|v| v.as_num())
+            .ok_or_else(|| anyhow::anyhow!("day_rate.{project}.amount must be a number"))?;
+        let min_hours =
+            match num("min_hours") {
+                None => None,
+                Some(v) => Some(v.as_num().ok_or_else(|| {
+                    anyhow::anyhow!("day_rate.{project}.min_hours must be a number")
+                })?),
+            };
+        rates.insert(project, DayRate { amount, min_hours });
+    }
+    Ok(rates)
+}
+
+/// Monthly pay of projects paid by the day: the days that earn the day
+/// rate, the days short of its minimum hours, and next to the flat amount
+/// what the same hours would earn at the hourly `rate`. `None` when no
+/// session is on such a project.
+pub fn day_rate_table(
+    sessions: &[Session],
+    rates: &BTreeMap<&str, DayRate>,
+    rate: Option<f64>,
+    formats: &Formats,
+) -> Option<String> {
+    let mut days: BTreeMap<(&str, &str), BTreeMap<&str, f64>> = BTreeMap::new();
+    for s in sessions {
+        if let Some(p) = s.project.as_deref().filter(|p| rates.contains_key(p)) {
+            *days
+                .entry((&s.date[..7], p))
+                .or_default()
+                .entry(&s.date)
+                .or_default() += s.minutes() as f64 / 60.0;
+        }
+    }
+    if days.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    writeln!(
+        out,
+        "| month | project | days | short days | hours | day rate | amount | hourly |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|-------|---------|------|------------|-------|----------|--------|--------|"
+    )
+    .unwrap();
+    for ((month, project), per_day) in days {
+        let day_rate = &rates[project];
+        let paid = per_day
+            .values()
+            .filter(|h| day_rate.min_hours.is_none_or(|min| **h >= min))
+            .count();
+        let hours: f64 = per_day.values().sum();
+        writeln!(
+            out,
+            "| {} | {project} | {paid} | {} | {} | {} | {} | {} |",
+            formats.month(month),
+            per_day.len() - paid,
+            format_hm(hours),
+            day_rate.amount,
+            (paid as f64 * day_rate.amount).round() as u64,
+            (hours * rate.unwrap_or(0.0)).round() as u64
+        )
+        .unwrap();
+    }
+    Some(out)
+}
+
+/// Share of a budget above which it is flagged.
+const BUDGET_WARNING: f64 = 0.8;
+
+/// Hours used per month against each project's monthly budget. Projects
+/// past 80% of their budget are marked (in yellow, or red once over, when
+/// `color` is set). `None` when no session is on a budgeted project.
+pub fn budget_table(
+    sessions: &[Session],
+    budgets: &BTreeMap<&str, f64>,
+    color: bool,
+    formats: &Formats,
+) -> Option<String> {
+    let mut used: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+    for s in sessions {
+        if let Some(p) = s.project.as_deref().filter(|p| budgets.contains_key(p)) {
+            *used.entry((&s.date[..7], p)).or_default() += s.minutes() as f64 / 60.0;
+        }
+    }
+    if used.is_empty() {
+        return None;
+    }
+    let mut out = String::new();
+    writeln!(
+        out,
+        "| month | project | used | budget | remaining | burn |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|-------|---------|------|--------|-----------|------|"
+    )
+    .unwrap();
+    for ((month, project), h) in used {
+        let budget = budgets[project];
+        let share = if budget > 0.0 { h / budget } else { 1.0 };
+        let mut burn = format!("{:.0}%", share * 100.0);
+        if share > BUDGET_WARNING {
+            burn = format!("⚠ {burn}");
+            if color {
+                let code = if share > 1.0 { 31 } else { 33 };
+                burn = format!("\x1b[{code}m{burn}\x1b[0m");
+            }
+        }
+        writeln!(
+            out,
+            "| {} | {project} | {} | {} | {} | {burn} |",
+            formats.month(month),
+            format_hm(h),
+            format_hm(budget),
+            format_hm((budget - h).max(0.0))
+        )
+        .unwrap();
+    }
+    Some(out)
+}
+
+/// Break time per month and kind. The `statutory` column sums the kinds that
+/// count toward the legally required break (all kinds unless configured).
+pub fn breaks_table(sessions: &[Session], statutory: Option<&[&str]>, formats: &Formats) -> String {
+    let mut kinds: Vec<&str> = sessions
+        .iter()
+        .flat_map(|s| s.break_minutes.keys().map(String::as_str))
+        .collect();
+    kinds.sort_unstable();
+    kinds.dedup();
+    let counts = |kind: &str| statutory.is_none_or(|list| list.contains(&kind));
+
+    let mut months: BTreeMap<&str, BTreeMap<&str, i64>> = BTreeMap::new();
+    for s in sessions {
+        let row = months.entry(&s.date[..7]).or_default();
+        for (kind, m) in &s.break_minutes {
+            *row.entry(kind).or_default() += m;
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "| month | {} | statutory |", kinds.join(" | ")).unwrap();
+    writeln!(
+        out,
+        "|-------|{}-----------|",
+        "------|".repeat(kinds.len())
+    )
+    .unwrap();
+    for (month, row) in months {
+        let cells: Vec<String> = kinds
+            .iter()
+            .map(|k| format_hm(row.get(k).copied().unwrap_or(0) as f64 / 60.0))
+            .collect();
+        let statutory: i64 = row.iter().filter(|(k, _)| counts(k)).map(|(_, m)| m).sum();
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            formats.month(month),
+            cells.join(" | "),
+            format_hm(statutory as f64 / 60.0)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Projects the current month's hours and salary: the hours so far plus the
+/// average per worked day times the workdays left after today.
+pub fn forecast(
+    sessions: &[Session],
+    rate: Option<f64>,
+    calendar: &Calendar,
+    today: NaiveDate,
+) -> Option<String> {
+    let month = today.format("%Y/%m").to_string();
+    let this_month: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| s.date.starts_with(&month))
+        .collect();
+    if this_month.is_empty() || users(sessions).len() > 1 {
+        return None;
+    }
+    let hours: f64 = this_month.iter().map(|s| s.minutes() as f64 / 60.0).sum();
+    let mut days: Vec<&str> = this_month.iter().map(|s| s.date.as_str()).collect();
+    days.dedup();
+    let remaining = calendar.remaining_in_month(today);
+    // The standard pattern says what is left; otherwise the days so far do.
+    let (rest, basis) = match calendar.remaining_hours(today) {
+        Some(expected) => (expected, format!("{} expected", format_hm(expected))),
+        None => {
+            let per_day = hours / days.len() as f64;
+            (
+                per_day * remaining as f64,
+                format!("{} per day", format_hm(per_day)),
+            )
+        }
+    };
+    let projected = hours + rest;
+    let mut line = format!(
+        "Forecast for {month}: {} ({} so far, {basis} over {remaining} remaining workdays)",
+        format_hm(projected),
+        format_hm(hours),
+    );
+    if let Some(r) = rate {
+        line.push_str(&format!(", salary {}", (projected * r).round() as u64));
+    }
+    Some(line)
+}
+
+/// Worked hours per `YYYY/MM` month.
+pub fn monthly_hours(sessions: &[Session]) -> BTreeMap<String, f64> {
+    let mut monthly: BTreeMap<String, f64> = BTreeMap::new();
+    for s in sessions {
+        let month = &s.date[..7];
+        *monthly.entry(month.to_string()).or_default() += s.minutes() as f64 / 60.0;
+    }
+    monthly
+}
+
+/// Formats hours as `7h30m (7.50h)`, or in the `format.hours` style when
+/// that is `decimal` or `clock`.
+pub fn format_hours(h: f64) -> String {
+    let hours = display::hours();
+    match hours.style {
+        HourStyle::Hm => format!("{} ({}h)", format_hm(h), hours.decimal(h)),
+        _ => format_hm(h),
+    }
+}
+
+/// `"s"` unless `n` is 1, for counts in messages.
+pub fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+/// Formats hours as `7h30m`, `7.50h` or `7:30` (`format.hours`).
+pub fn format_hm(h: f64) -> String {
+    display::hours().format(h)
+}
+
+/// A signed difference of hours, e.g. `+1h30m`.
+pub fn delta_hm(d: f64) -> String {
+    let sign = if d < 0.0 { "-" } else { "+" };
+    format!("{sign}{}", format_hm(d.abs()))
+}
+
+/// Reads one log per team member from `dir` (the file name without extension
+/// is the member's name) and prints hours per member and month.
+pub fn team_summary(dir: &Path) -> anyhow::Result<()> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| !n.starts_with('.'))
+        })
+        .collect();
+    files.sort();
+
+    let mut members = Vec::new();
+    let mut table: BTreeMap<String, BTreeMap<usize, f64>> = BTreeMap::new();
+    for path in files {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let sessions = build_sessions(read_events(Some(path))?);
+        for (month, h) in monthly_hours(&sessions) {
+            *table
+                .entry(month)
+                .or_default()
+                .entry(members.len())
+                .or_default() += h;
+        }
+        members.push(name);
+    }
+    if members.is_empty() {
+        anyhow::bail!("no log files in {}", dir.display());
+    }
+    let formats = Formats::load(&Config::load()?)?;
+
+    println!("| month | {} | total |", members.join(" | "));
+    println!("|-------|{}-------|", "------|".repeat(members.len()));
+    for (month, row) in table {
+        let cells: Vec<String> = (0..members.len())
+            .map(|i| format_hm(row.get(&i).copied().unwrap_or(0.0)))
+            .collect();
+        let total: f64 = row.values().sum();
+        println!(
+            "| {} | {} | {} |",
+            formats.month(&month),
+            cells.join(" | "),
+            format_hm(total)
+        );
+    }
+    println!();
+    Ok(())
+}
+
+/// `minutes` rounded up to a whole number of `increment`-minute blocks.
+pub fn billed_minutes(minutes: i64, increment: i64) -> i64 {
+    if increment <= 0 {
+        return minutes;
+    }
+    (minutes + increment - 1) / increment * increment
+}
+
+/// Monthly totals; one row per person and month when several users share
+/// the log. With an `increment` (in minutes), each session is also billed
+/// rounded up to whole increments, and the salary follows the billed time.
+pub fn summary_table(
+    sessions: &[Session],
+    rate: Option<f64>,
+    formats: &Formats,
+    increment: Option<i64>,
+) -> String {
+    let rate = rate.unwrap_or(0.0);
+    let multi_user = users(sessions).len() > 1;
+    let mut rows: BTreeMap<(&str, &str), (i64, i64)> = BTreeMap::new();
+    for s in sessions {
+        let user = if multi_user {
+            s.user.as_deref().unwrap_or_default()
+        } else {
+            ""
+        };
+        let (actual, billed) = rows.entry((&s.date[..7], user)).or_default();
+        *actual += s.minutes();
+        *billed += increment.map_or(s.minutes(), |i| billed_minutes(s.minutes(), i));
+    }
+    let mut head = vec!["month"];
+    if multi_user {
+        head.push("user");
+    }
+    head.push("hours");
+    if increment.is_some() {
+        head.push("billed");
+    }
+    head.push("salary");
+    let rules: Vec<String> = head.iter().map(|h| "-".repeat(h.len() + 2)).collect();
+    let mut out = String::new();
+    writeln!(out, "| {} |", head.join(" | ")).unwrap();
+    writeln!(out, "|{}|", rules.join("|")).unwrap();
+    for ((m, u), (actual, billed)) in rows {
+        let mut cells = vec![formats.month(m)];
+        if multi_user {
+            cells.push(u.to_string());
+        }
+        cells.push(format_hours(actual as f64 / 60.0));
+        if increment.is_some() {
+            cells.push(format_hours(billed as f64 / 60.0));
+        }
+        cells.push(((billed as f64 / 60.0 * rate).round() as u64).to_string());
+        writeln!(out, "| {} |", cells.join(" | ")).unwrap();
+    }
+    out
+}
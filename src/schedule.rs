@@ -0,0 +1,225 @@
+//! Recurring jobs run by `kintai daemon`, configured as
+//!
+//! ```toml
+//! [schedule.monthly-report]
+//! cron = "0 18 L * *"
+//! run = "$KINTAI summary -o ~/reports/$(date +%Y-%m).md"
+//! ```
+//!
+//! `cron` has the usual five fields (minute, hour, day of month, month, day
+//! of week, with `*`, lists, ranges and `/` steps); `L` as the day of month
+//! means its last day. `run` is a shell command, started with `$KINTAI`,
+//! `KINTAI_LOG` and `KINTAI_USER` set so it can call kintai on the same log.
+
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
+use std::{
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::{config::Config, hooks};
+
+#[derive(Clone)]
+struct Field {
+    allowed: Vec<bool>,
+    any: bool,
+}
+
+impl Field {
+    fn parse(spec: &str, min: u32, max: u32) -> anyhow::Result<Field> {
+        let mut allowed = vec![false; max as usize + 1];
+        for part in spec.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((r, s)) => (r, s.parse::<u32>()?),
+                None => (part, 1),
+            };
+            let (lo, hi) = if range == "*" {
+                (min, max)
+            } else if let Some((a, b)) = range.split_once('-') {
+                (a.parse()?, b.parse()?)
+            } else {
+                let v = range.parse()?;
+                (v, if step > 1 { max } else { v })
+            };
+            if lo < min || hi > max || lo > hi || step == 0 {
+                anyhow::bail!("{part:?} is out of range {min}-{max}");
+            }
+            for v in (lo..=hi).step_by(step as usize) {
+                allowed[v as usize] = true;
+            }
+        }
+        Ok(Field {
+            allowed,
+            any: spec == "*",
+        })
+    }
+
+    fn has(&self, v: u32) -> bool {
+        self.allowed.get(v as usize).copied().unwrap_or(false)
+    }
+}
+
+#[derive(Clone)]
+pub struct Cron {
+    minute: Field,
+    hour: Field,
+    /// `None` means `L`, the last day of the month.
+    day: Option<Field>,
+    month: Field,
+    weekday: Field,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> anyhow::Result<Cron> {
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = parts[..] else {
+            anyhow::bail!("expected 5 fields in cron expression {expr:?}");
+        };
+        let err = |e: anyhow::Error| anyhow::anyhow!("cron expression {expr:?}: {e}");
+        Ok(Cron {
+            minute: Field::parse(minute, 0, 59).map_err(err)?,
+            hour: Field::parse(hour, 0, 23).map_err(err)?,
+            day: match day {
+                "L" => None,
+                d => Some(Field::parse(d, 1, 31).map_err(err)?),
+            },
+            month: Field::parse(month, 1, 12).map_err(err)?,
+            // 7 is Sunday too.
+            weekday: {
+                let mut f = Field::parse(weekday, 0, 7).map_err(err)?;
+                if f.has(7) {
+                    f.allowed[0] = true;
+                }
+                f
+            },
+        })
+    }
+
+    pub fn matches(&self, t: DateTime<Local>) -> bool {
+        let dom = match &self.day {
+            None => (t.date_naive() + Duration::days(1)).day() == 1,
+            Some(d) => d.has(t.day()),
+        };
+        let dow = self.weekday.has(t.weekday().num_days_from_sunday());
+        // As in cron, when both day fields are restricted either may match.
+        let day_ok = if self.day.as_ref().is_some_and(|d| d.any) {
+            dow
+        } else if self.weekday.any {
+            dom
+        } else {
+            dom || dow
+        };
+        self.minute.has(t.minute())
+            && self.hour.has(t.hour())
+            && self.month.has(t.month())
+            && day_ok
+    }
+
+    /// The first matching minute after `t`, looking at most a year ahead.
+    pub fn next_after(&self, t: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut t = t.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = t + Duration::days(366);
+        while t < limit {
+            if self.matches(t) {
+                return Some(t);
+            }
+            t += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+pub struct Job {
+    pub name: String,
+    pub cron: Cron,
+    pub cron_expr: String,
+    pub run: String,
+}
+
+/// The `[schedule.<name>]` entries from the config.
+pub fn jobs(config: &Config) -> anyhow::Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for name in config.sections("schedule") {
+        let key = |k: &str| format!("schedule.{name}.{k}");
+        let cron_expr = config
+            .str(&key("cron"))
+            .ok_or_else(|| anyhow::anyhow!("schedule.{name}: missing `cron`"))?;
+        let run = config
+            .str(&key("run"))
+            .ok_or_else(|| anyhow::anyhow!("schedule.{name}: missing `run`"))?;
+        jobs.push(Job {
+            name: name.to_string(),
+            cron: Cron::parse(cron_expr)?,
+            cron_expr: cron_expr.to_string(),
+            run: run.to_string(),
+        });
+    }
+    Ok(jobs)
+}
+
+fn launch(job: &Job, log: &Path, user: Option<&str>) {
+    let mut cmd = hooks::shell(&job.run);
+    cmd.env("KINTAI_LOG", log);
+    if let Ok(exe) = std::env::current_exe() {
+        cmd.env("KINTAI", exe);
+    }
+    if let Some(u) = user {
+        cmd.env("KINTAI_USER", u);
+    }
+    let name = job.name.clone();
+    match cmd.spawn() {
+        Ok(mut child) => {
+            // Reap the child without holding up the scheduler.
+            thread::spawn(move || match child.wait() {
+                Ok(s) if !s.success() => eprintln!("kintai daemon: schedule {name}: {s}"),
+                Err(e) => eprintln!("kintai daemon: schedule {name}: {e}"),
+                _ => {}
+            });
+        }
+        Err(e) => eprintln!("kintai daemon: schedule {name}: {e}"),
+    }
+}
+
+/// Runs `jobs` on their schedules in a background thread.
+pub fn spawn(jobs: Vec<Job>, log: &Path, user: Option<&str>) {
+    if jobs.is_empty() {
+        return;
+    }
+    let log: PathBuf = log.to_path_buf();
+    let user = user.map(str::to_string);
+    thread::spawn(move || {
+        let mut last = Local::now();
+        loop {
+            let Some(next) = jobs.iter().filter_map(|j| j.cron.next_after(last)).min() else {
+                return;
+            };
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            thread::sleep(wait);
+            for job in jobs.iter().filter(|j| j.cron.matches(next)) {
+                launch(job, &log, user.as_deref());
+            }
+            last = next;
+        }
+    });
+}
+
+/// Lists the configured jobs and when each runs next.
+pub fn show() -> anyhow::Result<()> {
+    let jobs = jobs(&Config::load()?)?;
+    if jobs.is_empty() {
+        println!("No [schedule.<name>] entries in the config.");
+    }
+    let now = Local::now();
+    for job in &jobs {
+        let next = job
+            .cron
+            .next_after(now)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        println!(
+            "{}  [{}]  next {next}\n    {}",
+            job.name, job.cron_expr, job.run
+        );
+    }
+    Ok(())
+}
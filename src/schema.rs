@@ -0,0 +1,11 @@
+//! The JSON Schema of `kintai privacy export` output, shipped in
+//! `schema/` and printed by `kintai schema`.
+
+/// Version written as `schema_version` in exports.
+pub const EXPORT_VERSION: u64 = 1;
+
+pub const EXPORT_SCHEMA: &str = include_str!("../schema/export.v1.schema.json");
+
+pub fn print() {
+    print!("{EXPORT_SCHEMA}");
+}
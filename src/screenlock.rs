@@ -0,0 +1,61 @@
+//! Screen lock tracking for the daemon (feature `screenlock`).
+//!
+//! Watches the session bus with `dbus-monitor` for the screensaver's
+//! `ActiveChanged` signal: locking the screen while working starts a break,
+//! and unlocking it ends that break again. Both events are tagged
+//! `auto=screenlock`; breaks started by hand are never ended here.
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::daemon::Daemon;
+
+/// Screensavers announcing lock and unlock (freedesktop, GNOME, MATE, Cinnamon).
+const INTERFACES: [&str; 4] = [
+    "org.freedesktop.ScreenSaver",
+    "org.gnome.ScreenSaver",
+    "org.mate.ScreenSaver",
+    "org.cinnamon.ScreenSaver",
+];
+
+pub fn spawn(daemon: Arc<Mutex<Daemon>>) -> anyhow::Result<()> {
+    let rules = INTERFACES.map(|i| format!("type='signal',interface='{i}',member='ActiveChanged'"));
+    let mut child = Command::new("dbus-monitor")
+        .arg("--session")
+        .args(rules)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to start dbus-monitor: {e}"))?;
+    let stdout = child.stdout.take().expect("piped stdout");
+
+    thread::spawn(move || {
+        // The signal header is followed by its argument on the next line.
+        let mut in_signal = false;
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            let line = line.trim();
+            if line.starts_with("signal ") {
+                in_signal = line.contains("member=ActiveChanged");
+                continue;
+            }
+            let locked = match line {
+                "boolean true" => true,
+                "boolean false" => false,
+                _ => continue,
+            };
+            if !std::mem::take(&mut in_signal) {
+                continue;
+            }
+            if let Err(e) = daemon.lock().unwrap().auto_break(locked, "screenlock") {
+                eprintln!("kintai daemon: screen lock: {e}");
+            }
+        }
+        let _ = child.wait();
+        eprintln!("kintai daemon: dbus-monitor exited; screen lock tracking stopped");
+    });
+    Ok(())
+}
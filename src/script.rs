@@ -0,0 +1,171 @@
+//! `kintai report --script <file.lua>`: custom reports written in Lua, run
+//! by a Lua 5.4 interpreter built into kintai. Before the script runs, these
+//! globals are set:
+//!
+//! - `sessions`: list of `{date, user, start, time_range, minutes, hours,
+//!   content, location, project, refs, breaks}`, where `breaks` maps each
+//!   break kind to its minutes
+//! - `months`: list of `{month, user, minutes, hours}`
+//! - `hm(minutes)`: formats minutes as `7h30m`
+//! - `arg`: the arguments after the script name
+//! - `kintai`: the host API, with `config(key)` for a config value,
+//!   `hours(h)` to format hours in the `format.hours` style, `date(d)` and
+//!   `month(m)` to format labels with `[format]`, and `workday(date)` and
+//!   `expected(date)` from the calendar
+
+use chrono::NaiveDate;
+use mlua::{Lua, Table};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    calendar::Calendar,
+    config::{Config, Value},
+    display::{self, Formats},
+    event::{filter_user, read_events},
+    session::{Session, build_sessions},
+};
+
+const HELPERS: &str = "\
+function hm(minutes)
+  minutes = math.floor(minutes + 0.5)
+  return string.format('%dh%02dm', minutes // 60, minutes % 60)
+end
+";
+
+fn config_value<'lua>(lua: &'lua Lua, value: &Value) -> mlua::Result<mlua::Value<'lua>> {
+    Ok(match value {
+        Value::Str(s) => mlua::Value::String(lua.create_string(s)?),
+        Value::Num(n) => mlua::Value::Number(*n),
+        Value::Bool(b) => mlua::Value::Boolean(*b),
+        Value::List(items) => {
+            let t = lua.create_table()?;
+            for item in items {
+                t.push(config_value(lua, item)?)?;
+            }
+            mlua::Value::Table(t)
+        }
+    })
+}
+
+/// The `kintai` table: config values, display formats and the calendar.
+fn host_api(
+    lua: &Lua,
+    config: Config,
+    formats: Formats,
+    calendar: Calendar,
+) -> mlua::Result<Table<'_>> {
+    let date = |s: &str| NaiveDate::parse_from_str(&s.replace('/', "-"), "%Y-%m-%d").ok();
+    let formats = Rc::new(formats);
+    let calendar = Rc::new(calendar);
+    let api = lua.create_table()?;
+    api.set(
+        "config",
+        lua.create_function(move |lua, key: String| match config.get(&key) {
+            Some(v) => config_value(lua, v),
+            None => Ok(mlua::Value::Nil),
+        })?,
+    )?;
+    api.set(
+        "hours",
+        lua.create_function(|_, h: f64| Ok(display::hours().format(h)))?,
+    )?;
+    let f = formats.clone();
+    api.set(
+        "date",
+        lua.create_function(move |_, d: String| Ok(f.date(&d)))?,
+    )?;
+    api.set(
+        "month",
+        lua.create_function(move |_, m: String| Ok(formats.month(&m)))?,
+    )?;
+    let c = calendar.clone();
+    api.set(
+        "workday",
+        lua.create_function(move |_, d: String| Ok(date(&d).map(|d| c.is_workday(d))))?,
+    )?;
+    api.set(
+        "expected",
+        lua.create_function(move |_, d: String| {
+            Ok(date(&d).and_then(|d| calendar.expected_hours(d)))
+        })?,
+    )?;
+    Ok(api)
+}
+
+/// Sets the globals the script sees.
+fn prelude(
+    lua: &Lua,
+    sessions: &[Session],
+    api: Table,
+    args: &[String],
+    script: &Path,
+) -> mlua::Result<()> {
+    lua.load(HELPERS).set_name("helpers").exec()?;
+    let list = lua.create_table()?;
+    let mut months: BTreeMap<(&str, Option<&str>), i64> = BTreeMap::new();
+    for s in sessions {
+        *months.entry((&s.date[..7], s.user.as_deref())).or_default() += s.minutes();
+        let t = lua.create_table()?;
+        t.set("date", s.date.as_str())?;
+        t.set("user", s.user.as_deref())?;
+        t.set("start", s.start.to_rfc3339())?;
+        t.set("time_range", s.time_range.as_str())?;
+        t.set("minutes", s.minutes())?;
+        t.set("hours", s.minutes() as f64 / 60.0)?;
+        t.set("content", s.content.as_deref())?;
+        t.set("location", s.location.as_deref())?;
+        t.set("project", s.project.as_deref())?;
+        t.set(
+            "refs",
+            lua.create_sequence_from(s.refs.iter().map(String::as_str))?,
+        )?;
+        t.set(
+            "breaks",
+            lua.create_table_from(s.break_minutes.iter().map(|(k, m)| (k.as_str(), *m)))?,
+        )?;
+        list.push(t)?;
+    }
+    lua.globals().set("sessions", list)?;
+    let list = lua.create_table()?;
+    for ((month, user), minutes) in months {
+        let t = lua.create_table()?;
+        t.set("month", month)?;
+        t.set("user", user)?;
+        t.set("minutes", minutes)?;
+        t.set("hours", minutes as f64 / 60.0)?;
+        list.push(t)?;
+    }
+    lua.globals().set("months", list)?;
+    lua.globals().set("kintai", api)?;
+    let arg = lua.create_sequence_from(args.iter().map(String::as_str))?;
+    arg.set(0, script.display().to_string())?;
+    lua.globals().set("arg", arg)?;
+    Ok(())
+}
+
+pub fn run(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    script: &Path,
+    args: &[String],
+) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(script)
+        .map_err(|e| anyhow::anyhow!("{}: {e}", script.display()))?;
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let config = Config::load()?;
+    let formats = Formats::load(&config)?;
+    let calendar = Calendar::load(&config)?;
+    let lua = Lua::new();
+    let result = host_api(&lua, config, formats, calendar)
+        .and_then(|api| prelude(&lua, &sessions, api, args, script))
+        .and_then(|()| {
+            lua.load(source)
+                .set_name(format!("@{}", script.display()))
+                .exec()
+        });
+    result.map_err(|e| anyhow::anyhow!("{} failed: {e}", script.display()))
+}
@@ -0,0 +1,64 @@
+//! `kintai search <text>`: finds the sessions whose description, or the
+//! content of any event during them, contains the text (case-insensitive),
+//! for answering "when did I last work on X?".
+
+use chrono::DateTime;
+use std::{fmt::Write, path::PathBuf};
+
+use crate::{
+    config::Config,
+    correct::apply_corrections,
+    display::Formats,
+    event::{filter_user, read_events},
+    report::{format_hm, plural},
+    session::build_sessions,
+    text,
+};
+
+pub fn search(input: Option<PathBuf>, user: Option<&str>, query: &str) -> anyhow::Result<()> {
+    let needle = text::clean(query).to_lowercase();
+    let matches = |s: Option<&str>| s.is_some_and(|s| s.to_lowercase().contains(&needle));
+    let events = filter_user(read_events(input)?, user);
+    let hits: Vec<DateTime<_>> = apply_corrections(events.clone())
+        .iter()
+        .filter(|e| matches(e.content.as_deref()))
+        .filter_map(|e| DateTime::parse_from_rfc3339(&e.ts).ok())
+        .collect();
+    let formats = Formats::load(&Config::load()?)?;
+
+    let mut out = String::new();
+    writeln!(out, "| date | time | hours | content |").unwrap();
+    writeln!(out, "|------|------|-------|---------|").unwrap();
+    let (mut count, mut minutes, mut last) = (0, 0, None);
+    for s in build_sessions(events) {
+        let end = s.end();
+        if !matches(s.content.as_deref()) && !hits.iter().any(|t| (s.start..=end).contains(t)) {
+            continue;
+        }
+        let date = formats.date(&s.date);
+        writeln!(
+            out,
+            "| {date} | {} | {} | {} |",
+            formats.time_range(&s.time_range),
+            format_hm(s.minutes() as f64 / 60.0),
+            s.content.as_deref().unwrap_or_default()
+        )
+        .unwrap();
+        count += 1;
+        minutes += s.minutes();
+        last = Some(date);
+    }
+    let Some(last) = last else {
+        println!("No sessions match \"{query}\".");
+        return Ok(());
+    };
+    writeln!(
+        out,
+        "\n{count} session{}, {} in total, last on {last}",
+        plural(count),
+        format_hm(minutes as f64 / 60.0)
+    )
+    .unwrap();
+    println!("{out}");
+    Ok(())
+}
@@ -0,0 +1,109 @@
+//! Generates systemd user units for the daemon and status reminders.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::daemon::ShutdownAction;
+
+fn unit_dir() -> anyhow::Result<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .ok_or_else(|| anyhow::anyhow!("cannot locate the config directory (HOME is unset)"))?;
+    Ok(base.join("systemd").join("user"))
+}
+
+/// Global options shared by every `ExecStart=` line.
+fn global_args(exe: &Path, log: &Path, user: Option<&str>) -> String {
+    let mut args = format!("\"{}\" --log \"{}\"", exe.display(), log.display());
+    if let Some(u) = user {
+        args.push_str(&format!(" --user \"{u}\""));
+    }
+    args
+}
+
+fn daemon_unit(global: &str, on_shutdown: ShutdownAction) -> String {
+    format!(
+        "[Unit]
+Description=kintai attendance daemon
+
+[Service]
+ExecStart={global} daemon --on-shutdown {action}
+Restart=on-failure
+# SIGTERM lets the daemon record the shutdown event before exiting.
+KillSignal=SIGTERM
+TimeoutStopSec=10
+
+[Install]
+WantedBy=default.target
+",
+        action = on_shutdown.as_str(),
+    )
+}
+
+fn reminder_service(global: &str) -> String {
+    format!(
+        "[Unit]
+Description=kintai status reminder
+
+[Service]
+Type=oneshot
+ExecStart={global} status --notify
+"
+    )
+}
+
+fn reminder_timer(calendars: &[String]) -> String {
+    let mut unit = String::from("[Unit]\nDescription=kintai status reminder\n\n[Timer]\n");
+    for c in calendars {
+        unit.push_str(&format!("OnCalendar={c}\n"));
+    }
+    unit.push_str("Persistent=false\n\n[Install]\nWantedBy=timers.target\n");
+    unit
+}
+
+/// Writes (or with `dry_run`, prints) the unit files and tells the user how to
+/// enable them.
+pub fn install(
+    log: &Path,
+    user: Option<&str>,
+    on_shutdown: ShutdownAction,
+    remind: &[String],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let exe = env::current_exe()?;
+    // Units run with a different working directory, so the log must be absolute.
+    let log = std::path::absolute(log)?;
+    let global = global_args(&exe, &log, user);
+    let mut units = vec![("kintai.service", daemon_unit(&global, on_shutdown))];
+    if !remind.is_empty() {
+        units.push(("kintai-reminder.service", reminder_service(&global)));
+        units.push(("kintai-reminder.timer", reminder_timer(remind)));
+    }
+
+    if dry_run {
+        for (name, body) in &units {
+            println!("# {name}\n{body}");
+        }
+        return Ok(());
+    }
+
+    let dir = unit_dir()?;
+    fs::create_dir_all(&dir)?;
+    for (name, body) in &units {
+        let path = dir.join(name);
+        fs::write(&path, body)?;
+        println!("Wrote {}", path.display());
+    }
+    println!();
+    println!("Enable with:");
+    println!("  systemctl --user daemon-reload");
+    println!("  systemctl --user enable --now kintai.service");
+    if !remind.is_empty() {
+        println!("  systemctl --user enable --now kintai-reminder.timer");
+    }
+    Ok(())
+}
@@ -0,0 +1,488 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime};
+use clap::ValueEnum;
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use crate::{config::Config, correct::apply_corrections, event::LogEvent, issues, skew};
+
+/// Kind recorded for breaks started without `--kind`.
+pub const DEFAULT_BREAK_KIND: &str = "break";
+
+/// Which day an overnight session is counted on.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Overnight {
+    /// The day the session started
+    #[default]
+    Start,
+    /// The day the session finished
+    End,
+    /// Split at midnight into one row per day
+    Split,
+}
+
+/// What a custom interval (`[intervals.<name>]`) counts as.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CountsAs {
+    /// Tracked on its own, next to work
+    #[default]
+    Separate,
+    /// Worked time, like `start` and `finish`
+    Work,
+    /// A break of kind `<name>`, like `break_start` and `break_end`
+    Break,
+}
+
+/// An event type pair configured under `[intervals.<name>]`, e.g.
+/// `start = "oncall_start"`, `end = "oncall_end"`.
+#[derive(Clone)]
+pub struct IntervalType {
+    pub name: String,
+    pub start: String,
+    pub end: String,
+    pub counts_as: CountsAs,
+}
+
+/// How sessions are paired from events (the `[sessions]` config section).
+#[derive(Clone, Default)]
+pub struct SessionOptions {
+    /// Day overnight sessions are attributed to (`sessions.overnight`).
+    pub overnight: Overnight,
+    /// A `finish` followed by a `start` within this many minutes continues
+    /// the same session (`sessions.merge_gap`).
+    pub merge_gap: Option<i64>,
+    /// Count the gap of a merged session as worked time instead of a break
+    /// (`sessions.merge = "join"`).
+    pub merge_join: bool,
+    /// Custom interval types (`[intervals.<name>]`).
+    pub intervals: Vec<IntervalType>,
+}
+
+impl SessionOptions {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let merge_join = match config.str("sessions.merge") {
+            None | Some("break") => false,
+            Some("join") => true,
+            Some(other) => {
+                anyhow::bail!("sessions.merge must be \"break\" or \"join\", not {other:?}")
+            }
+        };
+        let overnight = match config.str("sessions.overnight") {
+            None => Overnight::default(),
+            Some(v) => Overnight::from_str(v, true).map_err(|_| {
+                anyhow::anyhow!(
+                    "sessions.overnight must be \"start\", \"end\" or \"split\", not {v:?}"
+                )
+            })?,
+        };
+        let mut intervals = Vec::new();
+        for name in config.sections("intervals") {
+            let key = |k: &str| format!("intervals.{name}.{k}");
+            let ty = |k: &str| {
+                config
+                    .str(&key(k))
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow::anyhow!("intervals.{name}: missing `{k}`"))
+            };
+            let counts_as = match config.str(&key("counts_as")) {
+                None => CountsAs::default(),
+                Some(v) => CountsAs::from_str(v, true).map_err(|_| {
+                    anyhow::anyhow!(
+                        "intervals.{name}.counts_as must be \"separate\", \"work\" or \"break\", not {v:?}"
+                    )
+                })?,
+            };
+            intervals.push(IntervalType {
+                name: name.to_string(),
+                start: ty("start")?,
+                end: ty("end")?,
+                counts_as,
+            });
+        }
+        Ok(SessionOptions {
+            overnight,
+            intervals,
+            merge_gap: config
+                .get("sessions.merge_gap")
+                .and_then(|v| v.as_num())
+                .map(|m| m as i64),
+            merge_join,
+        })
+    }
+}
+
+static OPTIONS: OnceLock<SessionOptions> = OnceLock::new();
+
+/// Sets the options used by `build_sessions`; call before any sessions are built.
+pub fn set_options(options: SessionOptions) {
+    let _ = OPTIONS.set(options);
+}
+
+/// The options set with [`set_options`], or the defaults.
+pub fn options() -> &'static SessionOptions {
+    OPTIONS.get_or_init(SessionOptions::default)
+}
+
+/// The configured interval type that `ty` starts or ends, if any.
+pub fn interval_type(ty: &str) -> Option<&'static IntervalType> {
+    options()
+        .intervals
+        .iter()
+        .find(|i| i.start == ty || i.end == ty)
+}
+
+/// The built-in type an event acts as: custom types that count as work or
+/// as a break map to `start`/`finish` or `break_start`/`break_end`, and the
+/// break kind is the interval's name.
+pub fn effective_type(e: &LogEvent) -> (&str, Option<&str>) {
+    let Some(i) = interval_type(&e.ty) else {
+        return (&e.ty, e.field("kind"));
+    };
+    let starts = i.start == e.ty;
+    match (i.counts_as, starts) {
+        (CountsAs::Work, true) => ("start", None),
+        (CountsAs::Work, false) => ("finish", None),
+        (CountsAs::Break, true) => ("break_start", Some(&i.name)),
+        (CountsAs::Break, false) => ("break_end", None),
+        (CountsAs::Separate, _) => (&e.ty, None),
+    }
+}
+
+struct ActiveSession {
+    start: DateTime<FixedOffset>,
+    location: Option<String>,
+    project: Option<String>,
+    breaks: Vec<(DateTime<FixedOffset>, DateTime<FixedOffset>, String)>,
+    last_break_start: Option<(DateTime<FixedOffset>, String)>,
+    /// Descriptions of earlier sessions merged into this one.
+    content: Option<String>,
+}
+
+/// A finished session held back in case the next `start` merges into it.
+struct Finished {
+    active: ActiveSession,
+    finish: DateTime<FixedOffset>,
+    content: Option<String>,
+}
+
+impl Finished {
+    fn into_sessions(self, user: Option<String>, overnight: Overnight) -> Vec<Session> {
+        let a = self.active;
+        let mut intervals = Vec::new();
+        let mut cursor = a.start;
+        for (bs, be, _) in &a.breaks {
+            intervals.push((cursor, *bs));
+            cursor = *be;
+        }
+        intervals.push((cursor, self.finish));
+
+        let day = match overnight {
+            Overnight::End => self.finish,
+            _ => a.start,
+        };
+        if overnight != Overnight::Split || a.start.date_naive() == self.finish.date_naive() {
+            return vec![Session {
+                start: a.start,
+                user,
+                date: day.format("%Y/%m/%d").to_string(),
+                time_range: time_range(&intervals, false),
+                refs: refs(self.content.as_deref()),
+                content: self.content,
+                location: a.location,
+                project: a.project,
+                break_minutes: break_minutes(&a.breaks),
+            }];
+        }
+
+        // One session per calendar day, cutting intervals and breaks at midnight.
+        let mut sessions = Vec::new();
+        let mut day_start = a.start;
+        while day_start < self.finish {
+            let midnight = (day_start.date_naive() + Duration::days(1))
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(*day_start.offset())
+                .unwrap();
+            let day_end = midnight.min(self.finish);
+            let clip = |(s, e): &(DateTime<FixedOffset>, DateTime<FixedOffset>)| {
+                let (s, e) = ((*s).max(day_start), (*e).min(day_end));
+                (s < e).then_some((s, e))
+            };
+            let parts: Vec<_> = intervals.iter().filter_map(clip).collect();
+            let breaks: Vec<_> = a
+                .breaks
+                .iter()
+                .filter_map(|(s, e, kind)| clip(&(*s, *e)).map(|(s, e)| (s, e, kind.clone())))
+                .collect();
+            if !parts.is_empty() {
+                sessions.push(Session {
+                    start: parts[0].0,
+                    user: user.clone(),
+                    date: day_start.format("%Y/%m/%d").to_string(),
+                    // Only a part that runs up to midnight ends at 24:00; a
+                    // break across midnight leaves the day's last part earlier.
+                    time_range: time_range(&parts, parts.last().map(|p| p.1) == Some(midnight)),
+                    content: self.content.clone(),
+                    refs: refs(self.content.as_deref()),
+                    location: a.location.clone(),
+                    project: a.project.clone(),
+                    break_minutes: break_minutes(&breaks),
+                });
+            }
+            day_start = midnight;
+        }
+        sessions
+    }
+}
+
+fn refs(content: Option<&str>) -> Vec<String> {
+    content.map(|c| issues::get().refs(c)).unwrap_or_default()
+}
+
+/// Formats intervals as `HH:MM~HH:MM,...`; with `to_midnight` the last one
+/// ends at `24:00`.
+fn time_range(
+    intervals: &[(DateTime<FixedOffset>, DateTime<FixedOffset>)],
+    to_midnight: bool,
+) -> String {
+    let last = intervals.len().saturating_sub(1);
+    intervals
+        .iter()
+        .enumerate()
+        .map(|(i, (s, e))| {
+            let end = if to_midnight && i == last {
+                "24:00".to_string()
+            } else {
+                e.format("%H:%M").to_string()
+            };
+            format!("{}~{end}", s.format("%H:%M"))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn break_minutes(
+    breaks: &[(DateTime<FixedOffset>, DateTime<FixedOffset>, String)],
+) -> BTreeMap<String, i64> {
+    let mut minutes = BTreeMap::new();
+    for (bs, be, kind) in breaks {
+        *minutes.entry(kind.clone()).or_default() += (*be - *bs).num_minutes();
+    }
+    minutes
+}
+
+pub struct Session {
+    pub start: DateTime<FixedOffset>,
+    pub user: Option<String>,
+    pub date: String,
+    pub time_range: String,
+    pub content: Option<String>,
+    /// Issue IDs mentioned in `content` (see [`issues`]).
+    pub refs: Vec<String>,
+    /// Where the session was worked (`location=` on `start`).
+    pub location: Option<String>,
+    /// Project the session was worked on (`project=` on `start`).
+    pub project: Option<String>,
+    /// Break minutes per kind (`kind=` on `break_start`, e.g. `lunch`).
+    pub break_minutes: BTreeMap<String, i64>,
+}
+
+/// Minutes since midnight of `HH:MM`, accepting `24:00` for the end of a day.
+pub fn minute_of_day(s: &str) -> Option<i64> {
+    if s == "24:00" {
+        return Some(24 * 60);
+    }
+    let t = NaiveTime::parse_from_str(s, "%H:%M").ok()?;
+    Some((t - NaiveTime::MIN).num_minutes())
+}
+
+impl Session {
+    /// The `HH:MM~HH:MM` parts of `time_range` as minutes since midnight of
+    /// `date`; an end before its start is on the next day.
+    pub fn parts(&self) -> Vec<(&str, i64, i64)> {
+        self.time_range
+            .split(',')
+            .filter_map(|part| {
+                let (a, b) = part.split_once('~')?;
+                let (a, mut b) = (minute_of_day(a)?, minute_of_day(b)?);
+                if b < a {
+                    b += 24 * 60;
+                }
+                Some((part, a, b))
+            })
+            .collect()
+    }
+
+    /// Worked minutes, summed over the `HH:MM~HH:MM` parts of `time_range`.
+    pub fn minutes(&self) -> i64 {
+        self.parts().iter().map(|(_, a, b)| b - a).sum()
+    }
+
+    /// When the session ended: `start` plus the span of its parts.
+    pub fn end(&self) -> DateTime<FixedOffset> {
+        let parts = self.parts();
+        let span = match (parts.first(), parts.last()) {
+            (Some(first), Some(last)) => last.2 - first.1,
+            _ => 0,
+        };
+        self.start + Duration::minutes(span)
+    }
+
+    /// First start and last end of `time_range`.
+    pub fn span(&self) -> Option<(NaiveTime, NaiveTime)> {
+        let first = self.time_range.split(',').next()?.split_once('~')?.0;
+        let last = self.time_range.rsplit(',').next()?.split_once('~')?.1;
+        Some((
+            NaiveTime::parse_from_str(first, "%H:%M").ok()?,
+            NaiveTime::parse_from_str(last, "%H:%M").ok()?,
+        ))
+    }
+}
+
+/// Pairs events into sessions, after applying `amend`/`void` corrections.
+/// Events of different users (the `user=` field)
+/// are paired independently, so one log can be shared by several people.
+/// Sessions going back in time are left out (see [`skew`]).
+pub fn build_sessions(events: Vec<LogEvent>) -> Vec<Session> {
+    let mut by_user: BTreeMap<Option<String>, Vec<LogEvent>> = BTreeMap::new();
+    for e in skew::exclude(apply_corrections(events)) {
+        by_user
+            .entry(e.user().map(str::to_string))
+            .or_default()
+            .push(e);
+    }
+    let mut sessions: Vec<Session> = by_user
+        .into_iter()
+        .flat_map(|(user, events)| build_user_sessions(events, user))
+        .collect();
+    sessions.sort_by_key(|s| s.start);
+    sessions
+}
+
+/// A tracked interval of a custom type that counts as neither work nor a
+/// break (`counts_as = "separate"`), e.g. on-call duty.
+pub struct Interval {
+    /// The `[intervals.<name>]` it belongs to.
+    pub name: String,
+    pub user: Option<String>,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl Interval {
+    pub fn minutes(&self) -> i64 {
+        (self.end - self.start).num_minutes()
+    }
+}
+
+/// Pairs the start and end events of separate custom intervals, per user
+/// and interval type, after applying corrections. A start while one is
+/// already open is ignored, as is an end without a start.
+pub fn build_intervals(events: Vec<LogEvent>) -> Vec<Interval> {
+    let mut events = apply_corrections(events);
+    events.sort_by_key(|e| e.ts.clone());
+    let mut open: BTreeMap<(Option<String>, &str), DateTime<FixedOffset>> = BTreeMap::new();
+    let mut intervals = Vec::new();
+    for e in events {
+        let Some(i) = interval_type(&e.ty).filter(|i| i.counts_as == CountsAs::Separate) else {
+            continue;
+        };
+        let Ok(dt) = DateTime::parse_from_rfc3339(&e.ts) else {
+            continue;
+        };
+        let key = (e.user().map(str::to_string), i.name.as_str());
+        if e.ty == i.start {
+            open.entry(key).or_insert(dt);
+        } else if let Some(start) = open.remove(&key) {
+            intervals.push(Interval {
+                name: i.name.clone(),
+                user: key.0,
+                start,
+                end: dt,
+            });
+        }
+    }
+    intervals
+}
+
+/// Distinct users appearing in `sessions`, in name order.
+pub fn users(sessions: &[Session]) -> Vec<&str> {
+    let mut users: Vec<&str> = sessions.iter().filter_map(|s| s.user.as_deref()).collect();
+    users.sort_unstable();
+    users.dedup();
+    users
+}
+
+fn build_user_sessions(mut events: Vec<LogEvent>, user: Option<String>) -> Vec<Session> {
+    events.sort_by_key(|e| e.ts.clone());
+    let options = options();
+    let mut sessions = Vec::new();
+    let mut active: Option<ActiveSession> = None;
+    let mut finished: Option<Finished> = None;
+
+    for e in events {
+        // `kintai validate` reports these; everything else leaves them out.
+        let Ok(dt) = DateTime::parse_from_rfc3339(&e.ts) else {
+            continue;
+        };
+        let (ty, kind) = effective_type(&e);
+        match ty {
+            "start" => {
+                if active.is_none()
+                    && let Some(f) = finished.take()
+                {
+                    if options
+                        .merge_gap
+                        .is_some_and(|gap| (dt - f.finish).num_minutes() <= gap)
+                    {
+                        let mut a = f.active;
+                        if !options.merge_join {
+                            a.breaks
+                                .push((f.finish, dt, DEFAULT_BREAK_KIND.to_string()));
+                        }
+                        a.content = f.content;
+                        active = Some(a);
+                        continue;
+                    }
+                    sessions.extend(f.into_sessions(user.clone(), options.overnight));
+                }
+                active = Some(ActiveSession {
+                    start: dt,
+                    location: e.field("location").map(str::to_string),
+                    project: e.field("project").map(str::to_string),
+                    breaks: Vec::new(),
+                    last_break_start: None,
+                    content: None,
+                });
+            }
+            "break_start" => {
+                if let Some(a) = active.as_mut() {
+                    let kind = kind.unwrap_or(DEFAULT_BREAK_KIND);
+                    a.last_break_start = Some((dt, kind.to_string()));
+                }
+            }
+            "break_end" => {
+                if let Some(a) = active.as_mut()
+                    && let Some((bs, kind)) = a.last_break_start.take()
+                {
+                    a.breaks.push((bs, dt, kind));
+                }
+            }
+            "finish" => {
+                if let Some(mut a) = active.take() {
+                    let content = match (a.content.take(), e.content) {
+                        (Some(before), Some(now)) => Some(format!("{before}; {now}")),
+                        (before, now) => now.or(before),
+                    };
+                    finished = Some(Finished {
+                        active: a,
+                        finish: dt,
+                        content,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(f) = finished {
+        sessions.extend(f.into_sessions(user, options.overnight));
+    }
+    sessions
+}
@@ -0,0 +1,153 @@
+//! Planned shifts and how the actual sessions compare. The recurring plan
+//! is configured per weekday (`[shifts] mon = "09:00-17:00"`); single days
+//! are planned or cancelled with `type=shift date=... plan=...` events,
+//! which override the weekly pattern.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use std::{collections::BTreeMap, fmt::Write, path::Path, path::PathBuf};
+
+use crate::{
+    config::Config,
+    correct::apply_corrections,
+    event::{LogEvent, filter_user, read_events, record},
+    session::build_sessions,
+    state,
+};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+type Shift = (NaiveTime, NaiveTime);
+
+/// Parses `HH:MM-HH:MM`; `off` (no shift) is `None`.
+fn parse_plan(s: &str) -> anyhow::Result<Option<Shift>> {
+    if s == "off" {
+        return Ok(None);
+    }
+    let parsed = s.split_once('-').and_then(|(a, b)| {
+        Some((
+            NaiveTime::parse_from_str(a.trim(), "%H:%M").ok()?,
+            NaiveTime::parse_from_str(b.trim(), "%H:%M").ok()?,
+        ))
+    });
+    parsed
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("expected a shift as HH:MM-HH:MM or `off`, got {s:?}"))
+}
+
+struct Plan {
+    weekly: [Option<Shift>; 7],
+    dates: BTreeMap<NaiveDate, Option<Shift>>,
+}
+
+impl Plan {
+    fn load(config: &Config, events: &[LogEvent]) -> anyhow::Result<Plan> {
+        let mut weekly = [None; 7];
+        for (i, day) in WEEKDAYS.iter().enumerate() {
+            if let Some(p) = config.str(&format!("shifts.{day}")) {
+                weekly[i] = parse_plan(p).map_err(|e| anyhow::anyhow!("shifts.{day}: {e}"))?;
+            }
+        }
+        let mut events = apply_corrections(events.to_vec());
+        events.sort_by(|a, b| a.ts.cmp(&b.ts));
+        let mut dates = BTreeMap::new();
+        for e in events.iter().filter(|e| e.ty == "shift") {
+            let date = e
+                .field("date")
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+            if let (Some(date), Some(plan)) = (date, e.field("plan")) {
+                dates.insert(date, parse_plan(plan)?);
+            }
+        }
+        Ok(Plan { weekly, dates })
+    }
+
+    fn on(&self, date: NaiveDate) -> Option<Shift> {
+        match self.dates.get(&date) {
+            Some(plan) => *plan,
+            None => self.weekly[date.weekday().num_days_from_monday() as usize],
+        }
+    }
+}
+
+pub fn add(log: &Path, user: Option<&str>, date: &str, plan: &str) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a date as YYYY-MM-DD, got {date:?}"))?;
+    parse_plan(plan)?;
+    record(
+        Some(log),
+        user,
+        "shift",
+        &[("date", date), ("plan", plan)],
+        None,
+        false,
+    )?;
+    println!("Planned {date}: {plan}.");
+    Ok(())
+}
+
+fn delta(planned: NaiveTime, actual: NaiveTime) -> String {
+    let m = (actual - planned).num_minutes();
+    let sign = if m < 0 { '-' } else { '+' };
+    format!("{sign}{}:{:02}", m.abs() / 60, m.abs() % 60)
+}
+
+fn range(s: Shift) -> String {
+    format!("{}~{}", s.0.format("%H:%M"), s.1.format("%H:%M"))
+}
+
+/// Planned vs actual start and finish for each day of `month` (default: the
+/// current month) up to today.
+pub fn diff(input: Option<PathBuf>, user: Option<&str>, month: Option<&str>) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let plan = Plan::load(&Config::load()?, &events)?;
+    let today = state::now().date_naive();
+    let first = match month {
+        Some(m) => NaiveDate::parse_from_str(&format!("{m}-01"), "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("expected a month as YYYY-MM, got {m:?}"))?,
+        None => today.with_day(1).unwrap_or(today),
+    };
+
+    let mut actual: BTreeMap<String, Shift> = BTreeMap::new();
+    for s in build_sessions(events) {
+        if let Some((a, b)) = s.span() {
+            actual
+                .entry(s.date.replace('/', "-"))
+                .and_modify(|d| *d = (d.0.min(a), d.1.max(b)))
+                .or_insert((a, b));
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "| date | planned | actual | start | finish | note |").unwrap();
+    writeln!(out, "|------|---------|--------|-------|--------|------|").unwrap();
+    let (mut missed, mut unplanned) = (0, 0);
+    let mut date = first;
+    while date.month() == first.month() && date <= today {
+        let key = date.format("%Y-%m-%d").to_string();
+        let row = match (plan.on(date), actual.get(&key)) {
+            (Some(p), Some(a)) => Some(format!(
+                "| {key} | {} | {} | {} | {} | |",
+                range(p),
+                range(*a),
+                delta(p.0, a.0),
+                delta(p.1, a.1)
+            )),
+            (Some(p), None) => {
+                missed += 1;
+                Some(format!("| {key} | {} | - | | | missed |", range(p)))
+            }
+            (None, Some(a)) => {
+                unplanned += 1;
+                Some(format!("| {key} | - | {} | | | unplanned |", range(*a)))
+            }
+            (None, None) => None,
+        };
+        if let Some(row) = row {
+            writeln!(out, "{row}").unwrap();
+        }
+        date += Duration::days(1);
+    }
+    println!("{out}");
+    println!("{missed} missed shifts, {unplanned} unplanned days.");
+    Ok(())
+}
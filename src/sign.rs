@@ -0,0 +1,52 @@
+//! Detached signatures over generated reports, made with an external tool
+//! configured under `[sign]`: `gpg` (default) or `ssh` (`ssh-keygen -Y sign`).
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::config::Config;
+
+/// Signs `path` and returns the signature file's path.
+pub fn sign(path: &Path) -> anyhow::Result<PathBuf> {
+    let config = Config::load()?;
+    let key = config.str("sign.key");
+    let (mut cmd, sig) = match config.str("sign.tool").unwrap_or("gpg") {
+        "gpg" => {
+            let sig = append_ext(path, "asc");
+            let mut cmd = Command::new("gpg");
+            cmd.args(["--batch", "--yes", "--armor", "--detach-sign"]);
+            if let Some(k) = key {
+                cmd.args(["--local-user", k]);
+            }
+            cmd.arg("--output").arg(&sig).arg(path);
+            (cmd, sig)
+        }
+        "ssh" => {
+            let key = key.ok_or_else(|| {
+                anyhow::anyhow!("sign.tool = \"ssh\" needs sign.key set to a private key file")
+            })?;
+            let mut cmd = Command::new("ssh-keygen");
+            cmd.args(["-Y", "sign", "-n", "kintai", "-f", key])
+                .arg(path);
+            (cmd, append_ext(path, "sig"))
+        }
+        other => anyhow::bail!("unknown sign.tool {other:?}; use \"gpg\" or \"ssh\""),
+    };
+    let status = cmd
+        .status()
+        .map_err(|e| anyhow::anyhow!("cannot run {:?}: {e}", cmd.get_program()))?;
+    if !status.success() {
+        anyhow::bail!("{:?} failed ({status})", cmd.get_program());
+    }
+    println!("Signature: {}", sig.display());
+    Ok(sig)
+}
+
+fn append_ext(path: &Path, ext: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".");
+    s.push(ext);
+    PathBuf::from(s)
+}
@@ -0,0 +1,219 @@
+//! Sessions whose events go back in time: a finish recorded before its
+//! start, or a break ending before it began. They come from changes of the
+//! system clock or mistyped times, and would pair into negative or
+//! absurd durations, so they are reported and left out of sessions until
+//! corrected. New events are checked against the clock before they are
+//! recorded, to catch a wrong system clock early.
+
+use chrono::{DateTime, Duration, FixedOffset};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    config::Config,
+    event::{LogEvent, filter_user, read_log},
+    session::effective_type,
+};
+
+/// An event of a session recorded after `earlier` but with an earlier time.
+pub struct Skew {
+    pub earlier: LogEvent,
+    pub later: LogEvent,
+    /// IDs of all events of the session, which is left out of reports.
+    pub session: Vec<String>,
+}
+
+/// A session being followed through the log.
+struct Open<'a> {
+    ids: Vec<String>,
+    last: &'a LogEvent,
+    /// Index of its skew in the result.
+    skew: Option<usize>,
+}
+
+fn when(e: &LogEvent) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(&e.ts).ok()
+}
+
+/// The skewed sessions of `events` (after corrections), in log order. Events
+/// of a session are compared in the order they were recorded, per user;
+/// events recorded after the session finished, such as breaks added with
+/// `kintai fix`, are not.
+pub fn find(events: &[LogEvent]) -> Vec<Skew> {
+    let mut open: BTreeMap<Option<&str>, Open> = BTreeMap::new();
+    let mut skews: Vec<Skew> = Vec::new();
+    for e in events {
+        let (ty, _) = effective_type(e);
+        let user = e.user();
+        // Entries added after the fact belong to sessions recorded earlier.
+        if e.field("manual") == Some("1") {
+            continue;
+        }
+        if ty == "start" {
+            // A start without a finish before it leaves that session running.
+            let session = Open {
+                ids: vec![e.id().to_string()],
+                last: e,
+                skew: None,
+            };
+            if let Some(Open {
+                ids, skew: Some(i), ..
+            }) = open.insert(user, session)
+            {
+                skews[i].session = ids;
+            }
+            continue;
+        }
+        if !matches!(ty, "break_start" | "break_end" | "finish") {
+            continue;
+        }
+        let Some(session) = open.get_mut(&user) else {
+            continue;
+        };
+        session.ids.push(e.id().to_string());
+        if session.skew.is_none()
+            && let (Some(a), Some(b)) = (when(session.last), when(e))
+            && b < a
+        {
+            session.skew = Some(skews.len());
+            skews.push(Skew {
+                earlier: session.last.clone(),
+                later: e.clone(),
+                session: Vec::new(),
+            });
+        }
+        session.last = e;
+        if ty == "finish"
+            && let Some(Open {
+                ids, skew: Some(i), ..
+            }) = open.remove(&user)
+        {
+            skews[i].session = ids;
+        }
+    }
+    for session in open.into_values() {
+        if let Some(i) = session.skew {
+            skews[i].session = session.ids;
+        }
+    }
+    skews
+}
+
+/// `events` without the events of skewed sessions.
+pub fn exclude(events: Vec<LogEvent>) -> Vec<LogEvent> {
+    let skews = find(&events);
+    if skews.is_empty() {
+        return events;
+    }
+    let skewed: HashSet<&str> = skews
+        .iter()
+        .flat_map(|s| s.session.iter().map(String::as_str))
+        .collect();
+    events
+        .into_iter()
+        .filter(|e| !skewed.contains(e.id()))
+        .collect()
+}
+
+impl Skew {
+    /// What is wrong, and how to correct it.
+    pub fn describe(&self) -> String {
+        let (Some(a), Some(b)) = (when(&self.earlier), when(&self.later)) else {
+            return String::new();
+        };
+        let (ty, earlier_ty) = (
+            effective_type(&self.later).0,
+            effective_type(&self.earlier).0,
+        );
+        let time = |t: DateTime<FixedOffset>| {
+            if t.date_naive() == a.date_naive() {
+                t.format("%H:%M").to_string()
+            } else {
+                t.format("%Y-%m-%d %H:%M").to_string()
+            }
+        };
+        let mut text = format!(
+            "{}: {ty} at {} was recorded after {earlier_ty} at {} ({} minutes earlier); \
+             the system clock may have been changed, or a time mistyped",
+            a.format("%Y/%m/%d"),
+            time(b),
+            time(a),
+            (a - b).num_minutes()
+        );
+        // A finish at 06:00 after a start at 09:00 is likely 18:00, and one at
+        // 01:00 after 23:00 likely the next day.
+        // Breaks have later events to fit between, so are not guessed.
+        let guess = [Duration::hours(12), Duration::days(1)]
+            .into_iter()
+            .map(|d| b + d)
+            .find(|t| ty == "finish" && *t >= a && *t - a < Duration::hours(16));
+        match guess {
+            Some(t) => text.push_str(&format!(
+                ". Did you mean {}? `kintai amend {} --ts {}`",
+                time(t),
+                self.later.id(),
+                t.format("\"%Y-%m-%d %H:%M\"")
+            )),
+            None => text.push_str(&format!(
+                ". Correct it with `kintai amend {} --ts <time>` or `kintai fix`",
+                self.later.id()
+            )),
+        }
+        text.push_str("; the session is left out of reports until then.");
+        text
+    }
+}
+
+/// Default `clock.max_jump_days`.
+const MAX_JUMP_DAYS: f64 = 90.0;
+
+/// Checks the system clock before an event at `ts` is recorded to `log`,
+/// against the last event recorded there (for `user`). A time before it
+/// needs `force`: the clock has likely been set back. A jump forward of more
+/// than `clock.max_jump_days` only warns, since a long holiday looks the
+/// same as a clock set far ahead.
+pub fn check_clock(log: &Path, user: Option<&str>, ts: &str, force: bool) -> anyhow::Result<()> {
+    let Some(now) = DateTime::parse_from_rfc3339(ts).ok() else {
+        return Ok(());
+    };
+    let events = filter_user(read_log(log)?, user);
+    let Some((last, at)) = events
+        .iter()
+        .rev()
+        .filter(|e| e.field("manual") != Some("1"))
+        .find_map(|e| Some((e, when(e)?)))
+    else {
+        return Ok(());
+    };
+    // Clocks synchronised over the network may step back by a few seconds.
+    if now < at - Duration::minutes(1) {
+        if !force {
+            anyhow::bail!(
+                "the system clock says {}, before the last event in {} ({} at {}); \
+                 check the clock, or use --force to record anyway",
+                now.format("%Y-%m-%d %H:%M"),
+                log.display(),
+                last.ty,
+                at.format("%Y-%m-%d %H:%M")
+            );
+        }
+        return Ok(());
+    }
+    let max_days = Config::load()?
+        .get("clock.max_jump_days")
+        .and_then(|v| v.as_num())
+        .unwrap_or(MAX_JUMP_DAYS);
+    let days = (now - at).num_days();
+    if days as f64 > max_days {
+        eprintln!(
+            "warning: the system clock says {}, {days} days after the last event ({} at {}); \
+             check the clock if that is wrong",
+            now.format("%Y-%m-%d %H:%M"),
+            last.ty,
+            at.format("%Y-%m-%d %H:%M")
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,393 @@
+//! Current tracking state (clocked in, on break, clocked out), derived from the
+//! tail of the log and cached so status-bar polling stays cheap.
+
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    archive,
+    config::{self, Config},
+    correct::apply_corrections,
+    event::{LogEvent, filter_user, read_log},
+    paths,
+    session::{self, build_sessions, effective_type},
+    time,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Idle,
+    Working,
+    OnBreak,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Idle => "idle",
+            Phase::Working => "working",
+            Phase::OnBreak => "break",
+        }
+    }
+
+    /// Exit code of `kintai status --quiet`.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Phase::Working => 0,
+            Phase::OnBreak => 1,
+            Phase::Idle => 2,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Phase> {
+        match s {
+            "idle" => Some(Phase::Idle),
+            "working" => Some(Phase::Working),
+            "break" => Some(Phase::OnBreak),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TrackingState {
+    pub phase: Phase,
+    pub session_start: Option<DateTime<FixedOffset>>,
+    pub break_since: Option<DateTime<FixedOffset>>,
+    /// Seconds spent in breaks that have already ended.
+    pub break_secs: i64,
+    /// When the current stretch of work without a break began (the session
+    /// start or the end of the last break).
+    pub resumed: Option<DateTime<FixedOffset>>,
+}
+
+impl TrackingState {
+    pub fn idle() -> Self {
+        TrackingState {
+            phase: Phase::Idle,
+            session_start: None,
+            break_since: None,
+            break_secs: 0,
+            resumed: None,
+        }
+    }
+
+    /// Replays events in timestamp order and returns the state after the last one.
+    pub fn from_events(events: Vec<LogEvent>) -> Self {
+        let mut events = apply_corrections(events);
+        events.sort_by_key(|e| e.ts.clone());
+        let mut st = TrackingState::idle();
+        for e in events {
+            let Ok(dt) = DateTime::parse_from_rfc3339(&e.ts) else {
+                continue;
+            };
+            match effective_type(&e).0 {
+                "start" => {
+                    st = TrackingState {
+                        phase: Phase::Working,
+                        session_start: Some(dt),
+                        break_since: None,
+                        break_secs: 0,
+                        resumed: Some(dt),
+                    };
+                }
+                "break_start" if st.phase == Phase::Working => {
+                    st.phase = Phase::OnBreak;
+                    st.break_since = Some(dt);
+                }
+                "break_end" if st.phase == Phase::OnBreak => {
+                    if let Some(bs) = st.break_since.take() {
+                        st.break_secs += (dt - bs).num_seconds();
+                    }
+                    st.phase = Phase::Working;
+                    st.resumed = Some(dt);
+                }
+                "finish" => st = TrackingState::idle(),
+                _ => {}
+            }
+        }
+        st
+    }
+
+    /// Seconds worked in the current session as of `now`, excluding breaks.
+    pub fn worked_secs(&self, now: DateTime<FixedOffset>) -> i64 {
+        let Some(start) = self.session_start else {
+            return 0;
+        };
+        let mut secs = (now - start).num_seconds() - self.break_secs;
+        if let Some(bs) = self.break_since {
+            secs -= (now - bs).num_seconds();
+        }
+        secs.max(0)
+    }
+
+    /// Seconds worked since the last break (or the session start), while
+    /// working.
+    pub fn continuous_secs(&self, now: DateTime<FixedOffset>) -> i64 {
+        match (self.phase, self.resumed.or(self.session_start)) {
+            (Phase::Working, Some(since)) => (now - since).num_seconds().max(0),
+            _ => 0,
+        }
+    }
+
+    /// Serialises the state as logfmt fields (the cache and daemon wire format).
+    pub fn to_fields(&self) -> String {
+        let mut line = format!(
+            "phase={} break_secs={}",
+            self.phase.as_str(),
+            self.break_secs
+        );
+        if let Some(s) = self.session_start {
+            line.push_str(&format!(" start={}", s.to_rfc3339()));
+        }
+        if let Some(b) = self.break_since {
+            line.push_str(&format!(" break_since={}", b.to_rfc3339()));
+        }
+        if let Some(r) = self.resumed {
+            line.push_str(&format!(" resumed={}", r.to_rfc3339()));
+        }
+        line
+    }
+
+    pub fn from_fields(line: &str) -> Option<Self> {
+        let mut st = TrackingState::idle();
+        for kv in line.split_whitespace() {
+            let (k, v) = kv.split_once('=')?;
+            match k {
+                "phase" => st.phase = Phase::parse(v)?,
+                "break_secs" => st.break_secs = v.parse().ok()?,
+                "start" => st.session_start = DateTime::parse_from_rfc3339(v).ok(),
+                "break_since" => st.break_since = DateTime::parse_from_rfc3339(v).ok(),
+                "resumed" => st.resumed = DateTime::parse_from_rfc3339(v).ok(),
+                _ => {}
+            }
+        }
+        Some(st)
+    }
+
+    /// Human-readable one-line description, as printed by `status`.
+    pub fn describe(&self, now: DateTime<FixedOffset>) -> String {
+        let worked = clock(self.worked_secs(now));
+        match (self.phase, self.session_start, self.break_since) {
+            (Phase::Working, Some(s), _) => {
+                format!("working since {} ({worked} worked)", s.format("%H:%M"))
+            }
+            (Phase::OnBreak, _, Some(b)) => {
+                format!("on break since {} ({worked} worked)", b.format("%H:%M"))
+            }
+            _ => "clocked out".to_string(),
+        }
+    }
+}
+
+/// Size and modification time of the log, used to detect appends cheaply.
+pub type LogStamp = (u64, u128);
+
+pub fn log_stamp(log: &Path) -> anyhow::Result<Option<LogStamp>> {
+    let meta = match fs::metadata(log) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    Ok(Some((meta.len(), mtime)))
+}
+
+/// What caches of `log` are computed from: the log and config files, and
+/// the options that change how events are read from them. A cache line
+/// starts with this stamp and is valid while it matches.
+pub fn inputs_stamp(log: &Path) -> anyhow::Result<Option<String>> {
+    let Some((len, mtime)) = log_stamp(log)? else {
+        return Ok(None);
+    };
+    let config = match log_stamp(&config::path())? {
+        Some((len, mtime)) => format!("{len}:{mtime}"),
+        None => "none".to_string(),
+    };
+    let overnight = session::options()
+        .overnight
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default();
+    Ok(Some(format!(
+        "len={len} mtime={mtime} config={config} overnight={overnight} archived={} ",
+        archive::included()
+    )))
+}
+
+/// Reads the whole log and replays `user`'s events, bypassing the cache.
+pub fn compute(log: &Path, user: Option<&str>) -> anyhow::Result<TrackingState> {
+    Ok(TrackingState::from_events(filter_user(
+        read_log(log)?,
+        user,
+    )))
+}
+
+/// Stable identifier of a log (and user), so caches and sockets of different
+/// logs never mix.
+pub fn log_key(log: &Path, user: Option<&str>) -> String {
+    let mut h = DefaultHasher::new();
+    fs::canonicalize(log)
+        .unwrap_or_else(|_| log.to_path_buf())
+        .hash(&mut h);
+    user.hash(&mut h);
+    format!("{:016x}", h.finish())
+}
+
+fn cache_path(log: &Path, user: Option<&str>) -> PathBuf {
+    paths::cache_dir().join(format!("state-{}", log_key(log, user)))
+}
+
+/// Loads the tracking state for `log`. A running daemon answers first;
+/// otherwise the cached state is reused as long as the log, the config (whose
+/// `[intervals]` decide what events mean) and the options reading the log
+/// are unchanged.
+pub fn load(log: &Path, user: Option<&str>) -> anyhow::Result<TrackingState> {
+    #[cfg(unix)]
+    if let Some(st) = crate::daemon::query(log, user) {
+        return Ok(st);
+    }
+    let Some(stamp) = inputs_stamp(log)? else {
+        return Ok(TrackingState::idle());
+    };
+    let cache = cache_path(log, user);
+    if let Ok(cached) = fs::read_to_string(&cache)
+        && let Some(fields) = cached.trim().strip_prefix(&stamp)
+        && let Some(st) = TrackingState::from_fields(fields)
+    {
+        return Ok(st);
+    }
+
+    let st = compute(log, user)?;
+    // The cache is an optimisation only; failing to write it is not an error.
+    if let Some(dir) = cache.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(&cache, format!("{stamp}{}", st.to_fields()));
+    Ok(st)
+}
+
+/// The current time, from the configured [`time::Clock`].
+pub fn now() -> DateTime<FixedOffset> {
+    time::clock().now()
+}
+
+/// Formats seconds as `H:MM`.
+pub fn clock(secs: i64) -> String {
+    format!("{}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+/// Prints a one-line status segment for shell prompts and status bars.
+/// Prints nothing while clocked out so the segment can collapse.
+pub fn prompt(log: &Path, user: Option<&str>, icon: &str) -> anyhow::Result<()> {
+    let st = load(log, user)?;
+    let worked = clock(st.worked_secs(now()));
+    match st.phase {
+        Phase::Idle => {}
+        Phase::Working => println!("{icon} {worked}"),
+        Phase::OnBreak => println!("{icon} {worked} (break)"),
+    }
+    Ok(())
+}
+
+pub const DEFAULT_MAX_CONTINUOUS_HOURS: f64 = 6.0;
+
+/// How long one may work without a break (`breaks.max_continuous_hours`).
+pub fn max_continuous_hours(config: &Config) -> f64 {
+    config
+        .get("breaks.max_continuous_hours")
+        .and_then(|v| v.as_num())
+        .unwrap_or(DEFAULT_MAX_CONTINUOUS_HOURS)
+}
+
+/// A warning when the current stretch of work is longer than `limit` hours.
+pub fn break_warning(st: &TrackingState, now: DateTime<FixedOffset>, limit: f64) -> Option<String> {
+    let secs = st.continuous_secs(now);
+    (secs as f64 > limit * 3600.0).then(|| {
+        format!(
+            "No break for {} (limit {limit}h). Take a break!",
+            clock(secs)
+        )
+    })
+}
+
+/// Seconds worked today: finished sessions that started today plus the
+/// running one.
+fn today_secs(
+    log: &Path,
+    user: Option<&str>,
+    st: &TrackingState,
+    now: DateTime<FixedOffset>,
+) -> anyhow::Result<i64> {
+    let today = now.format("%Y/%m/%d").to_string();
+    let finished: i64 = build_sessions(filter_user(read_log(log)?, user))
+        .iter()
+        .filter(|s| s.date == today)
+        .map(|s| s.minutes() * 60)
+        .sum();
+    Ok(finished + st.worked_secs(now))
+}
+
+/// Shows `text` as a desktop notification.
+pub fn desktop_notify(text: &str, critical: bool) -> anyhow::Result<()> {
+    let urgency = if critical { "critical" } else { "normal" };
+    std::process::Command::new("notify-send")
+        .args(["-u", urgency, "kintai", text])
+        .status()
+        .map_err(|e| anyhow::anyhow!("failed to run notify-send: {e}"))?;
+    Ok(())
+}
+
+/// Prints the current state, or with `notify` shows it as a desktop
+/// notification (used by the reminder timer). Warns when today's time is
+/// over `max_daily_hours`.
+pub fn status(log: &Path, user: Option<&str>, notify: bool, quiet: bool) -> anyhow::Result<()> {
+    if quiet {
+        // 1 is taken by "on break", so errors exit with 3.
+        let code = match load(log, user) {
+            Ok(st) => st.phase.exit_code(),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                3
+            }
+        };
+        std::process::exit(code);
+    }
+    let now = now();
+    let st = load(log, user)?;
+    let mut text = st.describe(now);
+    let limit = Config::load()?
+        .get("max_daily_hours")
+        .and_then(|v| v.as_num());
+    let mut over = false;
+    if let Some(limit) = limit {
+        let secs = today_secs(log, user, &st, now)?;
+        if secs as f64 > limit * 3600.0 {
+            over = true;
+            text.push_str(&format!(
+                "\nOver the daily limit: {} worked today (max {limit}h). Time to stop!",
+                clock(secs)
+            ));
+        }
+    }
+    if notify {
+        desktop_notify(&text, over)?;
+    } else if over && std::io::stdout().is_terminal() {
+        println!("\x1b[1;31m{text}\x1b[0m");
+    } else {
+        println!("{text}");
+    }
+    Ok(())
+}
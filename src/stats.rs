@@ -0,0 +1,205 @@
+//! Figures for a month of work, compared side by side by `kintai compare`
+//! and across years by `kintai yoy`.
+
+use chrono::{Datelike, NaiveDate};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use crate::{
+    config::Config,
+    event::{filter_user, read_events},
+    overtime::daily_limit,
+    report::{delta_hm, format_hm},
+    session::{Session, build_sessions},
+    state,
+};
+
+#[derive(Default)]
+pub struct MonthStats {
+    pub minutes: i64,
+    pub days_worked: usize,
+    /// Hours beyond `overtime.daily_hours`, summed over the days.
+    pub overtime: f64,
+    pub break_minutes: i64,
+    pub salary: u64,
+}
+
+impl MonthStats {
+    pub fn hours(&self) -> f64 {
+        self.minutes as f64 / 60.0
+    }
+
+    /// Share of the time at work spent on breaks.
+    pub fn break_ratio(&self) -> f64 {
+        let total = self.minutes + self.break_minutes;
+        if total == 0 {
+            0.0
+        } else {
+            self.break_minutes as f64 / total as f64
+        }
+    }
+
+    /// Average hours per day worked.
+    pub fn average_day(&self) -> f64 {
+        if self.days_worked == 0 {
+            0.0
+        } else {
+            self.hours() / self.days_worked as f64
+        }
+    }
+}
+
+/// The figures of `month` (`YYYY-MM`), with overtime beyond `limit` hours a
+/// day and the salary at `rate`.
+pub fn month_stats(sessions: &[Session], month: &str, limit: f64, rate: f64) -> MonthStats {
+    let prefix = month.replace('-', "/");
+    let sessions: Vec<&Session> = sessions
+        .iter()
+        .filter(|s| s.date.starts_with(&prefix))
+        .collect();
+    let minutes: i64 = sessions.iter().map(|s| s.minutes()).sum();
+    let mut by_day: BTreeMap<&str, f64> = BTreeMap::new();
+    for s in &sessions {
+        *by_day.entry(&s.date).or_default() += s.minutes() as f64 / 60.0;
+    }
+    MonthStats {
+        minutes,
+        days_worked: by_day.len(),
+        overtime: by_day.values().map(|h| (h - limit).max(0.0)).sum(),
+        break_minutes: sessions.iter().flat_map(|s| s.break_minutes.values()).sum(),
+        salary: (minutes as f64 / 60.0 * rate).round() as u64,
+    }
+}
+
+pub fn parse_month(month: &str) -> anyhow::Result<()> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("expected a month as YYYY-MM, got {month:?}"))?;
+    Ok(())
+}
+
+/// `kintai compare`: two months side by side, with the change from the
+/// first to the second.
+pub fn compare(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    a: &str,
+    b: &str,
+    rate: Option<f64>,
+) -> anyhow::Result<()> {
+    parse_month(a)?;
+    parse_month(b)?;
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let config = Config::load()?;
+    let limit = daily_limit(&config);
+    let rate = rate.unwrap_or(0.0);
+    let (x, y) = (
+        month_stats(&sessions, a, limit, rate),
+        month_stats(&sessions, b, limit, rate),
+    );
+
+    let mut out = String::new();
+    writeln!(out, "| | {a} | {b} | change |").unwrap();
+    writeln!(out, "|-|---------|---------|--------|").unwrap();
+    writeln!(
+        out,
+        "| hours | {} | {} | {} |",
+        format_hm(x.hours()),
+        format_hm(y.hours()),
+        delta_hm(y.hours() - x.hours())
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| days worked | {} | {} | {:+} |",
+        x.days_worked,
+        y.days_worked,
+        y.days_worked as i64 - x.days_worked as i64
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| average day | {} | {} | {} |",
+        format_hm(x.average_day()),
+        format_hm(y.average_day()),
+        delta_hm(y.average_day() - x.average_day())
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| overtime (over {limit}h/day) | {} | {} | {} |",
+        format_hm(x.overtime),
+        format_hm(y.overtime),
+        delta_hm(y.overtime - x.overtime)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| break ratio | {:.1}% | {:.1}% | {:+.1}pt |",
+        x.break_ratio() * 100.0,
+        y.break_ratio() * 100.0,
+        (y.break_ratio() - x.break_ratio()) * 100.0
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| salary | {} | {} | {:+} |",
+        x.salary,
+        y.salary,
+        y.salary as i64 - x.salary as i64
+    )
+    .unwrap();
+    println!("{out}");
+    Ok(())
+}
+
+/// `kintai yoy`: one calendar month (`MM`, default this month) in every year
+/// of the log, with the change in hours from the year before.
+pub fn year_over_year(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: Option<u32>,
+) -> anyhow::Result<()> {
+    let month = month.unwrap_or_else(|| state::now().month());
+    if !(1..=12).contains(&month) {
+        anyhow::bail!("expected a month from 1 to 12, got {month}");
+    }
+    let sessions = build_sessions(filter_user(read_events(input)?, user));
+    let limit = daily_limit(&Config::load()?);
+    let years: BTreeSet<&str> = sessions.iter().map(|s| &s.date[..4]).collect();
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "| month | hours | days worked | average day | overtime | change |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|-------|-------|-------------|-------------|----------|--------|"
+    )
+    .unwrap();
+    let mut previous: Option<f64> = None;
+    for year in years {
+        let label = format!("{year}-{month:02}");
+        let s = month_stats(&sessions, &label, limit, 0.0);
+        if s.days_worked == 0 {
+            continue;
+        }
+        let change = previous.map_or("-".to_string(), |p| delta_hm(s.hours() - p));
+        writeln!(
+            out,
+            "| {label} | {} | {} | {} | {} | {change} |",
+            format_hm(s.hours()),
+            s.days_worked,
+            format_hm(s.average_day()),
+            format_hm(s.overtime)
+        )
+        .unwrap();
+        previous = Some(s.hours());
+    }
+    println!("{out}");
+    Ok(())
+}
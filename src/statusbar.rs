@@ -0,0 +1,101 @@
+//! `kintai statusbar`: the tracking state as the JSON a status bar's custom
+//! module reads, so adding kintai to a bar is one line of config. The
+//! `raycast` style lists the state and the actions that fit it for launchers.
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::path::Path;
+
+use crate::{
+    hours::{self, Period},
+    report::format_hm,
+    state::{self, Phase, clock},
+};
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Style {
+    /// Waybar `custom` module with `"return-type": "json"`
+    #[default]
+    Waybar,
+    /// i3blocks with `format=json` (an i3bar block)
+    I3blocks,
+    /// Raycast or Alfred script filter: the state plus clock-in/out actions
+    #[value(alias = "alfred")]
+    Raycast,
+}
+
+/// The commands that make sense in `phase`, as `(title, subcommand)`.
+fn actions(phase: Phase) -> &'static [(&'static str, &'static str)] {
+    match phase {
+        Phase::Idle => &[("Clock in", "start")],
+        Phase::Working => &[("Start a break", "break-start"), ("Clock out", "finish")],
+        Phase::OnBreak => &[("End the break", "break-end"), ("Clock out", "finish")],
+    }
+}
+
+/// Script-filter items: the state (not actionable), then one item per
+/// action whose `arg` is the kintai subcommand to run.
+fn launcher_items(state: &str, today: &str, phase: Phase) -> Value {
+    let mut items = vec![json!({
+        "uid": "status",
+        "title": state,
+        "subtitle": format!("{today} today"),
+        "valid": false,
+    })];
+    items.extend(actions(phase).iter().map(|(title, cmd)| {
+        json!({
+            "uid": cmd,
+            "title": title,
+            "subtitle": format!("kintai {cmd}"),
+            "arg": cmd,
+        })
+    }));
+    json!({ "items": items })
+}
+
+/// i3bar colour of each phase; working keeps the bar's own colour.
+fn color(phase: Phase) -> Option<&'static str> {
+    match phase {
+        Phase::Working => None,
+        Phase::OnBreak => Some("#e5c07b"),
+        Phase::Idle => Some("#7f848e"),
+    }
+}
+
+/// Prints one JSON line for `style`. The text is the `prompt` segment, empty
+/// while clocked out so the module collapses; the tooltip adds today's total.
+pub fn print(log: &Path, user: Option<&str>, style: Style, icon: &str) -> anyhow::Result<()> {
+    let now = state::now();
+    let st = state::load(log, user)?;
+    let worked = clock(st.worked_secs(now));
+    let text = match st.phase {
+        Phase::Idle => String::new(),
+        Phase::Working => format!("{icon} {worked}"),
+        Phase::OnBreak => format!("{icon} {worked} (break)"),
+    };
+    let today = format_hm(hours::total(log, user, Period::Today)? as f64 / 3600.0);
+    let tooltip = format!("{}\n{today} today", st.describe(now));
+    let doc = match style {
+        Style::Waybar => json!({
+            "text": text,
+            "alt": st.phase.as_str(),
+            "tooltip": tooltip,
+            "class": st.phase.as_str(),
+        }),
+        Style::I3blocks => {
+            let mut block = json!({
+                "full_text": text,
+                "short_text": if st.phase == Phase::Idle { "" } else { &worked },
+                "name": "kintai",
+                "instance": st.phase.as_str(),
+            });
+            if let Some(c) = color(st.phase) {
+                block["color"] = Value::from(c);
+            }
+            block
+        }
+        Style::Raycast => launcher_items(&st.describe(now), &today, st.phase),
+    };
+    println!("{doc}");
+    Ok(())
+}
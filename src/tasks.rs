@@ -0,0 +1,106 @@
+//! `kintai tasks`: hours per recurring task, grouped by the session's
+//! description (normalised, so "Design review" and "design  review." count
+//! together) or by the issue ID it mentions.
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use crate::{
+    event::{filter_user, read_events},
+    report::format_hm,
+    session::{Session, build_sessions},
+};
+
+/// How sessions are grouped into tasks.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TaskBy {
+    /// The normalised description
+    Content,
+    /// The first issue ID in the description (`issues.pattern`)
+    Ticket,
+}
+
+/// Lowercased, with runs of whitespace collapsed and trailing punctuation
+/// dropped.
+fn normalize(content: &str) -> String {
+    content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', ',', ';', ':', '。', '、'])
+        .to_lowercase()
+}
+
+fn task_key(s: &Session, by: TaskBy) -> String {
+    let key = match by {
+        TaskBy::Content => normalize(s.content.as_deref().unwrap_or_default()),
+        TaskBy::Ticket => s.refs.first().cloned().unwrap_or_default(),
+    };
+    if key.is_empty() { "-".to_string() } else { key }
+}
+
+/// Hours, sessions and days per task, largest first, with each task's
+/// share of the total.
+pub fn task_table(sessions: &[Session], by: TaskBy) -> String {
+    let mut tasks: BTreeMap<String, (i64, usize, BTreeSet<&str>)> = BTreeMap::new();
+    for s in sessions {
+        let (minutes, count, days) = tasks.entry(task_key(s, by)).or_default();
+        *minutes += s.minutes();
+        *count += 1;
+        days.insert(&s.date);
+    }
+    let total: i64 = tasks.values().map(|t| t.0).sum();
+    let mut rows: Vec<_> = tasks.into_iter().collect();
+    rows.sort_by(|a, b| b.1.0.cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+
+    let mut out = String::new();
+    writeln!(out, "| task | sessions | days | hours | share |").unwrap();
+    writeln!(out, "|------|----------|------|-------|-------|").unwrap();
+    for (task, (minutes, count, days)) in rows {
+        let share = if total > 0 {
+            minutes as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "| {task} | {count} | {} | {} | {share:.0}% |",
+            days.len(),
+            format_hm(minutes as f64 / 60.0)
+        )
+        .unwrap();
+    }
+    writeln!(out, "| total | | | {} | |", format_hm(total as f64 / 60.0)).unwrap();
+    out
+}
+
+/// `YYYY-MM-DD` as the `YYYY/MM/DD` of session dates.
+fn session_date(day: &str) -> anyhow::Result<String> {
+    let d = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("invalid date {day:?} (expected YYYY-MM-DD)"))?;
+    Ok(d.format("%Y/%m/%d").to_string())
+}
+
+pub fn report(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+    by: TaskBy,
+) -> anyhow::Result<()> {
+    let from = from.map(session_date).transpose()?;
+    let to = to.map(session_date).transpose()?;
+
+    let sessions: Vec<Session> = build_sessions(filter_user(read_events(input)?, user))
+        .into_iter()
+        .filter(|s| from.as_ref().is_none_or(|f| &s.date >= f))
+        .filter(|s| to.as_ref().is_none_or(|t| &s.date <= t))
+        .collect();
+    println!("{}", task_table(&sessions, by));
+    Ok(())
+}
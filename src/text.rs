@@ -0,0 +1,34 @@
+//! Cleaning up text typed into the log, so the same description typed on
+//! macOS (which often produces decomposed characters) and on Linux compares
+//! equal: text is normalized to Unicode NFC, and control and invisible
+//! zero-width characters are removed.
+
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters dropped from text: control characters other than line breaks,
+/// and zero-width spaces, word joiners and byte order marks. Zero-width
+/// joiners, which emoji sequences and some scripts need, are kept.
+fn is_dropped(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t') || matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// `s` in NFC and with invisible characters removed; tabs become spaces
+/// and `\r\n` becomes `\n`. Plain ASCII without control characters is
+/// returned as is.
+pub fn clean(s: &str) -> Cow<'_, str> {
+    if s.bytes().all(|b| (0x20..0x7F).contains(&b)) {
+        return Cow::Borrowed(s);
+    }
+    let out: String = s
+        .chars()
+        .filter(|&c| !is_dropped(c))
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .nfc()
+        .collect();
+    if out == s {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(out)
+    }
+}
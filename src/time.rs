@@ -0,0 +1,81 @@
+//! The current time and times typed by the user. The current time comes
+//! from a [`Clock`]: the system clock, or with `--now` (`KINTAI_NOW`) a
+//! fixed time, so scripts and tests can record reproducible logs.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone};
+use std::sync::OnceLock;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<FixedOffset>;
+
+    /// Whether `now` never changes, in which case event IDs are derived from
+    /// it instead of drawn at random.
+    fn is_fixed(&self) -> bool {
+        false
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+}
+
+/// Always the same time, given with `--now`.
+pub struct FixedClock(pub DateTime<FixedOffset>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.0
+    }
+
+    fn is_fixed(&self) -> bool {
+        true
+    }
+}
+
+static CLOCK: OnceLock<Box<dyn Clock>> = OnceLock::new();
+
+/// Sets where the current time comes from; call before anything is recorded.
+pub fn set_clock(clock: Box<dyn Clock>) {
+    let _ = CLOCK.set(clock);
+}
+
+pub fn clock() -> &'static dyn Clock {
+    CLOCK.get_or_init(|| Box::new(SystemClock)).as_ref()
+}
+
+/// Parses a user-supplied time: RFC 3339, `YYYY-MM-DD HH:MM[:SS]` in local
+/// time, or a bare `HH:MM[:SS]` on the same day as `base`.
+pub fn parse_when(s: &str, base: DateTime<FixedOffset>) -> anyhow::Result<DateTime<FixedOffset>> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt);
+    }
+    for fmt in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.fixed_offset())
+                .ok_or_else(|| anyhow::anyhow!("{s:?} does not exist in the local time zone"));
+        }
+    }
+    for fmt in ["%H:%M", "%H:%M:%S"] {
+        if let Ok(t) = NaiveTime::parse_from_str(s, fmt) {
+            return base
+                .offset()
+                .from_local_datetime(&base.date_naive().and_time(t))
+                .single()
+                .ok_or_else(|| anyhow::anyhow!("invalid time {s:?}"));
+        }
+    }
+    anyhow::bail!("cannot parse time {s:?}; use HH:MM, \"YYYY-MM-DD HH:MM\" or RFC 3339")
+}
@@ -0,0 +1,59 @@
+//! System tray icon for the daemon (feature `tray`).
+//!
+//! Drives `yad --notification --listen`, which is available on most Linux
+//! desktops and needs no native bindings: menu entries run the kintai CLI
+//! against the daemon's log, and the tooltip is refreshed from daemon state.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    daemon::Daemon,
+    state::{self, Phase},
+};
+
+pub fn spawn(log: &Path, daemon: Arc<Mutex<Daemon>>) -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let base = format!("{} --log '{}'", exe.display(), log.display());
+    let menu = format!(
+        "Start!{base} start|Break!{base} break-start|Resume!{base} break-end|Finish!{base} finish"
+    );
+    let mut child = Command::new("yad")
+        .args(["--notification", "--listen", "--image=appointment-soon"])
+        .arg(format!("--menu={menu}"))
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to start yad for the tray icon: {e}"))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+
+    thread::spawn(move || {
+        loop {
+            let line = match daemon.lock().unwrap().refresh() {
+                Ok(st) => {
+                    let icon = match st.phase {
+                        Phase::Idle => "appointment-missed",
+                        Phase::Working => "appointment-soon",
+                        Phase::OnBreak => "appointment-new",
+                    };
+                    format!(
+                        "icon:{icon}\ntooltip:kintai: {}\n",
+                        st.describe(state::now())
+                    )
+                }
+                Err(e) => format!("tooltip:kintai: {e}\n"),
+            };
+            if stdin.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_secs(30));
+        }
+        let _ = child.wait();
+    });
+    Ok(())
+}
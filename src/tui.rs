@@ -0,0 +1,307 @@
+//! `kintai tui`: a full-screen dashboard of the worked days of a month.
+//! Opening a day lists its sessions, which can be corrected in place: their
+//! times and breaks, a new break, or the description. Changes are recorded
+//! as correction events, as with `kintai fix`.
+
+use chrono::{Months, NaiveDate};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, IsTerminal, Write},
+    path::Path,
+};
+
+use crate::{
+    event::{filter_user, read_log},
+    fix::{self, RawSession, when},
+    history,
+    report::format_hm,
+    state,
+    stats::parse_month,
+};
+
+/// Puts the terminal into raw mode on the alternate screen until dropped.
+struct Terminal {
+    saved: libc::termios,
+}
+
+impl Terminal {
+    fn raw() -> anyhow::Result<Terminal> {
+        let mut saved = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            anyhow::bail!("cannot read the terminal settings");
+        }
+        let term = Terminal { saved };
+        term.resume();
+        Ok(term)
+    }
+
+    /// Back to line input, for prompts.
+    fn suspend(&self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+        print!("\x1b[?25h\x1b[2J\x1b[H");
+        let _ = io::stdout().flush();
+    }
+
+    fn resume(&self) {
+        let mut raw = self.saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Back,
+    Char(u8),
+}
+
+/// Waits for a key press. Arrow keys arrive as one escape sequence.
+fn read_key() -> anyhow::Result<Key> {
+    let mut buf = [0u8; 8];
+    let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+    if n <= 0 {
+        return Ok(Key::Back);
+    }
+    Ok(match &buf[..n as usize] {
+        b"\x1b[A" | b"k" => Key::Up,
+        b"\x1b[B" | b"j" => Key::Down,
+        b"\x1b[D" | b"h" => Key::Left,
+        b"\x1b[C" | b"l" => Key::Right,
+        b"\r" | b"\n" => Key::Enter,
+        // Escape, q, Ctrl-C and Ctrl-D.
+        b"\x1b" | b"q" | b"\x03" | b"\x04" => Key::Back,
+        [c, ..] => Key::Char(*c),
+        [] => Key::Back,
+    })
+}
+
+fn date(s: &RawSession) -> String {
+    when(&s.start).format("%Y/%m/%d").to_string()
+}
+
+fn time_range(s: &RawSession) -> String {
+    let end = s.finish.as_ref().map_or_else(
+        || "running".to_string(),
+        |f| when(f).format("%H:%M").to_string(),
+    );
+    format!("{}~{end}", when(&s.start).format("%H:%M"))
+}
+
+fn content(s: &RawSession) -> &str {
+    s.finish
+        .as_ref()
+        .and_then(|f| f.content.as_deref())
+        .unwrap_or_default()
+}
+
+/// Sessions per day of `month` (`YYYY-MM`).
+fn days<'a>(sessions: &'a [RawSession], month: &str) -> BTreeMap<String, Vec<&'a RawSession>> {
+    let mut days: BTreeMap<String, Vec<&RawSession>> = BTreeMap::new();
+    for s in sessions {
+        let d = date(s);
+        if d.replace('/', "-").starts_with(month) {
+            days.entry(d).or_default().push(s);
+        }
+    }
+    days
+}
+
+fn hours(sessions: &[&RawSession]) -> String {
+    format_hm(sessions.iter().map(|s| s.minutes()).sum::<i64>().max(0) as f64 / 60.0)
+}
+
+fn row(out: &mut String, selected: bool, text: &str) {
+    if selected {
+        writeln!(out, "\x1b[7m{text}\x1b[0m").unwrap();
+    } else {
+        writeln!(out, "{text}").unwrap();
+    }
+}
+
+fn month_screen(days: &BTreeMap<String, Vec<&RawSession>>, month: &str, selected: usize) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "kintai {month}   ←/→ month  ↑/↓ day  Enter open  q quit\n"
+    )
+    .unwrap();
+    if days.is_empty() {
+        writeln!(out, "No sessions.").unwrap();
+    }
+    for (i, (date, sessions)) in days.iter().enumerate() {
+        let ranges: Vec<String> = sessions.iter().map(|s| time_range(s)).collect();
+        let contents: Vec<&str> = sessions
+            .iter()
+            .map(|s| content(s))
+            .filter(|c| !c.is_empty())
+            .collect();
+        let text = format!(
+            "{date}  {:>7}  {:<24}  {}",
+            hours(sessions),
+            ranges.join(","),
+            contents.join("; ")
+        );
+        row(&mut out, i == selected, &text);
+    }
+    let all: Vec<&RawSession> = days.values().flatten().copied().collect();
+    writeln!(out, "\nTotal: {}", hours(&all)).unwrap();
+    out
+}
+
+fn day_screen(date: &str, sessions: &[&RawSession], selected: usize) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "kintai {date}   ↑/↓ session  e edit  b add break  c content  q back\n"
+    )
+    .unwrap();
+    for (i, s) in sessions.iter().enumerate() {
+        let breaks: Vec<String> = s
+            .breaks
+            .iter()
+            .map(|(b, e)| {
+                let end = e
+                    .as_ref()
+                    .map(|e| when(e).format("%H:%M").to_string())
+                    .unwrap_or_default();
+                format!("{}~{end}", when(b).format("%H:%M"))
+            })
+            .collect();
+        let breaks = if breaks.is_empty() {
+            "no breaks".to_string()
+        } else {
+            format!("breaks {}", breaks.join(","))
+        };
+        let text = format!(
+            "{:<14}  {:>7}  {:<24}  {}",
+            time_range(s),
+            hours(&[s]),
+            breaks,
+            content(s)
+        );
+        row(&mut out, i == selected, &text);
+    }
+    out
+}
+
+/// `kintai tui`: browse the worked days of `month` (default: this month),
+/// open a day and correct its sessions.
+pub fn run(log: &Path, user: Option<&str>, month: Option<&str>, force: bool) -> anyhow::Result<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        anyhow::bail!("`kintai tui` needs a terminal");
+    }
+    let mut month = match month {
+        Some(m) => {
+            parse_month(m)?;
+            m.to_string()
+        }
+        None => state::now().format("%Y-%m").to_string(),
+    };
+    let load = || -> anyhow::Result<Vec<RawSession>> {
+        Ok(fix::raw_sessions(filter_user(read_log(log)?, user)))
+    };
+    let mut sessions = load()?;
+    let term = Terminal::raw()?;
+    let (mut day, mut day_row, mut session_row) = (None::<String>, 0, 0);
+    let mut message = String::new();
+    loop {
+        let by_day = days(&sessions, &month);
+        let mut screen = String::from("\x1b[2J\x1b[H");
+        match day.as_ref().and_then(|d| Some((d, by_day.get(d)?))) {
+            Some((d, list)) => screen.push_str(&day_screen(d, list, session_row)),
+            None => screen.push_str(&month_screen(&by_day, &month, day_row)),
+        }
+        if !message.is_empty() {
+            writeln!(screen, "\n{message}").unwrap();
+        }
+        print!("{screen}");
+        io::stdout().flush()?;
+        message.clear();
+
+        let key = read_key()?;
+        let Some(d) = day.clone() else {
+            match key {
+                Key::Up => day_row = day_row.saturating_sub(1),
+                Key::Down => day_row = (day_row + 1).min(by_day.len().saturating_sub(1)),
+                Key::Left | Key::Right => {
+                    let first = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")?;
+                    let first = match key {
+                        Key::Left => first - Months::new(1),
+                        _ => first + Months::new(1),
+                    };
+                    month = first.format("%Y-%m").to_string();
+                    day_row = 0;
+                }
+                Key::Enter => {
+                    day = by_day.keys().nth(day_row).cloned();
+                    session_row = 0;
+                }
+                Key::Back => return Ok(()),
+                Key::Char(_) => {}
+            }
+            continue;
+        };
+        let list = by_day.get(&d).cloned().unwrap_or_default();
+        let Some(&session) = list.get(session_row) else {
+            day = None;
+            continue;
+        };
+        let edit: fn(&RawSession) -> anyhow::Result<Vec<fix::Change>> = match key {
+            Key::Up => {
+                session_row = session_row.saturating_sub(1);
+                continue;
+            }
+            Key::Down => {
+                session_row = (session_row + 1).min(list.len() - 1);
+                continue;
+            }
+            Key::Back | Key::Left => {
+                day = None;
+                continue;
+            }
+            Key::Char(b'e') | Key::Enter => fix::edit,
+            Key::Char(b'b') => |s| Ok(fix::add_break(s)?.into_iter().collect()),
+            Key::Char(b'c') => {
+                if session.finish.is_none() {
+                    message = "A running session gets its content when it finishes.".to_string();
+                    continue;
+                }
+                |s| Ok(fix::edit_content(s)?.into_iter().collect())
+            }
+            _ => continue,
+        };
+        term.suspend();
+        println!("{}\n", session.line());
+        history::begin("tui".to_string());
+        let result = edit(session).and_then(|changes| {
+            if changes.is_empty() {
+                return Ok(false);
+            }
+            fix::apply(log, user, changes, force)
+        });
+        message = match result {
+            Ok(true) => "Saved.".to_string(),
+            Ok(false) => "Nothing changed.".to_string(),
+            Err(e) => format!("Error: {e}"),
+        };
+        term.resume();
+        sessions = load()?;
+    }
+}
@@ -0,0 +1,98 @@
+//! Work units for piecework (`kintai count 3 "translated pages"`), stored as
+//! `type=count` events with `count=` and the unit as content. A count
+//! belongs to the session it was recorded in, and is paid at the unit's
+//! rate under `[unit_rate]`.
+
+use chrono::DateTime;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use crate::{
+    config::Config,
+    correct::apply_corrections,
+    display::Formats,
+    event::{LogEvent, record},
+    session::Session,
+};
+
+pub struct UnitCount {
+    /// Date of the session the count belongs to (`YYYY/MM/DD`).
+    pub date: String,
+    pub count: f64,
+    pub unit: String,
+    pub project: Option<String>,
+}
+
+/// The counts in `events`, after corrections, each attached to the session
+/// of the same user it was recorded in, else to the last session started
+/// earlier that day.
+pub fn unit_counts(events: &[LogEvent], sessions: &[Session]) -> Vec<UnitCount> {
+    let mut out: Vec<UnitCount> = apply_corrections(events.to_vec())
+        .into_iter()
+        .filter(|e| e.ty == "count")
+        .filter_map(|e| {
+            let ts = DateTime::parse_from_rfc3339(&e.ts).ok()?;
+            let user = e.user().map(str::to_string);
+            let mut mine = sessions.iter().filter(|s| s.user == user && s.start <= ts);
+            let session = mine
+                .clone()
+                .find(|s| ts <= s.end())
+                .or_else(|| mine.rfind(|s| s.start.date_naive() == ts.date_naive()));
+            Some(UnitCount {
+                date: session.map_or_else(|| ts.format("%Y/%m/%d").to_string(), |s| s.date.clone()),
+                count: e.field("count")?.parse().ok()?,
+                unit: e.content.clone().unwrap_or_default(),
+                project: session.and_then(|s| s.project.clone()),
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.date.cmp(&b.date));
+    out
+}
+
+pub fn record_count(
+    log: Option<&Path>,
+    user: Option<&str>,
+    count: &str,
+    unit: String,
+    force: bool,
+) -> anyhow::Result<()> {
+    let value: f64 = count
+        .parse()
+        .map_err(|_| anyhow::anyhow!("count must be a number, got {count:?}"))?;
+    if !value.is_finite() {
+        anyhow::bail!("count must be a number, got {count:?}");
+    }
+    let unit = unit.trim().to_string();
+    if unit.is_empty() {
+        anyhow::bail!("name the unit counted, e.g. `kintai count 3 \"translated pages\"`");
+    }
+    record(log, user, "count", &[("count", count)], Some(unit), force)
+}
+
+/// Units per month, unit and project, with the pay at `[unit_rate]`.
+pub fn units_table(counts: &[UnitCount], config: &Config, formats: &Formats) -> String {
+    let mut totals: BTreeMap<(&str, &str, &str), f64> = BTreeMap::new();
+    for c in counts {
+        let project = c.project.as_deref().unwrap_or_default();
+        *totals.entry((&c.date[..7], &c.unit, project)).or_default() += c.count;
+    }
+    let mut out = String::new();
+    writeln!(out, "| month | unit | project | count | rate | amount |").unwrap();
+    writeln!(out, "|-------|------|---------|-------|------|--------|").unwrap();
+    for ((month, unit, project), count) in totals {
+        let rate = config
+            .get(&format!("unit_rate.{unit}"))
+            .and_then(|v| v.as_num());
+        let (rate, amount) = match rate {
+            Some(r) => (r.to_string(), ((count * r).round() as u64).to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        writeln!(
+            out,
+            "| {} | {unit} | {project} | {count} | {rate} | {amount} |",
+            formats.month(month)
+        )
+        .unwrap();
+    }
+    out
+}
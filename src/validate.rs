@@ -0,0 +1,56 @@
+//! `kintai validate`: checks the log for things that need fixing or
+//! attention, such as unreadable timestamps, working too long without a
+//! break or sessions going back in time.
+
+use chrono::DateTime;
+use std::path::PathBuf;
+
+use crate::{
+    config::Config,
+    correct::apply_corrections,
+    event::{filter_user, read_events},
+    session::build_sessions,
+    skew::{self, Skew},
+    state::max_continuous_hours,
+};
+
+pub fn validate(input: Option<PathBuf>, user: Option<&str>) -> anyhow::Result<()> {
+    let events = filter_user(read_events(input)?, user);
+    let limit = max_continuous_hours(&Config::load()?);
+    let mut problems: Vec<String> = events
+        .iter()
+        .filter(|e| DateTime::parse_from_rfc3339(&e.ts).is_err())
+        .map(|e| {
+            format!(
+                "{} event with unreadable time `{}`, left out of all reports",
+                e.ty, e.ts
+            )
+        })
+        .collect();
+    problems.extend(
+        skew::find(&apply_corrections(events.clone()))
+            .iter()
+            .map(Skew::describe),
+    );
+    for s in build_sessions(events) {
+        for (part, a, b) in s.parts() {
+            let minutes = b - a;
+            if minutes as f64 > limit * 60.0 {
+                problems.push(format!(
+                    "{} {part}: {}:{:02} without a break (limit {limit}h)",
+                    s.date,
+                    minutes / 60,
+                    minutes % 60
+                ));
+            }
+        }
+    }
+    for p in &problems {
+        println!("{p}");
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{} problems found", problems.len());
+    }
+    println!("No problems found.");
+    Ok(())
+}
@@ -0,0 +1,128 @@
+//! `--watch` for reports: re-render whenever the log changes. On Linux the
+//! log's directory is watched with inotify; elsewhere the log is checked
+//! every second.
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{input, state};
+
+/// Size and modification time of every file `path` names, to tell real
+/// changes from other activity in the directory.
+fn stamp(path: &Path) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    input::files(path)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|p| {
+            let meta = p.metadata().ok()?;
+            Some((p, meta.len(), meta.modified().ok()))
+        })
+        .collect()
+}
+
+/// The directory whose changes matter: `path` itself if it is one, else
+/// the directory it (or its pattern) sits in.
+fn watched_dir(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        return path.to_path_buf();
+    }
+    match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => PathBuf::from("."),
+        Some(p) => p.to_path_buf(),
+        None => PathBuf::from("."),
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct Notify {
+    fd: libc::c_int,
+}
+
+#[cfg(target_os = "linux")]
+impl Notify {
+    fn new(dir: &Path) -> Option<Notify> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+        let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let mask = libc::IN_MODIFY
+            | libc::IN_CLOSE_WRITE
+            | libc::IN_CREATE
+            | libc::IN_DELETE
+            | libc::IN_MOVED_TO;
+        if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            unsafe { libc::close(fd) };
+            return None;
+        }
+        Some(Notify { fd })
+    }
+
+    /// Blocks until something in the directory changes.
+    fn wait(&self) {
+        let mut buf = [0u8; 4096];
+        unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Notify {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Notify;
+
+#[cfg(not(target_os = "linux"))]
+impl Notify {
+    fn new(_: &Path) -> Option<Notify> {
+        None
+    }
+
+    fn wait(&self) {}
+}
+
+/// Prints `render()` now and again after every change to `path`, until
+/// interrupted. On a terminal the screen is cleared first. A report that
+/// fails, e.g. on a half-written line, shows the error until the next change.
+pub fn watch(
+    path: &Path,
+    mut render: impl FnMut() -> anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let notify = Notify::new(&watched_dir(path));
+    let clear = std::io::stdout().is_terminal();
+    let mut last = None;
+    loop {
+        let now = stamp(path);
+        if last.as_ref() != Some(&now) {
+            if clear {
+                print!("\x1b[2J\x1b[H");
+            }
+            match render() {
+                Ok(report) => print!("{report}"),
+                Err(e) => println!("Error: {e}"),
+            }
+            println!(
+                "Watching {} (updated {}; Ctrl-C to stop)",
+                path.display(),
+                state::now().format("%H:%M:%S")
+            );
+            last = Some(now);
+        }
+        match &notify {
+            Some(n) => {
+                n.wait();
+                // Let a burst of writes settle before reading the log.
+                thread::sleep(Duration::from_millis(200));
+            }
+            None => thread::sleep(Duration::from_secs(1)),
+        }
+    }
+}
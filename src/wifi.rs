@@ -0,0 +1,145 @@
+//! Wi-Fi rules for the daemon: start work when joining a known network and
+//! take a break after leaving it for a while, e.g.
+//!
+//! ```toml
+//! [wifi.office]
+//! ssid = "OfficeWiFi"
+//! hours = "08:00-20:00"
+//! break_after = 15
+//! ```
+//!
+//! Connecting to `ssid` within `hours` while clocked out starts a session;
+//! being away from it for more than `break_after` minutes while working
+//! starts a break, which reconnecting ends again. Every event recorded here
+//! carries `auto=wifi`.
+
+use chrono::NaiveTime;
+use std::{
+    process::Command,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    daemon::Daemon,
+    flex::parse_window,
+    state::{self, Phase},
+};
+
+const POLL: Duration = Duration::from_secs(30);
+
+pub struct Rule {
+    name: String,
+    ssid: String,
+    /// When connecting starts a session (any time if unset).
+    hours: Option<(NaiveTime, NaiveTime)>,
+    /// Minutes away from `ssid` before a break starts (never if unset).
+    break_after: Option<i64>,
+}
+
+/// The `[wifi.<name>]` rules of the config.
+pub fn rules(config: &Config) -> anyhow::Result<Vec<Rule>> {
+    let mut rules = Vec::new();
+    for name in config.sections("wifi") {
+        let key = |k: &str| format!("wifi.{name}.{k}");
+        let ssid = config
+            .str(&key("ssid"))
+            .ok_or_else(|| anyhow::anyhow!("wifi.{name}: missing `ssid`"))?;
+        let hours = config
+            .str(&key("hours"))
+            .map(parse_window)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("wifi.{name}.hours: {e}"))?;
+        rules.push(Rule {
+            name: name.to_string(),
+            ssid: ssid.to_string(),
+            hours,
+            break_after: config
+                .get(&key("break_after"))
+                .and_then(|v| v.as_num())
+                .map(|m| m as i64),
+        });
+    }
+    Ok(rules)
+}
+
+/// SSID of the Wi-Fi network currently connected to, if any: the output of
+/// `wifi.command` when configured, else `iwgetid -r`, else NetworkManager's
+/// active connection.
+fn current_ssid(command: Option<&str>) -> Option<String> {
+    let run = |cmd: &mut Command| {
+        let out = cmd.output().ok().filter(|o| o.status.success())?;
+        Some(String::from_utf8_lossy(&out.stdout).into_owned())
+    };
+    let ssid = match command {
+        Some(c) => run(Command::new("sh").arg("-c").arg(c))?,
+        None => match run(Command::new("iwgetid").arg("-r")) {
+            Some(ssid) => ssid,
+            None => run(Command::new("nmcli").args(["-t", "-f", "active,ssid", "dev", "wifi"]))?
+                .lines()
+                .find_map(|l| l.strip_prefix("yes:"))?
+                .to_string(),
+        },
+    };
+    let ssid = ssid.trim();
+    (!ssid.is_empty()).then(|| ssid.to_string())
+}
+
+/// Polls the connected network and applies `rules`.
+pub fn spawn(rules: Vec<Rule>, command: Option<String>, daemon: Arc<Mutex<Daemon>>) {
+    if rules.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        // Per rule: connected at the last poll, and polls spent away since
+        // leaving the network (`None` until it is joined, and again once a
+        // break has been taken for the absence).
+        let mut connected = vec![false; rules.len()];
+        let mut away: Vec<Option<i64>> = vec![None; rules.len()];
+        loop {
+            let ssid = current_ssid(command.as_deref());
+            let now = state::now();
+            for (i, rule) in rules.iter().enumerate() {
+                let here = ssid.as_deref() == Some(rule.ssid.as_str());
+                let joined = here && !connected[i];
+                connected[i] = here;
+                away[i] = if here {
+                    Some(0)
+                } else {
+                    away[i].map(|n| n + 1)
+                };
+
+                let mut d = daemon.lock().unwrap();
+                let phase = match d.refresh() {
+                    Ok(st) => st.phase,
+                    Err(e) => {
+                        eprintln!("kintai daemon: wifi.{}: {e}", rule.name);
+                        continue;
+                    }
+                };
+                let in_hours = rule.hours.is_none_or(|(a, b)| (a..b).contains(&now.time()));
+                let away_minutes = away[i].map(|n| n * POLL.as_secs() as i64 / 60);
+                let gone = rule
+                    .break_after
+                    .is_some_and(|m| away_minutes.is_some_and(|a| a > m));
+                let (result, what) = match phase {
+                    Phase::Idle if joined && in_hours => (d.auto_start("wifi"), "started work"),
+                    Phase::OnBreak if here => (d.auto_break(false, "wifi"), "ended the break"),
+                    Phase::Working if gone => {
+                        away[i] = None;
+                        (d.auto_break(true, "wifi"), "started a break")
+                    }
+                    _ => (Ok(false), ""),
+                };
+                match result {
+                    Ok(true) => eprintln!("kintai daemon: wifi.{}: {what}", rule.name),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("kintai daemon: wifi.{}: {e}", rule.name),
+                }
+            }
+            thread::sleep(POLL);
+        }
+    });
+}
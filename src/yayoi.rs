@@ -0,0 +1,129 @@
+//! The attendance CSV that 弥生給与 imports (`kintai export yayoi`): one row
+//! per employee with days worked, paid leave, and regular, overtime,
+//! late-night overtime and holiday hours, split as in `close-report` (see
+//! [`payroll`](crate::payroll)).
+//!
+//! 弥生給与 reads Shift_JIS, so the file is written in it. Employees are
+//! matched by code, taken from `[yayoi.codes]` (user name = code) and
+//! otherwise the user name itself; a log without users is one employee,
+//! whose code is `excel.header.employee_id`.
+
+use encoding_rs::SHIFT_JIS;
+use std::{fmt::Write, fs, path::PathBuf};
+
+use crate::{
+    config::Config,
+    event::{filter_user, read_events},
+    payroll::{Breakdown, Limits, breakdown},
+    session::{build_sessions, users},
+    stats::parse_month,
+};
+
+const HEADER: [&str; 8] = [
+    "社員コード",
+    "氏名",
+    "出勤日数",
+    "有休日数",
+    "出勤時間",
+    "普通残業時間",
+    "深夜残業時間",
+    "休日出勤時間",
+];
+
+/// Hours as `H:MM`, the time format of the import.
+fn hours(h: f64) -> String {
+    let minutes = (h * 60.0).round() as i64;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// `field` quoted when it holds a comma, quote or line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn row(code: &str, name: &str, b: &Breakdown) -> String {
+    [
+        quote(code),
+        quote(name),
+        b.days_worked.to_string(),
+        b.leave_days.to_string(),
+        hours(b.regular),
+        // Late-night overtime has a column of its own, so it is left out here.
+        hours(b.overtime - b.late_night_overtime),
+        hours(b.late_night_overtime),
+        hours(b.holiday),
+    ]
+    .join(",")
+}
+
+/// Writes the CSV for `month` (`YYYY-MM`) to `output`, by default
+/// `yayoi-YYYY-MM.csv`.
+pub fn export(
+    input: Option<PathBuf>,
+    user: Option<&str>,
+    month: &str,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    parse_month(month)?;
+    let events = filter_user(read_events(input)?, user);
+    let people: Vec<String> = users(&build_sessions(events.clone()))
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let config = Config::load()?;
+    let limits = Limits::load(&config)?;
+
+    let mut csv = HEADER.join(",");
+    csv.push_str("\r\n");
+    if people.is_empty() {
+        let code = config.str("excel.header.employee_id").unwrap_or("");
+        let name = config.str("excel.header.employee").unwrap_or("");
+        let b = breakdown(&events, month, &limits);
+        write!(csv, "{}\r\n", row(code, name, &b)).unwrap();
+    }
+    for person in &people {
+        let code = config
+            .str(&format!("yayoi.codes.{person}"))
+            .unwrap_or(person);
+        let b = breakdown(&filter_user(events.clone(), Some(person)), month, &limits);
+        write!(csv, "{}\r\n", row(code, person, &b)).unwrap();
+    }
+
+    let (bytes, _, unmappable) = SHIFT_JIS.encode(&csv);
+    if unmappable {
+        eprintln!("warning: characters without a Shift_JIS form were written as HTML entities");
+    }
+    let path = output.unwrap_or_else(|| PathBuf::from(format!("yayoi-{month}.csv")));
+    fs::write(&path, bytes)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event::parse_line, payroll::Limits};
+    use chrono::Weekday;
+
+    #[test]
+    fn keeps_late_night_overtime_out_of_regular_overtime() {
+        // 13:00 to 24:00: 8 hours, then 21:00-22:00 overtime and 22:00-24:00
+        // late-night overtime.
+        let events = [
+            "ts=2026-04-06T13:00:00+09:00 type=start",
+            "ts=2026-04-07T00:00:00+09:00 type=finish",
+        ]
+        .map(|l| parse_line(l).unwrap());
+        let limits = Limits {
+            daily: 8.0,
+            weekly: 40.0,
+            week_start: Weekday::Sun,
+        };
+        let b = breakdown(&events, "2026-04", &limits);
+        assert_eq!(row("1", "山田", &b), "1,山田,1,0,8:00,1:00,2:00,0:00");
+    }
+}